@@ -0,0 +1,130 @@
+//! Bus cycles recorded with the 65816's VDA/VPA/VPB outputs, see `Bus::cycle_log`.
+//! Meant for checking the CPU against cycle-level test suites.
+//!
+//! VPB is the real signal, raised while the core pulls an interrupt vector. The
+//! instructions don't report what kind of access they make though, so VDA and VPA
+//! are inferred: reads of the current instruction's bytes are program fetches (VPA,
+//! plus VDA on the opcode) and everything else is a data access (VDA). That's wrong
+//! for things like a data read that happens to hit the instruction's own operand, so
+//! they are only informative and the test runner doesn't compare them.
+use crate::cpu::disasm::{self, WidthState};
+use crate::cpu::registers::Registers;
+
+use super::memory_access::AccessKind;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BusCycle {
+    pub address: u32,
+    pub value: u8,
+    /// Read or Write
+    pub kind: AccessKind,
+    /// Valid data address, set for data accesses and opcode fetches (inferred)
+    pub vda: bool,
+    /// Valid program address, set for opcode and operand fetches (inferred)
+    pub vpa: bool,
+    /// Vector pull, set while reading an interrupt vector
+    pub vpb: bool,
+}
+
+/// Bytes of the instruction currently executing, reads from them are program fetches
+#[derive(Copy, Clone)]
+struct Instruction {
+    address: u32,
+    length: u8,
+}
+
+pub struct BusCycleLog {
+    pub cycles: Vec<BusCycle>,
+    instruction: Option<Instruction>,
+    is_vector_pull: bool,
+}
+
+impl BusCycleLog {
+    pub fn new() -> Self {
+        Self {
+            cycles: vec![],
+            instruction: None,
+            is_vector_pull: false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cycles.clear();
+        self.instruction = None;
+        self.is_vector_pull = false;
+    }
+
+    /// Sets the instruction about to run at PC
+    pub fn begin_instruction(&mut self, registers: &Registers, opcode: u8) {
+        let (_, mode) = disasm::opcode_info(opcode);
+        let state = WidthState::from_registers(registers);
+        self.instruction = Some(Instruction {
+            address: registers.get_pc_address(),
+            length: 1 + mode.operand_length(&state),
+        });
+    }
+
+    pub fn set_vector_pull(&mut self, is_vector_pull: bool) {
+        self.is_vector_pull = is_vector_pull;
+    }
+
+    pub fn record(&mut self, kind: AccessKind, address: u32, value: u8) {
+        // Instructions wrap around within their bank
+        let offset = self.instruction
+            .filter(|instruction| (address & 0xFF0000) == (instruction.address & 0xFF0000))
+            .map(|instruction| ((address as u16).wrapping_sub(instruction.address as u16), instruction.length as u16))
+            .filter(|(offset, length)| offset < length)
+            .map(|(offset, _)| offset);
+        let is_program = kind == AccessKind::Read && !self.is_vector_pull && offset.is_some();
+        self.cycles.push(BusCycle {
+            address,
+            value,
+            kind,
+            vda: !is_program || offset == Some(0),
+            vpa: is_program,
+            vpb: self.is_vector_pull,
+        });
+    }
+}
+
+impl Default for BusCycleLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod common_bus_cycle_tests {
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        let mut log = BusCycleLog::new();
+        let mut registers = Registers::new();
+        registers.pbr = 0x01;
+        registers.pc = 0xFFFF;
+        // LDA $1234 wrapping around the bank
+        log.begin_instruction(&registers, 0xAD);
+        log.record(AccessKind::Read, 0x01FFFF, 0xAD);
+        log.record(AccessKind::Read, 0x010000, 0x34);
+        log.record(AccessKind::Read, 0x010001, 0x12);
+        log.record(AccessKind::Read, 0x001234, 0x56);
+        log.set_vector_pull(true);
+        log.record(AccessKind::Read, 0x00FFE6, 0x00);
+        log.set_vector_pull(false);
+        log.record(AccessKind::Write, 0x010000, 0x00);
+
+        let flags: Vec<(bool, bool, bool)> = log.cycles.iter().map(|c| (c.vda, c.vpa, c.vpb)).collect();
+        assert_eq!(flags, vec![
+            (true, true, false),
+            (false, true, false),
+            (false, true, false),
+            (true, false, false),
+            (true, false, true),
+            (true, false, false),
+        ]);
+        log.clear();
+        assert!(log.cycles.is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+/// Memory accesses recorded while debugging, see `Bus::access_log`
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MemorySpace {
+    /// CPU address bus (A-bus and B-bus as seen by the CPU)
+    Bus,
+    /// Byte addresses, word address * 2 (+1 for the high byte)
+    VRAM,
+    /// Byte addresses, color index * 2 (+1 for the high byte)
+    CGRAM,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryAccess {
+    pub space: MemorySpace,
+    pub kind: AccessKind,
+    pub address: u32,
+    pub value: u8,
+}
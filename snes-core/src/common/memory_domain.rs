@@ -0,0 +1,39 @@
+/// Memories addressed by offset rather than through the CPU bus, see `Bus::read_domain`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MemoryDomain {
+    WRAM,
+    /// Cartridge save RAM, empty until the mapper provides it
+    SRAM,
+    CartROM,
+    /// Bytes, word address * 2 (+1 for the high byte)
+    VRAM,
+    /// Bytes, color index * 2 (+1 for the high byte)
+    CGRAM,
+    OAM,
+}
+
+impl MemoryDomain {
+    pub const ALL: [MemoryDomain; 6] = [
+        MemoryDomain::WRAM,
+        MemoryDomain::SRAM,
+        MemoryDomain::CartROM,
+        MemoryDomain::VRAM,
+        MemoryDomain::CGRAM,
+        MemoryDomain::OAM,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryDomain::WRAM => "WRAM",
+            MemoryDomain::SRAM => "SRAM",
+            MemoryDomain::CartROM => "CARTROM",
+            MemoryDomain::VRAM => "VRAM",
+            MemoryDomain::CGRAM => "CGRAM",
+            MemoryDomain::OAM => "OAM",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|domain| domain.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
@@ -1,2 +1,5 @@
 pub mod instructions;
-pub mod flags;
\ No newline at end of file
+pub mod flags;
+pub mod memory_access;
+pub mod memory_domain;
+pub mod bus_cycle;
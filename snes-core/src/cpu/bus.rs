@@ -1,56 +1,199 @@
 use crate::ppu::PPU;
-use crate::cpu::dma::DMA;
+use crate::cpu::dma::{DMA, TransferDirection};
 use crate::cpu::internal_registers::InternalRegisters;
 use crate::rom::ROM;
 use crate::rom::lo_rom::LoROM;
+use crate::rom::test_memory::TestMemory;
+use crate::joypad::Joypad;
+use crate::common::memory_access::{MemoryAccess, MemorySpace, AccessKind};
+use crate::common::memory_domain::MemoryDomain;
+use crate::common::bus_cycle::BusCycleLog;
+use crate::cpu::registers::Registers;
+use crate::debugger::cdl::CodeDataLogger;
+
+// WRAM B-bus port
+pub const WMDATA: u16       = 0x2180;  // WRAM Data Read/Write (R/W)
+pub const WMADDL: u16       = 0x2181;  // WRAM Address (lower 8bit) (W)
+pub const WMADDM: u16       = 0x2182;  // WRAM Address (middle 8bit) (W)
+pub const WMADDH: u16       = 0x2183;  // WRAM Address (upper 1bit) (W)
 
 pub struct Bus {
-    wram: [u8; 0x10000],
+    wram: [u8; 0x20000],
+    wram_port_address: u32,
     pub ppu: PPU,
     pub rom: Box<dyn ROM>,
     pub internal_registers: InternalRegisters,
     pub dma: DMA,
-    pub force_cart_lookup: bool,
+    pub joypad: Joypad,
+    address_decoding: AddressDecoding,
+    /// Last value seen on the CPU data bus, returned by reads that nothing drives (open bus)
+    pub mdr: u8,
+    /// When set, every read and write is recorded here (used by the debugger)
+    pub access_log: Option<Vec<MemoryAccess>>,
+    /// When set, marks how each ROM byte gets used
+    pub cdl: Option<CodeDataLogger>,
+    /// When set, every read and write is recorded with its VDA/VPA/VPB flags (used by the CPU test runner)
+    pub cycle_log: Option<BusCycleLog>,
+}
+
+/// How CPU addresses are decoded
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AddressDecoding {
+    /// The console's memory map
+    Console,
+    /// Every address goes to the cartridge, a flat `TestMemory` (used by CPU test suites)
+    Flat,
 }
 
 #[derive(PartialEq, Debug)]
 pub enum MemoryMap {
     WRAM,
+    WRAMPort,
     PPU,
     CPU,
     DMA,
     Joypad,
     Cartridge,
+    Unmapped,
 }
 
 impl Bus {
     pub fn new() -> Self {
         Self {
-            wram: [0; 0x10000],
+            wram: [0; 0x20000],
+            wram_port_address: 0,
             ppu: PPU::new(),
             rom: Box::new(LoROM::new()),
             internal_registers: InternalRegisters::new(),
             dma: DMA::new(),
-            force_cart_lookup: false,
+            joypad: Joypad::new(),
+            address_decoding: AddressDecoding::Console,
+            mdr: 0x00,
+            access_log: None,
+            cdl: None,
+            cycle_log: None,
+        }
+    }
+
+    /// A bus where every address reads back what was last written to it, see `rom::test_memory`
+    pub fn new_flat() -> Self {
+        Self {
+            rom: Box::new(TestMemory::new()),
+            address_decoding: AddressDecoding::Flat,
+            ..Self::new()
         }
     }
 
     pub fn hard_reset(&mut self) {
-        self.wram = [0; 0x10000];
+        self.wram = [0; 0x20000];
+        self.wram_port_address = 0;
+        self.mdr = 0x00;
         self.internal_registers = InternalRegisters::new();
         self.dma = DMA::new();
+        self.joypad.reset();
+    }
+
+    fn wram_index(address: u32) -> usize {
+        match (address >> 16) as u8 {
+            0x7E..=0x7F => (address & 0x1FFFF) as usize,
+            // Banks 00-3F and 80-BF only mirror the first 8KB
+            _ => (address & 0x1FFF) as usize,
+        }
     }
 
     fn read_wram(&self, address: u32) -> u8 {
-        self.wram[(address & 0xFFFF) as usize]
+        self.wram[Bus::wram_index(address)]
     }
 
     fn write_wram(&mut self, address: u32, value: u8) {
-        self.wram[(address & 0xFFFF) as usize] = value;
+        self.wram[Bus::wram_index(address)] = value;
+    }
+
+    fn read_wram_port_external(&self, address: u16) -> u8 {
+        match address {
+            WMDATA => self.wram[self.wram_port_address as usize],
+            // WMADDL/M/H are write-only
+            _ => self.mdr,
+        }
+    }
+
+    fn read_wram_port(&mut self, address: u16) -> u8 {
+        let result = self.read_wram_port_external(address);
+        if address == WMDATA {
+            self.increment_wram_port_address();
+        }
+        result
+    }
+
+    fn write_wram_port(&mut self, address: u16, value: u8) {
+        let current = self.wram_port_address;
+        match address {
+            WMDATA => {
+                self.wram[current as usize] = value;
+                self.increment_wram_port_address();
+            },
+            WMADDL => self.wram_port_address = (current & 0x1FF00) | (value as u32),
+            WMADDM => self.wram_port_address = (current & 0x100FF) | ((value as u32) << 8),
+            WMADDH => self.wram_port_address = (current & 0x0FFFF) | (((value & 1) as u32) << 16),
+            _ => unreachable!(),
+        }
+    }
+
+    fn increment_wram_port_address(&mut self) {
+        self.wram_port_address = (self.wram_port_address + 1) & 0x1FFFF;
+    }
+
+    /// The WRAM port can't be used by DMA while the A-bus side of the transfer
+    /// is also WRAM, the hardware just skips the access.
+    pub fn is_wram_to_wram_dma(&self, src: u32, dst: u32) -> bool {
+        let is_wram_port = |address: u32| (address & 0xFFFF) as u16 == WMDATA;
+        (is_wram_port(src) && self.map_address(dst) == MemoryMap::WRAM) ||
+        (is_wram_port(dst) && self.map_address(src) == MemoryMap::WRAM)
+    }
+
+    /// DMA can't reach the B-bus registers, the DMA registers or MDMAEN/HDMAEN through the A-bus.
+    /// Reads there return open bus and writes are dropped.
+    pub fn is_dma_a_bus_address_accessible(&self, address: u32) -> bool {
+        let bank = (address >> 16) as u8;
+        let is_system_bank = matches!(bank, 0x00..=0x3F | 0x80..=0xBF);
+        !(is_system_bank && matches!(address as u16, 0x2100..=0x21FF | 0x4300..=0x437F | 0x420B..=0x420C))
+    }
+
+    /// Reads a DMA source or HDMA table byte from the A-bus
+    pub fn read_dma_a_bus(&mut self, address: u32) -> u8 {
+        if !self.is_dma_a_bus_address_accessible(address) {
+            return self.mdr;
+        }
+        let value = self.read(address);
+        self.log_cdl_dma_read(address);
+        value
+    }
+
+    /// Moves one DMA/HDMA byte between the A-bus and the B-bus register $21xx
+    pub fn transfer_dma_byte(&mut self, direction: TransferDirection, a_bus_address: u32, b_bus_address: u8) {
+        let b_bus_address = 0x002100 | b_bus_address as u32;
+        match direction {
+            TransferDirection::AtoB => {
+                if self.is_wram_to_wram_dma(a_bus_address, b_bus_address) {
+                    return;
+                }
+                let byte = self.read_dma_a_bus(a_bus_address);
+                self.write(b_bus_address, byte);
+            },
+            TransferDirection::BtoA => {
+                if self.is_wram_to_wram_dma(b_bus_address, a_bus_address) {
+                    return;
+                }
+                let byte = self.read(b_bus_address);
+                if self.is_dma_a_bus_address_accessible(a_bus_address) {
+                    self.write(a_bus_address, byte);
+                }
+            },
+        }
     }
 
     fn map_address(&self, address: u32) -> MemoryMap {
-        if self.force_cart_lookup {
+        if self.address_decoding == AddressDecoding::Flat {
             return MemoryMap::Cartridge;
         }
         let (bank, sub_address) = {
@@ -62,8 +205,11 @@ impl Bus {
             0x7E..=0x7F => MemoryMap::WRAM,
             0x80..=0xBF | 0x00..=0x3F => match sub_address {
                 0x0000..=0x1FFF => MemoryMap::WRAM,
+                0x2180..=0x2183 => MemoryMap::WRAMPort,
                 0x2100..=0x21FF => MemoryMap::PPU,
+                0x2000..=0x20FF | 0x2200..=0x3FFF => MemoryMap::Unmapped,
                 0x4016..=0x4017 => MemoryMap::Joypad,
+                0x4000..=0x41FF => MemoryMap::Unmapped,
                 0x4200..=0x42FF => MemoryMap::CPU,
                 0x4300..=0x5FFF => MemoryMap::DMA,
                 _ => MemoryMap::Cartridge,
@@ -78,45 +224,174 @@ impl Bus {
         let section = self.map_address(address);
         match section {
             MemoryMap::WRAM => self.read_wram(address),
-            MemoryMap::PPU => self.ppu.registers.read_external(address as u16),
+            MemoryMap::WRAMPort => self.read_wram_port_external(address as u16),
+            MemoryMap::PPU => self.ppu.registers.read_external(address as u16, self.mdr),
             MemoryMap::CPU => self.internal_registers.read_external(
                 address as u16,
                 &self.ppu.registers,
+                self.mdr,
             ),
-            MemoryMap::DMA => self.dma.read(address as u16),
-            MemoryMap::Joypad => 0x00,  // TODO: Placeholder
-            MemoryMap::Cartridge => self.rom.read(address),
+            MemoryMap::DMA => self.dma.read(address as u16, self.mdr),
+            MemoryMap::Joypad => self.joypad.read_external(address as u16, self.mdr),
+            MemoryMap::Cartridge => self.rom.read(address).unwrap_or(self.mdr),
+            MemoryMap::Unmapped => self.mdr,
         }
     }
 
     pub fn read(&mut self, address: u32) -> u8 {
         let section = self.map_address(address);
-        match section {
+        let value = match section {
             MemoryMap::WRAM => self.read_wram(address),
-            MemoryMap::PPU => self.ppu.registers.read(address as u16),
+            MemoryMap::WRAMPort => self.read_wram_port(address as u16),
+            MemoryMap::PPU => self.ppu.registers.read(address as u16, self.mdr),
             MemoryMap::CPU => self.internal_registers.read(
                 address as u16,
                 &mut self.ppu.registers,
+                self.mdr,
             ),
-            MemoryMap::DMA => self.dma.read(address as u16),
-            MemoryMap::Joypad => 0x00,  // TODO: Placeholder
-            MemoryMap::Cartridge => self.rom.read(address),
+            MemoryMap::DMA => self.dma.read(address as u16, self.mdr),
+            MemoryMap::Joypad => self.joypad.read(address as u16, self.mdr),
+            MemoryMap::Cartridge => {
+                self.log_cdl_read(address);
+                self.rom.read(address).unwrap_or(self.mdr)
+            },
+            MemoryMap::Unmapped => self.mdr,
+        };
+        self.mdr = value;
+        self.record_access(AccessKind::Read, address, value);
+        value
+    }
+
+    fn record_access(&mut self, kind: AccessKind, address: u32, value: u8) {
+        if let Some(access_log) = &mut self.access_log {
+            access_log.push(MemoryAccess {space: MemorySpace::Bus, kind, address, value});
+        }
+        if let Some(cycle_log) = &mut self.cycle_log {
+            cycle_log.record(kind, address, value);
+        }
+    }
+
+    /// Reads an interrupt vector, with VPB asserted
+    pub fn read_vector(&mut self, address: u32) -> u8 {
+        if let Some(cycle_log) = &mut self.cycle_log {
+            cycle_log.set_vector_pull(true);
+        }
+        let value = self.read(address);
+        if let Some(cycle_log) = &mut self.cycle_log {
+            cycle_log.set_vector_pull(false);
+        }
+        value
+    }
+
+    /// Tells the cycle log which bytes belong to the instruction at PC, before it runs
+    pub fn begin_cycle_log_instruction(&mut self, registers: &Registers) {
+        if self.cycle_log.is_some() {
+            let opcode = self.read_external(registers.get_pc_address());
+            if let Some(cycle_log) = &mut self.cycle_log {
+                cycle_log.begin_instruction(registers, opcode);
+            }
+        }
+    }
+
+    fn log_cdl_read(&mut self, address: u32) {
+        if let Some(cdl) = &mut self.cdl {
+            if let Some(offset) = self.rom.rom_offset(address) {
+                cdl.log_read(address, offset);
+            }
+        }
+    }
+
+    /// Marks ROM bytes read as a DMA source in the Code/Data Logger
+    pub fn log_cdl_dma_read(&mut self, address: u32) {
+        if let Some(cdl) = &mut self.cdl {
+            if let Some(offset) = self.rom.rom_offset(address) {
+                cdl.log_dma_read(offset);
+            }
+        }
+    }
+
+    /// Marks the instruction at PC as code in the Code/Data Logger, before it runs
+    pub fn begin_cdl_instruction(&mut self, registers: &Registers) {
+        if self.cdl.is_some() {
+            let opcode = self.read_external(registers.get_pc_address());
+            if let Some(cdl) = &mut self.cdl {
+                let rom = &self.rom;
+                cdl.begin_instruction(registers, opcode, |address| rom.rom_offset(address));
+            }
+        }
+    }
+
+    /// Marks jump and subroutine targets in the Code/Data Logger, after the instruction ran
+    pub fn end_cdl_instruction(&mut self, opcode: u8, pc_address: u32) {
+        if let Some(cdl) = &mut self.cdl {
+            let rom = &self.rom;
+            cdl.end_instruction(opcode, pc_address, |address| rom.rom_offset(address));
         }
     }
 
     pub fn write(&mut self, address: u32, value: u8) {
+        self.mdr = value;
+        self.record_access(AccessKind::Write, address, value);
         let section = self.map_address(address);
         match section {
             MemoryMap::WRAM => self.write_wram(address, value),
+            MemoryMap::WRAMPort => self.write_wram_port(address as u16, value),
             MemoryMap::PPU => self.ppu.registers.write(address as u16, value),
             MemoryMap::CPU => self.internal_registers.write(
                 address as u16,
                 value,
                 &mut self.dma,
+                &mut self.ppu.registers,
             ),
             MemoryMap::DMA => self.dma.write(address as u16, value),
-            MemoryMap::Joypad => {},  // TODO: Placeholder
+            MemoryMap::Joypad => self.joypad.write(address as u16, value),
             MemoryMap::Cartridge => self.rom.write(address, value),
+            MemoryMap::Unmapped => {},
+        }
+    }
+
+    pub fn domain_size(&self, domain: MemoryDomain) -> usize {
+        match domain {
+            MemoryDomain::WRAM => self.wram.len(),
+            MemoryDomain::SRAM => self.rom.sram().len(),
+            MemoryDomain::CartROM => self.rom.data().len(),
+            MemoryDomain::VRAM => self.ppu.registers.vram().len() * 2,
+            MemoryDomain::CGRAM => self.ppu.registers.cgram().len() * 2,
+            MemoryDomain::OAM => self.ppu.registers.oam().len(),
+        }
+    }
+
+    /// Reads a memory directly, without side effects. Offsets past the end read 0
+    pub fn read_domain(&self, domain: MemoryDomain, offset: usize) -> u8 {
+        let word = |words: &[u16]| words.get(offset / 2)
+            .map(|word| (word >> ((offset & 1) * 8)) as u8)
+            .unwrap_or_default();
+        match domain {
+            MemoryDomain::WRAM => self.wram.get(offset).copied().unwrap_or_default(),
+            MemoryDomain::SRAM => self.rom.sram().get(offset).copied().unwrap_or_default(),
+            MemoryDomain::CartROM => self.rom.data().get(offset).copied().unwrap_or_default(),
+            MemoryDomain::VRAM => word(self.ppu.registers.vram()),
+            MemoryDomain::CGRAM => word(self.ppu.registers.cgram()),
+            MemoryDomain::OAM => self.ppu.registers.oam().get(offset).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Writes a memory directly, without side effects. Offsets past the end are ignored
+    pub fn write_domain(&mut self, domain: MemoryDomain, offset: usize, value: u8) {
+        let write_word = |words: &mut [u16]| if let Some(word) = words.get_mut(offset / 2) {
+            let shift = (offset & 1) * 8;
+            *word = (*word & !(0xFF << shift)) | ((value as u16) << shift);
+        };
+        let byte = match domain {
+            MemoryDomain::WRAM => self.wram.get_mut(offset),
+            MemoryDomain::SRAM => self.rom.sram_mut().get_mut(offset),
+            MemoryDomain::CartROM => self.rom.data_mut().get_mut(offset),
+            MemoryDomain::VRAM => return write_word(self.ppu.registers.vram_mut()),
+            MemoryDomain::CGRAM => return write_word(self.ppu.registers.cgram_mut()),
+            MemoryDomain::OAM => self.ppu.registers.oam_mut().get_mut(offset),
+        };
+        if let Some(byte) = byte {
+            *byte = value;
         }
     }
 }
@@ -131,6 +406,7 @@ impl Default for Bus {
 #[cfg(test)]
 mod bus_tests {
     use super::*;
+    use crate::joypad::controller::{ControllerPort, BUTTON_B};
 
     #[test]
     fn test_memory_map() {
@@ -156,6 +432,11 @@ mod bus_tests {
         assert_eq!(bus.map_address(0xBF2100), MemoryMap::PPU);
         assert_eq!(bus.map_address(0xBF21FF), MemoryMap::PPU);
 
+        assert_eq!(bus.map_address(0x002180), MemoryMap::WRAMPort);
+        assert_eq!(bus.map_address(0x002183), MemoryMap::WRAMPort);
+        assert_eq!(bus.map_address(0x802180), MemoryMap::WRAMPort);
+        assert_eq!(bus.map_address(0xBF2183), MemoryMap::WRAMPort);
+
         assert_eq!(bus.map_address(0x004200), MemoryMap::CPU);
         assert_eq!(bus.map_address(0x00420F), MemoryMap::CPU);
         assert_eq!(bus.map_address(0x3F4200), MemoryMap::CPU);
@@ -173,6 +454,11 @@ mod bus_tests {
         assert_eq!(bus.map_address(0x804017), MemoryMap::Joypad);
         assert_eq!(bus.map_address(0xBF4016), MemoryMap::Joypad);
         assert_eq!(bus.map_address(0xBF4017), MemoryMap::Joypad);
+
+        assert_eq!(bus.map_address(0x002000), MemoryMap::Unmapped);
+        assert_eq!(bus.map_address(0x003FFF), MemoryMap::Unmapped);
+        assert_eq!(bus.map_address(0x004000), MemoryMap::Unmapped);
+        assert_eq!(bus.map_address(0x8041FF), MemoryMap::Unmapped);
     }
 
     #[test]
@@ -184,6 +470,135 @@ mod bus_tests {
         bus.write(0x80_0000, 0xEE);
         assert_eq!(bus.read(0x7E_0000), 0xEE);
         assert_eq!(bus.read(0x00_0000), 0xEE);
+        // Bank 7F is not a mirror of bank 7E
+        bus.write(0x7F_0000, 0x55);
+        assert_eq!(bus.read(0x7E_0000), 0xEE);
+        assert_eq!(bus.read(0x7F_0000), 0x55);
+        // Only the first 8KB are mirrored in the lower banks
+        bus.write(0x7E_1FFF, 0x66);
+        assert_eq!(bus.read(0x80_1FFF), 0x66);
     }
-}
 
+    #[test]
+    fn test_wram_port() {
+        let mut bus = Bus::new();
+        bus.write(WMADDL as u32, 0xFF);
+        bus.write(WMADDM as u32, 0xFF);
+        bus.write(WMADDH as u32, 0x00);
+        bus.write(WMDATA as u32, 0x12);
+        bus.write(WMDATA as u32, 0x34);
+        assert_eq!(bus.read(0x7E_FFFF), 0x12);
+        assert_eq!(bus.read(0x7F_0000), 0x34);
+
+        // The address is 17 bits wide and wraps around
+        bus.write(WMADDL as u32, 0xFF);
+        bus.write(WMADDM as u32, 0xFF);
+        bus.write(WMADDH as u32, 0xFF);
+        bus.write(WMDATA as u32, 0x56);
+        bus.write(WMDATA as u32, 0x78);
+        assert_eq!(bus.read(0x7F_FFFF), 0x56);
+        assert_eq!(bus.read(0x7E_0000), 0x78);
+
+        bus.write(WMADDL as u32, 0xFF);
+        bus.write(WMADDM as u32, 0xFF);
+        bus.write(WMADDH as u32, 0x00);
+        assert_eq!(bus.read_external(WMDATA as u32), 0x12);
+        assert_eq!(bus.read(WMDATA as u32), 0x12);
+        assert_eq!(bus.read(WMDATA as u32), 0x34);
+    }
+
+    #[test]
+    fn test_open_bus() {
+        let mut bus = Bus::new();
+        bus.write(0x7E_0010, 0xAB);
+        assert_eq!(bus.read(0x7E_0010), 0xAB);
+        assert_eq!(bus.mdr, 0xAB);
+        // Unmapped areas return the last value on the data bus
+        assert_eq!(bus.read(0x00_3000), 0xAB);
+        assert_eq!(bus.read_external(0x00_3000), 0xAB);
+        // So do write-only registers
+        assert_eq!(bus.read(WMADDL as u32), 0xAB);
+        assert_eq!(bus.read(0x00_2100), 0xAB);
+        assert_eq!(bus.read(0x00_4200), 0xAB);
+        // Writes also drive the data bus
+        bus.write(0x00_2100, 0x8F);
+        assert_eq!(bus.read(0x00_3000), 0x8F);
+        // An empty cartridge doesn't drive the bus either
+        assert_eq!(bus.read(0x80_8000), 0x8F);
+    }
+
+    #[test]
+    fn test_is_wram_to_wram_dma() {
+        let bus = Bus::new();
+        assert!(bus.is_wram_to_wram_dma(0x7E_0000, 0x00_2180));
+        assert!(bus.is_wram_to_wram_dma(0x00_0100, 0x00_2180));
+        assert!(bus.is_wram_to_wram_dma(0x00_2180, 0x7F_0000));
+        assert!(!bus.is_wram_to_wram_dma(0x80_8000, 0x00_2180));
+        assert!(!bus.is_wram_to_wram_dma(0x7E_0000, 0x00_2118));
+    }
+
+    #[test]
+    fn test_hard_reset_clears_joypad() {
+        // Stands for whatever device the frontend plugged in
+        struct AlwaysPressed;
+        impl ControllerPort for AlwaysPressed {
+            fn latch(&mut self, _value: bool) {}
+            fn read_data(&mut self) -> u8 { 1 }
+            fn peek_data(&self) -> u8 { 1 }
+            fn set_buttons(&mut self, _buttons: u16) {}
+            fn reset(&mut self) {}
+        }
+
+        let mut bus = Bus::new();
+        bus.joypad.ports[0].set_buttons(BUTTON_B);
+        bus.joypad.ports[1] = Box::new(AlwaysPressed);
+        bus.write(0x00_4016, 0x01);
+        assert!(bus.joypad.is_latched());
+        assert_eq!(bus.read_external(0x00_4016) & 1, 1);
+        bus.hard_reset();
+        assert!(!bus.joypad.is_latched());
+        assert_eq!(bus.read_external(0x00_4016) & 1, 0);
+        // The devices are kept, the standard controller keeps its buttons for the next latch
+        assert_eq!(bus.read_external(0x00_4017) & 1, 1);
+        bus.write(0x00_4016, 0x01);
+        assert_eq!(bus.read_external(0x00_4016) & 1, 1);
+    }
+
+    #[test]
+    fn test_flat_address_decoding() {
+        let mut bus = Bus::new_flat();
+        for address in [0x00_2100, 0x00_4300, 0x7E_0000, 0xFF_FFFF] {
+            assert_eq!(bus.map_address(address), MemoryMap::Cartridge);
+            bus.write(address, 0x5A);
+            assert_eq!(bus.read(address), 0x5A);
+        }
+        assert_eq!(bus.read(0x00_2101), 0x00);
+    }
+
+    #[test]
+    fn test_memory_domains() {
+        let mut bus = Bus::new();
+        assert_eq!(bus.domain_size(MemoryDomain::WRAM), 0x20000);
+        assert_eq!(bus.domain_size(MemoryDomain::VRAM), 0x10000);
+        assert_eq!(bus.domain_size(MemoryDomain::CGRAM), 512);
+        assert_eq!(bus.domain_size(MemoryDomain::OAM), 544);
+        assert_eq!(bus.domain_size(MemoryDomain::SRAM), 0);
+
+        bus.write_domain(MemoryDomain::WRAM, 0x10010, 0x12);
+        assert_eq!(bus.read_external(0x7F_0010), 0x12);
+        bus.write_domain(MemoryDomain::VRAM, 0x0003, 0xAB);
+        bus.write_domain(MemoryDomain::VRAM, 0x0002, 0xCD);
+        assert_eq!(bus.ppu.registers.vram()[1], 0xABCD);
+        assert_eq!(bus.read_domain(MemoryDomain::VRAM, 0x0003), 0xAB);
+        bus.write_domain(MemoryDomain::CGRAM, 0x0001, 0x7F);
+        assert_eq!(bus.ppu.registers.cgram()[0], 0x7F00);
+        // Out of range accesses are ignored
+        bus.write_domain(MemoryDomain::CartROM, 0x0000, 0x01);
+        assert_eq!(bus.read_domain(MemoryDomain::CartROM, 0x0000), 0x00);
+        bus.write_domain(MemoryDomain::SRAM, 0x0000, 0x01);
+        assert_eq!(bus.read_domain(MemoryDomain::SRAM, 0x0000), 0x00);
+        assert_eq!(MemoryDomain::from_name("cartrom"), Some(MemoryDomain::CartROM));
+        assert_eq!(MemoryDomain::from_name("APURAM"), None);
+        assert_eq!(MemoryDomain::from_name("sram"), Some(MemoryDomain::SRAM));
+    }
+}
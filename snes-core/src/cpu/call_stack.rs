@@ -0,0 +1,202 @@
+//! Tracks subroutine calls and interrupts as they happen, so debuggers can
+//! show how execution got to the current instruction.
+use super::registers::Registers;
+use super::vectors::Vector;
+
+/// Frames beyond this depth drop the oldest ones, code that never returns
+/// (like a main loop entered with JSR) would grow the stack forever otherwise
+pub const MAX_DEPTH: usize = 1024;
+/// Number of unbalanced returns kept around
+pub const MAX_UNBALANCED_RETURNS: usize = 64;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CallKind {
+    JSR,
+    JSL,
+    /// BRK and COP, or a hardware interrupt
+    Interrupt(Vector),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    /// Address of the JSR/JSL/BRK/COP instruction,
+    /// or of the instruction a hardware interrupt returns to
+    pub caller: u32,
+    pub target: u32,
+    /// Stack pointer before the return address was pushed,
+    /// a balanced return brings it back to this value
+    pub stack_pointer: u16,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ReturnKind {
+    RTS,
+    RTL,
+    RTI,
+}
+
+impl ReturnKind {
+    fn matches(&self, call: CallKind) -> bool {
+        matches!(
+            (self, call),
+            (ReturnKind::RTS, CallKind::JSR) |
+            (ReturnKind::RTL, CallKind::JSL) |
+            (ReturnKind::RTI, CallKind::Interrupt(_))
+        )
+    }
+}
+
+/// A return that didn't match the innermost frame, either because of its kind
+/// or because the stack was manipulated in between (e.g. PLA before RTS)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UnbalancedReturn {
+    pub kind: ReturnKind,
+    /// Address of the return instruction
+    pub address: u32,
+    /// Where it returned to
+    pub target: u32,
+    /// Stack pointer after returning
+    pub stack_pointer: u16,
+    /// Innermost frame at the time, `None` if the stack was empty
+    pub expected: Option<CallFrame>,
+}
+
+pub struct CallStack {
+    /// Innermost call last
+    pub frames: Vec<CallFrame>,
+    /// Newest last, up to `MAX_UNBALANCED_RETURNS`
+    pub unbalanced_returns: Vec<UnbalancedReturn>,
+    /// Frames pushed since power on
+    pub total_calls: u64,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![],
+            unbalanced_returns: vec![],
+            total_calls: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.unbalanced_returns.clear();
+    }
+
+    pub fn push(&mut self, frame: CallFrame) {
+        if self.frames.len() >= MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+        self.total_calls += 1;
+    }
+
+    /// Pops the frame a return belongs to. Returns that don't match the innermost
+    /// frame are recorded, and frames the stack pointer moved past are dropped.
+    pub fn pop(&mut self, kind: ReturnKind, address: u32, target: u32, stack_pointer: u16) {
+        if let Some(frame) = self.frames.last() {
+            if kind.matches(frame.kind) && frame.stack_pointer == stack_pointer {
+                self.frames.pop();
+                return;
+            }
+        }
+        if self.unbalanced_returns.len() >= MAX_UNBALANCED_RETURNS {
+            self.unbalanced_returns.remove(0);
+        }
+        self.unbalanced_returns.push(UnbalancedReturn {
+            kind,
+            address,
+            target,
+            stack_pointer,
+            expected: self.frames.last().copied(),
+        });
+        // The stack grows down, frames pushed below the new stack pointer are gone
+        while self.frames.last().is_some_and(|f| f.stack_pointer <= stack_pointer) {
+            self.frames.pop();
+        }
+    }
+
+    /// Updates the stack after an instruction ran.
+    /// `address` and `stack_pointer` are the values from before it executed.
+    pub fn track_instruction(&mut self, opcode: u8, address: u32, stack_pointer: u16, registers: &Registers) {
+        let target = registers.get_pc_address();
+        let call = |kind| CallFrame {kind, caller: address, target, stack_pointer};
+        match opcode {
+            // JSR addr, JSR (addr,X)
+            0x20 | 0xFC => self.push(call(CallKind::JSR)),
+            0x22 => self.push(call(CallKind::JSL)),
+            0x00 => self.push(call(CallKind::Interrupt(Vector::Break))),
+            0x02 => self.push(call(CallKind::Interrupt(Vector::COP))),
+            0x60 => self.pop(ReturnKind::RTS, address, target, registers.sp),
+            0x6B => self.pop(ReturnKind::RTL, address, target, registers.sp),
+            0x40 => self.pop(ReturnKind::RTI, address, target, registers.sp),
+            _ => {},
+        }
+    }
+}
+
+impl Default for CallStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod cpu_call_stack_tests {
+    use super::*;
+
+    fn frame(kind: CallKind, stack_pointer: u16) -> CallFrame {
+        CallFrame {kind, caller: 0x008000, target: 0x009000, stack_pointer}
+    }
+
+    #[test]
+    fn test_balanced_returns() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(frame(CallKind::JSR, 0x1FF));
+        call_stack.push(frame(CallKind::JSL, 0x1FD));
+        call_stack.push(frame(CallKind::Interrupt(Vector::NMI), 0x1FA));
+        call_stack.pop(ReturnKind::RTI, 0x00C000, 0x009010, 0x1FA);
+        call_stack.pop(ReturnKind::RTL, 0x009020, 0x008004, 0x1FD);
+        assert_eq!(call_stack.frames, vec![frame(CallKind::JSR, 0x1FF)]);
+        call_stack.pop(ReturnKind::RTS, 0x009030, 0x008003, 0x1FF);
+        assert!(call_stack.frames.is_empty());
+        assert!(call_stack.unbalanced_returns.is_empty());
+    }
+
+    #[test]
+    fn test_unbalanced_returns() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(frame(CallKind::JSR, 0x1FF));
+        call_stack.push(frame(CallKind::JSR, 0x1FD));
+        // PLA PLA RTS: returns straight to the outer caller
+        call_stack.pop(ReturnKind::RTS, 0x009040, 0x008003, 0x1FF);
+        assert!(call_stack.frames.is_empty());
+        assert_eq!(call_stack.unbalanced_returns.len(), 1);
+        assert_eq!(call_stack.unbalanced_returns[0].expected, Some(frame(CallKind::JSR, 0x1FD)));
+
+        // RTL out of a JSR
+        call_stack.push(frame(CallKind::JSR, 0x1FF));
+        call_stack.pop(ReturnKind::RTL, 0x009050, 0x123456, 0x1FF);
+        assert!(call_stack.frames.is_empty());
+        // PEA/RTS jump, nothing to pop
+        call_stack.push(frame(CallKind::JSR, 0x1FF));
+        call_stack.pop(ReturnKind::RTS, 0x009060, 0x00A000, 0x1FB);
+        assert_eq!(call_stack.frames.len(), 1);
+        assert_eq!(call_stack.unbalanced_returns.len(), 3);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut call_stack = CallStack::new();
+        for i in 0..MAX_DEPTH + 2 {
+            call_stack.push(frame(CallKind::JSR, 0xFFFF - i as u16));
+        }
+        assert_eq!(call_stack.frames.len(), MAX_DEPTH);
+        assert_eq!(call_stack.frames[0].stack_pointer, 0xFFFD);
+    }
+}
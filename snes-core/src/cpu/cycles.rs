@@ -64,10 +64,8 @@ fn common_conditions(cpu_registers: &Registers, addressing_mode: AddressingMode,
                     A::DirectPage | A::DirectPageIndirect | A::DirectPageIndirectLong |
                     A::DirectPageIndexed(_) | A::DirectPageIndexedIndirect(_) |
                     A::DirectPageIndirectIndexed(_) |
-                    A::DirectPageIndirectLongIndexed(_) => {
-                        if cpu_registers.direct_page_low() != 0 {
-                            cycles += 1;
-                        }
+                    A::DirectPageIndirectLongIndexed(_) if cpu_registers.direct_page_low() != 0 => {
+                        cycles += 1;
                     },
                     _ => {},
                 };
@@ -438,7 +436,7 @@ pub fn increment_cycles_xba() -> (u16, usize) {
 }
 
 pub fn increment_cycles_brk(is_emulation_mode: bool) -> (u16, usize) {
-    (2, if is_emulation_mode {8} else {7})
+    (2, if is_emulation_mode {7} else {8})
 }
 
 pub fn increment_cycles_stp() -> (u16, usize) {
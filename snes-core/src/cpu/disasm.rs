@@ -0,0 +1,620 @@
+//! Standalone 65816 disassembler.
+//!
+//! Unlike `CPUInstruction::mnemonic`, it doesn't depend on the live CPU
+//! registers: operand widths are inferred by following REP/SEP/XCE (and
+//! CLC/SEC for the carry XCE swaps in) through the decoded code.
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+
+use crate::cpu::bus::Bus;
+use crate::cpu::registers::Registers;
+
+use self::OperandMode as M;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OperandMode {
+    Implied,
+    Accumulator,
+    /// Width depends on the M flag
+    ImmediateM,
+    /// Width depends on the X flag
+    ImmediateX,
+    /// Always 8 bits (REP, SEP, BRK and COP signatures, WDM)
+    Immediate8,
+    Relative,
+    RelativeLong,
+    DirectPage,
+    DirectPageX,
+    DirectPageY,
+    DirectPageIndirect,
+    DirectPageIndirectLong,
+    DirectPageIndexedIndirect,
+    DirectPageIndirectIndexed,
+    DirectPageIndirectLongIndexed,
+    StackRelative,
+    StackRelativeIndirectIndexed,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    AbsoluteLong,
+    AbsoluteLongX,
+    AbsoluteIndirect,
+    AbsoluteIndirectLong,
+    AbsoluteIndexedIndirect,
+    BlockMove,
+}
+
+impl OperandMode {
+    pub fn operand_length(&self, state: &WidthState) -> u8 {
+        match self {
+            M::Implied | M::Accumulator => 0,
+            M::ImmediateM => if state.is_16bit_mode { 2 } else { 1 },
+            M::ImmediateX => if state.is_16bit_index { 2 } else { 1 },
+            M::Immediate8 | M::Relative | M::DirectPage | M::DirectPageX |
+            M::DirectPageY | M::DirectPageIndirect | M::DirectPageIndirectLong |
+            M::DirectPageIndexedIndirect | M::DirectPageIndirectIndexed |
+            M::DirectPageIndirectLongIndexed | M::StackRelative |
+            M::StackRelativeIndirectIndexed => 1,
+            M::RelativeLong | M::Absolute | M::AbsoluteX | M::AbsoluteY |
+            M::AbsoluteIndirect | M::AbsoluteIndirectLong |
+            M::AbsoluteIndexedIndirect | M::BlockMove => 2,
+            M::AbsoluteLong | M::AbsoluteLongX => 3,
+        }
+    }
+}
+
+static OPCODES: [(&str, OperandMode); 256] = [
+    // 0x00
+    ("BRK", M::Immediate8), ("ORA", M::DirectPageIndexedIndirect), ("COP", M::Immediate8), ("ORA", M::StackRelative),
+    ("TSB", M::DirectPage), ("ORA", M::DirectPage), ("ASL", M::DirectPage), ("ORA", M::DirectPageIndirectLong),
+    ("PHP", M::Implied), ("ORA", M::ImmediateM), ("ASL", M::Accumulator), ("PHD", M::Implied),
+    ("TSB", M::Absolute), ("ORA", M::Absolute), ("ASL", M::Absolute), ("ORA", M::AbsoluteLong),
+    // 0x10
+    ("BPL", M::Relative), ("ORA", M::DirectPageIndirectIndexed), ("ORA", M::DirectPageIndirect), ("ORA", M::StackRelativeIndirectIndexed),
+    ("TRB", M::DirectPage), ("ORA", M::DirectPageX), ("ASL", M::DirectPageX), ("ORA", M::DirectPageIndirectLongIndexed),
+    ("CLC", M::Implied), ("ORA", M::AbsoluteY), ("INC", M::Accumulator), ("TCS", M::Implied),
+    ("TRB", M::Absolute), ("ORA", M::AbsoluteX), ("ASL", M::AbsoluteX), ("ORA", M::AbsoluteLongX),
+    // 0x20
+    ("JSR", M::Absolute), ("AND", M::DirectPageIndexedIndirect), ("JSL", M::AbsoluteLong), ("AND", M::StackRelative),
+    ("BIT", M::DirectPage), ("AND", M::DirectPage), ("ROL", M::DirectPage), ("AND", M::DirectPageIndirectLong),
+    ("PLP", M::Implied), ("AND", M::ImmediateM), ("ROL", M::Accumulator), ("PLD", M::Implied),
+    ("BIT", M::Absolute), ("AND", M::Absolute), ("ROL", M::Absolute), ("AND", M::AbsoluteLong),
+    // 0x30
+    ("BMI", M::Relative), ("AND", M::DirectPageIndirectIndexed), ("AND", M::DirectPageIndirect), ("AND", M::StackRelativeIndirectIndexed),
+    ("BIT", M::DirectPageX), ("AND", M::DirectPageX), ("ROL", M::DirectPageX), ("AND", M::DirectPageIndirectLongIndexed),
+    ("SEC", M::Implied), ("AND", M::AbsoluteY), ("DEC", M::Accumulator), ("TSC", M::Implied),
+    ("BIT", M::AbsoluteX), ("AND", M::AbsoluteX), ("ROL", M::AbsoluteX), ("AND", M::AbsoluteLongX),
+    // 0x40
+    ("RTI", M::Implied), ("EOR", M::DirectPageIndexedIndirect), ("WDM", M::Immediate8), ("EOR", M::StackRelative),
+    ("MVP", M::BlockMove), ("EOR", M::DirectPage), ("LSR", M::DirectPage), ("EOR", M::DirectPageIndirectLong),
+    ("PHA", M::Implied), ("EOR", M::ImmediateM), ("LSR", M::Accumulator), ("PHK", M::Implied),
+    ("JMP", M::Absolute), ("EOR", M::Absolute), ("LSR", M::Absolute), ("EOR", M::AbsoluteLong),
+    // 0x50
+    ("BVC", M::Relative), ("EOR", M::DirectPageIndirectIndexed), ("EOR", M::DirectPageIndirect), ("EOR", M::StackRelativeIndirectIndexed),
+    ("MVN", M::BlockMove), ("EOR", M::DirectPageX), ("LSR", M::DirectPageX), ("EOR", M::DirectPageIndirectLongIndexed),
+    ("CLI", M::Implied), ("EOR", M::AbsoluteY), ("PHY", M::Implied), ("TCD", M::Implied),
+    ("JML", M::AbsoluteLong), ("EOR", M::AbsoluteX), ("LSR", M::AbsoluteX), ("EOR", M::AbsoluteLongX),
+    // 0x60
+    ("RTS", M::Implied), ("ADC", M::DirectPageIndexedIndirect), ("PER", M::RelativeLong), ("ADC", M::StackRelative),
+    ("STZ", M::DirectPage), ("ADC", M::DirectPage), ("ROR", M::DirectPage), ("ADC", M::DirectPageIndirectLong),
+    ("PLA", M::Implied), ("ADC", M::ImmediateM), ("ROR", M::Accumulator), ("RTL", M::Implied),
+    ("JMP", M::AbsoluteIndirect), ("ADC", M::Absolute), ("ROR", M::Absolute), ("ADC", M::AbsoluteLong),
+    // 0x70
+    ("BVS", M::Relative), ("ADC", M::DirectPageIndirectIndexed), ("ADC", M::DirectPageIndirect), ("ADC", M::StackRelativeIndirectIndexed),
+    ("STZ", M::DirectPageX), ("ADC", M::DirectPageX), ("ROR", M::DirectPageX), ("ADC", M::DirectPageIndirectLongIndexed),
+    ("SEI", M::Implied), ("ADC", M::AbsoluteY), ("PLY", M::Implied), ("TDC", M::Implied),
+    ("JMP", M::AbsoluteIndexedIndirect), ("ADC", M::AbsoluteX), ("ROR", M::AbsoluteX), ("ADC", M::AbsoluteLongX),
+    // 0x80
+    ("BRA", M::Relative), ("STA", M::DirectPageIndexedIndirect), ("BRL", M::RelativeLong), ("STA", M::StackRelative),
+    ("STY", M::DirectPage), ("STA", M::DirectPage), ("STX", M::DirectPage), ("STA", M::DirectPageIndirectLong),
+    ("DEY", M::Implied), ("BIT", M::ImmediateM), ("TXA", M::Implied), ("PHB", M::Implied),
+    ("STY", M::Absolute), ("STA", M::Absolute), ("STX", M::Absolute), ("STA", M::AbsoluteLong),
+    // 0x90
+    ("BCC", M::Relative), ("STA", M::DirectPageIndirectIndexed), ("STA", M::DirectPageIndirect), ("STA", M::StackRelativeIndirectIndexed),
+    ("STY", M::DirectPageX), ("STA", M::DirectPageX), ("STX", M::DirectPageY), ("STA", M::DirectPageIndirectLongIndexed),
+    ("TYA", M::Implied), ("STA", M::AbsoluteY), ("TXS", M::Implied), ("TXY", M::Implied),
+    ("STZ", M::Absolute), ("STA", M::AbsoluteX), ("STZ", M::AbsoluteX), ("STA", M::AbsoluteLongX),
+    // 0xA0
+    ("LDY", M::ImmediateX), ("LDA", M::DirectPageIndexedIndirect), ("LDX", M::ImmediateX), ("LDA", M::StackRelative),
+    ("LDY", M::DirectPage), ("LDA", M::DirectPage), ("LDX", M::DirectPage), ("LDA", M::DirectPageIndirectLong),
+    ("TAY", M::Implied), ("LDA", M::ImmediateM), ("TAX", M::Implied), ("PLB", M::Implied),
+    ("LDY", M::Absolute), ("LDA", M::Absolute), ("LDX", M::Absolute), ("LDA", M::AbsoluteLong),
+    // 0xB0
+    ("BCS", M::Relative), ("LDA", M::DirectPageIndirectIndexed), ("LDA", M::DirectPageIndirect), ("LDA", M::StackRelativeIndirectIndexed),
+    ("LDY", M::DirectPageX), ("LDA", M::DirectPageX), ("LDX", M::DirectPageY), ("LDA", M::DirectPageIndirectLongIndexed),
+    ("CLV", M::Implied), ("LDA", M::AbsoluteY), ("TSX", M::Implied), ("TYX", M::Implied),
+    ("LDY", M::AbsoluteX), ("LDA", M::AbsoluteX), ("LDX", M::AbsoluteY), ("LDA", M::AbsoluteLongX),
+    // 0xC0
+    ("CPY", M::ImmediateX), ("CMP", M::DirectPageIndexedIndirect), ("REP", M::Immediate8), ("CMP", M::StackRelative),
+    ("CPY", M::DirectPage), ("CMP", M::DirectPage), ("DEC", M::DirectPage), ("CMP", M::DirectPageIndirectLong),
+    ("INY", M::Implied), ("CMP", M::ImmediateM), ("DEX", M::Implied), ("WAI", M::Implied),
+    ("CPY", M::Absolute), ("CMP", M::Absolute), ("DEC", M::Absolute), ("CMP", M::AbsoluteLong),
+    // 0xD0
+    ("BNE", M::Relative), ("CMP", M::DirectPageIndirectIndexed), ("CMP", M::DirectPageIndirect), ("CMP", M::StackRelativeIndirectIndexed),
+    ("PEI", M::DirectPageIndirect), ("CMP", M::DirectPageX), ("DEC", M::DirectPageX), ("CMP", M::DirectPageIndirectLongIndexed),
+    ("CLD", M::Implied), ("CMP", M::AbsoluteY), ("PHX", M::Implied), ("STP", M::Implied),
+    ("JML", M::AbsoluteIndirectLong), ("CMP", M::AbsoluteX), ("DEC", M::AbsoluteX), ("CMP", M::AbsoluteLongX),
+    // 0xE0
+    ("CPX", M::ImmediateX), ("SBC", M::DirectPageIndexedIndirect), ("SEP", M::Immediate8), ("SBC", M::StackRelative),
+    ("CPX", M::DirectPage), ("SBC", M::DirectPage), ("INC", M::DirectPage), ("SBC", M::DirectPageIndirectLong),
+    ("INX", M::Implied), ("SBC", M::ImmediateM), ("NOP", M::Implied), ("XBA", M::Implied),
+    ("CPX", M::Absolute), ("SBC", M::Absolute), ("INC", M::Absolute), ("SBC", M::AbsoluteLong),
+    // 0xF0
+    ("BEQ", M::Relative), ("SBC", M::DirectPageIndirectIndexed), ("SBC", M::DirectPageIndirect), ("SBC", M::StackRelativeIndirectIndexed),
+    ("PEA", M::Absolute), ("SBC", M::DirectPageX), ("INC", M::DirectPageX), ("SBC", M::DirectPageIndirectLongIndexed),
+    ("SED", M::Implied), ("SBC", M::AbsoluteY), ("PLX", M::Implied), ("XCE", M::Implied),
+    ("JSR", M::AbsoluteIndexedIndirect), ("SBC", M::AbsoluteX), ("INC", M::AbsoluteX), ("SBC", M::AbsoluteLongX),
+];
+
+pub fn opcode_info(opcode: u8) -> (&'static str, OperandMode) {
+    OPCODES[opcode as usize]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Syntax {
+    Asar,
+    Ca65,
+}
+
+/// Register widths assumed while decoding
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WidthState {
+    pub is_16bit_mode: bool,
+    pub is_16bit_index: bool,
+    pub emulation_mode: bool,
+    /// Needed to follow XCE, `None` when it can't be known statically
+    pub carry: Option<bool>,
+}
+
+impl WidthState {
+    pub fn new() -> Self {
+        // Power on state: emulation mode, 8 bit registers
+        Self {
+            is_16bit_mode: false,
+            is_16bit_index: false,
+            emulation_mode: true,
+            carry: None,
+        }
+    }
+
+    pub fn from_registers(registers: &Registers) -> Self {
+        Self {
+            is_16bit_mode: registers.is_16bit_mode(),
+            is_16bit_index: registers.is_16bit_index(),
+            emulation_mode: registers.emulation_mode,
+            carry: Some(registers.get_carry_flag()),
+        }
+    }
+
+    /// Applies the effects of an already decoded instruction
+    fn update(&mut self, instruction: &DisassembledInstruction) {
+        let operand = instruction.operand as u8;
+        match instruction.opcode {
+            // REP
+            0xC2 => {
+                if !self.emulation_mode {
+                    self.is_16bit_mode |= operand & 0x20 != 0;
+                    self.is_16bit_index |= operand & 0x10 != 0;
+                }
+                if operand & 0x01 != 0 {
+                    self.carry = Some(false);
+                }
+            },
+            // SEP
+            0xE2 => {
+                self.is_16bit_mode &= operand & 0x20 == 0;
+                self.is_16bit_index &= operand & 0x10 == 0;
+                if operand & 0x01 != 0 {
+                    self.carry = Some(true);
+                }
+            },
+            // CLC
+            0x18 => self.carry = Some(false),
+            // SEC
+            0x38 => self.carry = Some(true),
+            // XCE
+            0xFB => if let Some(carry) = self.carry {
+                self.carry = Some(self.emulation_mode);
+                self.emulation_mode = carry;
+                if self.emulation_mode {
+                    self.is_16bit_mode = false;
+                    self.is_16bit_index = false;
+                }
+            },
+            _ => if matches!(
+                instruction.name,
+                "ADC" | "SBC" | "CMP" | "CPX" | "CPY" | "ASL" | "LSR" | "ROL" | "ROR" | "PLP" | "RTI"
+            ) {
+                self.carry = None;
+            },
+        };
+    }
+}
+
+impl Default for WidthState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisassembledInstruction {
+    pub address: u32,
+    pub opcode: u8,
+    pub name: &'static str,
+    pub mode: OperandMode,
+    /// Little endian operand value
+    pub operand: u32,
+    pub length: u8,
+    /// Resolved destination of branches, jumps and calls
+    pub target: Option<u32>,
+    /// Widths the instruction was decoded with
+    pub state: WidthState,
+}
+
+impl DisassembledInstruction {
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.opcode];
+        for i in 0..(self.length - 1) {
+            bytes.push((self.operand >> (i * 8)) as u8);
+        }
+        bytes
+    }
+
+    pub fn next_address(&self) -> u32 {
+        self.address + self.length as u32
+    }
+
+    fn resolve_target(address: u32, opcode: u8, mode: OperandMode, operand: u32) -> Option<u32> {
+        let bank = address & 0xFF0000;
+        let pc = address as u16;
+        match mode {
+            M::Relative => Some(bank | pc.wrapping_add(2).wrapping_add(operand as u8 as i8 as u16) as u32),
+            // PER pushes an address rather than jumping, but it's still relative to PC
+            M::RelativeLong => Some(bank | pc.wrapping_add(3).wrapping_add(operand as u16) as u32),
+            M::Absolute => match opcode {
+                // JSR, JMP
+                0x20 | 0x4C => Some(bank | operand),
+                _ => None,
+            },
+            M::AbsoluteLong => match opcode {
+                // JSL, JML
+                0x22 | 0x5C => Some(operand),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn format_absolute(&self, syntax: Syntax, index: &str) -> String {
+        // Make sure absolute operands in the zero page aren't assembled as direct page
+        let operand = self.operand as u16;
+        match (syntax, operand <= 0xFF) {
+            (Syntax::Ca65, true) => format!("a:${:04X}{}", operand, index),
+            _ => format!("${:04X}{}", operand, index),
+        }
+    }
+
+    fn format_long(&self, syntax: Syntax, index: &str) -> String {
+        match (syntax, self.operand <= 0xFFFF) {
+            (Syntax::Ca65, true) => format!("f:${:06X}{}", self.operand, index),
+            _ => format!("${:06X}{}", self.operand, index),
+        }
+    }
+
+    fn size_suffix(&self, syntax: Syntax) -> &'static str {
+        if syntax != Syntax::Asar {
+            return "";
+        }
+        match self.mode {
+            M::Absolute | M::AbsoluteX | M::AbsoluteY if self.operand <= 0xFF => ".w",
+            M::AbsoluteLong | M::AbsoluteLongX if self.operand <= 0xFFFF => ".l",
+            _ => "",
+        }
+    }
+
+    pub fn format_operand(&self, syntax: Syntax, labels: &HashMap<u32, String>) -> String {
+        if let Some(label) = self.target.and_then(|target| labels.get(&target)) {
+            return label.clone();
+        }
+        let byte = self.operand as u8;
+        match self.mode {
+            M::Implied => String::new(),
+            M::Accumulator => String::from("A"),
+            M::ImmediateM | M::ImmediateX => match self.length {
+                3 => format!("#${:04X}", self.operand),
+                _ => format!("#${:02X}", byte),
+            },
+            M::Immediate8 => format!("#${:02X}", byte),
+            M::Relative | M::RelativeLong => format!("${:06X}", self.target.unwrap_or_default()),
+            M::DirectPage => format!("${:02X}", byte),
+            M::DirectPageX => format!("${:02X},X", byte),
+            M::DirectPageY => format!("${:02X},Y", byte),
+            M::DirectPageIndirect => format!("(${:02X})", byte),
+            M::DirectPageIndirectLong => format!("[${:02X}]", byte),
+            M::DirectPageIndexedIndirect => format!("(${:02X},X)", byte),
+            M::DirectPageIndirectIndexed => format!("(${:02X}),Y", byte),
+            M::DirectPageIndirectLongIndexed => format!("[${:02X}],Y", byte),
+            M::StackRelative => format!("${:02X},S", byte),
+            M::StackRelativeIndirectIndexed => format!("(${:02X},S),Y", byte),
+            M::Absolute => self.format_absolute(syntax, ""),
+            M::AbsoluteX => self.format_absolute(syntax, ",X"),
+            M::AbsoluteY => self.format_absolute(syntax, ",Y"),
+            M::AbsoluteLong => self.format_long(syntax, ""),
+            M::AbsoluteLongX => self.format_long(syntax, ",X"),
+            M::AbsoluteIndirect => format!("(${:04X})", self.operand),
+            M::AbsoluteIndirectLong => format!("[${:04X}]", self.operand),
+            M::AbsoluteIndexedIndirect => format!("(${:04X},X)", self.operand),
+            // Machine code stores the destination bank first, but both assemblers expect the source first
+            M::BlockMove => format!("${:02X},${:02X}", self.operand >> 8, byte),
+        }
+    }
+
+    pub fn format(&self, syntax: Syntax, labels: &HashMap<u32, String>) -> String {
+        let operand = self.format_operand(syntax, labels);
+        let name = format!("{}{}", self.name, self.size_suffix(syntax));
+        match operand.is_empty() {
+            true => name,
+            false => format!("{} {}", name, operand),
+        }
+    }
+}
+
+impl Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(Syntax::Asar, &HashMap::new()))
+    }
+}
+
+/// Decodes the instruction at `address`, updating `state` with its side effects on the register widths
+pub fn decode<F: Fn(u32) -> u8>(read: F, address: u32, state: &mut WidthState) -> DisassembledInstruction {
+    let opcode = read(address);
+    let (name, mode) = opcode_info(opcode);
+    let operand_length = mode.operand_length(state);
+    let mut operand = 0;
+    for i in 0..operand_length {
+        operand |= (read(address + 1 + i as u32) as u32) << (i * 8);
+    }
+    let instruction = DisassembledInstruction {
+        address,
+        opcode,
+        name,
+        mode,
+        operand,
+        length: operand_length + 1,
+        target: DisassembledInstruction::resolve_target(address, opcode, mode, operand),
+        state: *state,
+    };
+    state.update(&instruction);
+    instruction
+}
+
+/// Linearly decodes every instruction starting in `start..end`
+pub fn disassemble<F: Fn(u32) -> u8>(read: F, start: u32, end: u32, state: WidthState) -> Vec<DisassembledInstruction> {
+    let mut state = state;
+    let mut instructions = vec![];
+    let mut address = start;
+    while address < end {
+        let instruction = decode(&read, address, &mut state);
+        address = instruction.next_address();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// Disassembles a range of the address space without triggering side effects on the bus
+pub fn disassemble_bus(bus: &Bus, start: u32, end: u32, state: WidthState) -> Vec<DisassembledInstruction> {
+    disassemble(|address| bus.read_external(address), start, end, state)
+}
+
+pub fn default_label_name(address: u32) -> String {
+    format!("L{:06X}", address)
+}
+
+/// Labels every branch, jump and call target that falls inside the given instructions
+pub fn generate_labels(instructions: &[DisassembledInstruction]) -> HashMap<u32, String> {
+    let addresses: BTreeSet<u32> = instructions.iter().map(|i| i.address).collect();
+    instructions.iter()
+        .filter_map(|i| i.target)
+        .filter(|target| addresses.contains(target))
+        .map(|target| (target, default_label_name(target)))
+        .collect()
+}
+
+fn width_directives(previous: Option<WidthState>, current: WidthState) -> Vec<&'static str> {
+    let mut directives = vec![];
+    if previous.map(|p| p.is_16bit_mode) != Some(current.is_16bit_mode) {
+        directives.push(if current.is_16bit_mode { ".a16" } else { ".a8" });
+    }
+    if previous.map(|p| p.is_16bit_index) != Some(current.is_16bit_index) {
+        directives.push(if current.is_16bit_index { ".i16" } else { ".i8" });
+    }
+    directives
+}
+
+/// Emits assembler source that rebuilds the same bytes.
+/// `labels` take precedence over the generated ones for branch targets.
+pub fn to_source(instructions: &[DisassembledInstruction], syntax: Syntax, labels: &HashMap<u32, String>) -> String {
+    let mut all_labels = generate_labels(instructions);
+    all_labels.extend(labels.iter().map(|(address, name)| (*address, name.clone())));
+
+    let mut source = String::new();
+    if syntax == Syntax::Ca65 {
+        source.push_str(".p816\n.smart -\n");
+    }
+    let mut expected_address = None;
+    let mut previous_state = None;
+    for instruction in instructions {
+        if expected_address != Some(instruction.address) {
+            match syntax {
+                Syntax::Asar => source.push_str(&format!("\norg ${:06X}\n", instruction.address)),
+                Syntax::Ca65 => source.push_str(&format!("\n.org ${:06X}\n", instruction.address)),
+            }
+        }
+        if syntax == Syntax::Ca65 {
+            for directive in width_directives(previous_state, instruction.state) {
+                source.push_str(&format!("{}\n", directive));
+            }
+        }
+        if let Some(label) = all_labels.get(&instruction.address) {
+            source.push_str(&format!("{}:\n", label));
+        }
+        let bytes: Vec<String> = instruction.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        source.push_str(&format!(
+            "    {:<24}; {:06X}: {}\n",
+            instruction.format(syntax, &all_labels),
+            instruction.address,
+            bytes.join(" "),
+        ));
+        expected_address = Some(instruction.next_address());
+        previous_state = Some(instruction.state);
+    }
+    source
+}
+
+
+#[cfg(test)]
+mod cpu_disasm_tests {
+    use super::*;
+
+    fn reader(bytes: &[u8], base: u32) -> impl Fn(u32) -> u8 + '_ {
+        move |address| *bytes.get((address - base) as usize).unwrap_or(&0x00)
+    }
+
+    fn native_state() -> WidthState {
+        WidthState {
+            is_16bit_mode: false,
+            is_16bit_index: false,
+            emulation_mode: false,
+            carry: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_addressing_modes() {
+        let bytes = [
+            0xBD, 0x34, 0x12,       // LDA $1234,X
+            0xBF, 0x56, 0x34, 0x12, // LDA $123456,X
+            0xB7, 0x10,             // LDA [$10],Y
+            0xB3, 0x02,             // LDA ($02,S),Y
+            0x54, 0x7F, 0x7E,       // MVN $7E,$7F
+            0xDC, 0x00, 0x02,       // JML [$0200]
+            0x0A,                   // ASL A
+        ];
+        let instructions = disassemble(reader(&bytes, 0x8000), 0x8000, 0x8000 + bytes.len() as u32, native_state());
+        let text: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, vec![
+            "LDA $1234,X",
+            "LDA $123456,X",
+            "LDA [$10],Y",
+            "LDA ($02,S),Y",
+            "MVN $7E,$7F",
+            "JML [$0200]",
+            "ASL A",
+        ]);
+    }
+
+    #[test]
+    fn test_width_tracking() {
+        let bytes = [
+            0xA9, 0x12,             // LDA #$12
+            0xC2, 0x30,             // REP #$30
+            0xA9, 0x34, 0x12,       // LDA #$1234
+            0xA2, 0x78, 0x56,       // LDX #$5678
+            0xE2, 0x20,             // SEP #$20
+            0xA9, 0x12,             // LDA #$12
+            0xA0, 0x34, 0x12,       // LDY #$1234
+            0x38,                   // SEC
+            0xFB,                   // XCE
+            0xA2, 0x12,             // LDX #$12
+        ];
+        let instructions = disassemble(reader(&bytes, 0x8000), 0x8000, 0x8000 + bytes.len() as u32, native_state());
+        let text: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, vec![
+            "LDA #$12", "REP #$30", "LDA #$1234", "LDX #$5678", "SEP #$20",
+            "LDA #$12", "LDY #$1234", "SEC", "XCE", "LDX #$12",
+        ]);
+        assert!(instructions.last().unwrap().state.emulation_mode);
+    }
+
+    #[test]
+    fn test_xce_to_native_mode() {
+        let bytes = [
+            0x18,               // CLC
+            0xFB,               // XCE
+            0xC2, 0x20,         // REP #$20
+            0xA9, 0x34, 0x12,   // LDA #$1234
+        ];
+        let instructions = disassemble(reader(&bytes, 0x8000), 0x8000, 0x8000 + bytes.len() as u32, WidthState::new());
+        assert_eq!(instructions[3].to_string(), "LDA #$1234");
+        assert_eq!(instructions[3].state.carry, Some(true));
+
+        // REP is ignored in emulation mode
+        let instructions = disassemble(reader(&bytes[2..], 0x8000), 0x8000, 0x8003, WidthState::new());
+        assert_eq!(instructions[1].length, 2);
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        let mut state = native_state();
+        // BNE -2 at the start of the bank
+        let instruction = decode(reader(&[0xD0, 0xFC], 0x808000), 0x808000, &mut state);
+        assert_eq!(instruction.target, Some(0x807FFE));
+        // Branches wrap around the bank
+        let instruction = decode(reader(&[0x80, 0x10], 0x80FFF8), 0x80FFF8, &mut state);
+        assert_eq!(instruction.target, Some(0x80000A));
+        let instruction = decode(reader(&[0x82, 0x00, 0x80], 0x808000), 0x808000, &mut state);
+        assert_eq!(instruction.target, Some(0x800003));
+        let instruction = decode(reader(&[0x20, 0x00, 0x90], 0x808000), 0x808000, &mut state);
+        assert_eq!(instruction.target, Some(0x809000));
+        let instruction = decode(reader(&[0x22, 0x00, 0x90, 0xC0], 0x808000), 0x808000, &mut state);
+        assert_eq!(instruction.target, Some(0xC09000));
+        let instruction = decode(reader(&[0xAD, 0x00, 0x90], 0x808000), 0x808000, &mut state);
+        assert_eq!(instruction.target, None);
+    }
+
+    #[test]
+    fn test_ambiguous_operand_sizes() {
+        let mut state = native_state();
+        let labels = HashMap::new();
+        let instruction = decode(reader(&[0xAD, 0x12, 0x00], 0), 0, &mut state);
+        assert_eq!(instruction.format(Syntax::Asar, &labels), "LDA.w $0012");
+        assert_eq!(instruction.format(Syntax::Ca65, &labels), "LDA a:$0012");
+        let instruction = decode(reader(&[0xAF, 0x12, 0x00, 0x00], 0), 0, &mut state);
+        assert_eq!(instruction.format(Syntax::Asar, &labels), "LDA.l $000012");
+        assert_eq!(instruction.format(Syntax::Ca65, &labels), "LDA f:$000012");
+    }
+
+    #[test]
+    fn test_to_source() {
+        let bytes = [
+            0xC2, 0x20,         // REP #$20
+            0xA9, 0x00, 0x00,   // LDA #$0000
+            0x1A,               // INC A
+            0xD0, 0xFD,         // BNE $808005
+            0x60,               // RTS
+        ];
+        let instructions = disassemble(reader(&bytes, 0x808000), 0x808000, 0x808009, native_state());
+        let source = to_source(&instructions, Syntax::Asar, &HashMap::new());
+        assert!(source.contains("org $808000\n"));
+        assert!(source.contains("L808005:\n    INC A"));
+        assert!(source.contains("BNE L808005"));
+        assert!(source.contains("; 808002: A9 00 00"));
+
+        let mut labels = HashMap::new();
+        labels.insert(0x808005, String::from("loop"));
+        let source = to_source(&instructions, Syntax::Ca65, &labels);
+        assert!(source.starts_with(".p816\n"));
+        assert!(source.contains(".a8\n.i8\n    REP #$20"));
+        assert!(source.contains(".a16\n    LDA #$0000"));
+        assert!(source.contains("loop:\n    INC A"));
+        assert!(source.contains("BNE loop"));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let bytes = [0x54, 0x7F, 0x7E];
+        let instruction = decode(reader(&bytes, 0), 0, &mut native_state());
+        assert_eq!(instruction.bytes(), bytes.to_vec());
+    }
+}
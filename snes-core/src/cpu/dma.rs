@@ -1,4 +1,9 @@
+use std::collections::VecDeque;
+
+use super::MASTER_CYCLES_PER_CPU_CYCLE;
+
 pub const MDMAEN: u16       = 0x420B;  // Select General Purpose DMA Channel(s) and Start Transfer (W)
+pub const HDMAEN: u16       = 0x420C;  // Select H-Blank DMA (H-DMA) Channel(s) (W)
 
 pub const DMAPX: u16        = 0x4300;  // DMA/HDMA Parameters (R/W)
 pub const BBADX: u16        = 0x4301;  // DMA/HDMA I/O-Bus Address (PPU-Bus aka B-Bus) (R/W)
@@ -9,6 +14,14 @@ pub const A1BX:  u16        = 0x4304;  // HDMA Table Start Address (bank) / DMA
 pub const DASXL: u16        = 0x4305;  // Indirect HDMA Address (low) / DMA Byte-Counter (low) (R/W)
 pub const DASXH: u16        = 0x4306;  // Indirect HDMA Address (hi) / DMA Byte-Counter (hi) (R/W)
 pub const DASBX: u16        = 0x4307;  // Indirect HDMA Address (bank) (R/W)
+pub const A2AXL: u16        = 0x4308;  // HDMA Table Current Address (low) (R/W)
+pub const A2AXH: u16        = 0x4309;  // HDMA Table Current Address (high) (R/W)
+pub const NTRLX: u16        = 0x430A;  // HDMA Line-Counter (from current Table entry) (R/W)
+
+// Costs in master cycles, the CPU is halted meanwhile
+pub const SETUP_CYCLES: usize = 18;
+pub const CHANNEL_CYCLES: usize = 8;
+pub const BYTE_CYCLES: usize = 8;
 
 #[derive(PartialEq, Debug)]
 pub enum DMAChannel {
@@ -22,6 +35,7 @@ pub enum DMAChannel {
     Channel7,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TransferDirection {
     AtoB,
     BtoA,
@@ -47,6 +61,64 @@ pub enum TransferFormat {
     FourBytesFourRegisters, // Write once
 }
 
+impl TransferDirection {
+    pub fn from_params(params: u8) -> Self {
+        match params >> 7 == 1 {
+            false => TransferDirection::AtoB,
+            true => TransferDirection::BtoA,
+        }
+    }
+}
+
+impl DMAAddressingMode {
+    pub fn from_params(params: u8) -> Self {
+        match (params >> 6) & 1 == 1 {
+            false => DMAAddressingMode::Direct,
+            true => DMAAddressingMode::Indirect,
+        }
+    }
+}
+
+impl AutoUpdateA {
+    pub fn from_params(params: u8) -> Self {
+        match (params >> 3) & 0b11 {
+            0b00 => AutoUpdateA::Increment,
+            0b10 => AutoUpdateA::Decrement,
+            0b01 | 0b11 => AutoUpdateA::Neither,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl TransferFormat {
+    pub fn from_params(params: u8) -> Self {
+        match params & 0b111 {
+            0 => TransferFormat::OneByteOneRegister,
+            1 => TransferFormat::TwoBytesTwoRegisters,
+            2 => TransferFormat::TwoBytesOneRegister,
+            3 => TransferFormat::FourBytesTwoRegisters,
+            4 => TransferFormat::FourBytesFourRegisters,
+            5 => TransferFormat::FourBytesTwoRegistersSequential,
+            6 => TransferFormat::TwoBytesOneRegister,
+            7 => TransferFormat::FourBytesTwoRegisters,
+            _ => unreachable!(),
+        }
+    }
+
+    /// B-bus address offset of each byte in one unit of the format.
+    /// HDMA transfers a whole unit every line.
+    pub fn b_bus_offsets(&self) -> &'static [u8] {
+        match self {
+            TransferFormat::OneByteOneRegister => &[0],
+            TransferFormat::TwoBytesTwoRegisters => &[0, 1],
+            TransferFormat::TwoBytesOneRegister => &[0, 0],
+            TransferFormat::FourBytesTwoRegisters => &[0, 0, 1, 1],
+            TransferFormat::FourBytesFourRegisters => &[0, 1, 2, 3],
+            TransferFormat::FourBytesTwoRegistersSequential => &[0, 1, 0, 1],
+        }
+    }
+}
+
 #[allow(dead_code)] pub struct DMATransferProps {
     channel: DMAChannel,
     channel_number: u8,
@@ -57,6 +129,8 @@ pub enum TransferFormat {
     a_bus_address: u32,
     b_bus_address: u8,
     number_of_bytes: u16,
+    /// Bytes moved so far, picks the B-bus register from the transfer format
+    bytes_transferred: usize,
 }
 
 impl DMATransferProps {
@@ -84,31 +158,10 @@ impl DMATransferProps {
             _ => unreachable!(),
         };
         let params = DMATransferProps::read_channel_register(registers, DMAPX, channel_number);
-        let direction = match params >> 7 == 1 {
-            false => TransferDirection::AtoB,
-            true => TransferDirection::BtoA,
-        };
-        let addressing_mode= match (params >> 6) & 1 == 1 {
-            false => DMAAddressingMode::Direct,
-            true => DMAAddressingMode::Indirect,
-        };
-        let auto_update_a_address = match (params >> 3) & 0b11 {
-            0b00 => AutoUpdateA::Increment,
-            0b10 => AutoUpdateA::Decrement,
-            0b01 | 0b11 => AutoUpdateA::Neither,
-            _ => unreachable!(),
-        };
-        let transfer_format = match params & 0b111 {
-            0 => TransferFormat::OneByteOneRegister,
-            1 => TransferFormat::TwoBytesTwoRegisters,
-            2 => TransferFormat::TwoBytesOneRegister,
-            3 => TransferFormat::FourBytesTwoRegisters,
-            4 => TransferFormat::FourBytesFourRegisters,
-            5 => TransferFormat::FourBytesTwoRegistersSequential,
-            6 => TransferFormat::TwoBytesOneRegister,
-            7 => TransferFormat::FourBytesTwoRegisters,
-            _ => unreachable!(),
-        };
+        let direction = TransferDirection::from_params(params);
+        let addressing_mode = DMAAddressingMode::from_params(params);
+        let auto_update_a_address = AutoUpdateA::from_params(params);
+        let transfer_format = TransferFormat::from_params(params);
         let a_bus_address =
             (DMATransferProps::read_channel_register(registers, A1BX, channel_number) as u32) << 16 |
             (DMATransferProps::read_channel_register(registers, A1TXH, channel_number) as u32) << 8 |
@@ -128,102 +181,132 @@ impl DMATransferProps {
             a_bus_address,
             b_bus_address,
             number_of_bytes,
+            bytes_transferred: 0,
         }
     }
 
     fn save_channel_state(&self, registers: &mut [u8]) {
-        // Update B Bus address
-        DMATransferProps::write_channel_register(registers, BBADX, self.channel_number, self.b_bus_address);
-        // Update A Bus address
-        DMATransferProps::write_channel_register(registers, A1BX, self.channel_number, (self.a_bus_address >> 16) as u8);
+        // Update A Bus address, the bank never changes
         DMATransferProps::write_channel_register(registers, A1TXH, self.channel_number, (self.a_bus_address >> 8) as u8);
-        DMATransferProps::write_channel_register(registers, A1BX, self.channel_number, self.a_bus_address as u8);
+        DMATransferProps::write_channel_register(registers, A1TXL, self.channel_number, self.a_bus_address as u8);
         // Update Byte count
         DMATransferProps::write_channel_register(registers, DASXH, self.channel_number, (self.number_of_bytes >> 8) as u8);
         DMATransferProps::write_channel_register(registers, DASXL, self.channel_number, self.number_of_bytes as u8);
     }
 
-    pub fn tick(&mut self, registers: &mut [u8]) -> Vec<(u32, u32)> {
-        let source_address = match self.direction {
-            TransferDirection::AtoB => self.a_bus_address,
-            TransferDirection::BtoA => 0x002100 | (self.b_bus_address as u32),
-        };
-        let dest_address = match self.direction {
-            TransferDirection::AtoB => 0x002100 | (self.b_bus_address as u32),
-            TransferDirection::BtoA => self.a_bus_address,
-        };
+    /// Moves the channel on by one byte, returning the A-bus address and B-bus register
+    /// (the low byte of $21xx) it goes between
+    pub fn tick(&mut self, registers: &mut [u8]) -> (u32, u8) {
+        let offsets = self.transfer_format.b_bus_offsets();
+        let b_bus_address = self.b_bus_address.wrapping_add(offsets[self.bytes_transferred % offsets.len()]);
+        let a_bus_address = self.a_bus_address;
 
-        let mut pending_bus_writes = Vec::new();
-        match self.transfer_format {
-            TransferFormat::OneByteOneRegister => {
-                // Transfer 1 byte    xx                    ;eg. for WRAM (port 2180h)
-                pending_bus_writes.push((source_address, dest_address));
-            },
-            TransferFormat::TwoBytesTwoRegisters => {
-                // Transfer 2 bytes   xx, xx+1              ;eg. for VRAM (port 2118h/19h)
-                for index in 0..2 {
-                    pending_bus_writes.push((
-                        source_address.wrapping_add(index),
-                        dest_address.wrapping_add(index),
-                    ));
-                }
-            },
-            TransferFormat::TwoBytesOneRegister => {
-                // Transfer 2 bytes   xx, xx                ;eg. for OAM or CGRAM
-                for index in 0..2 {
-                    pending_bus_writes.push((
-                        source_address.wrapping_add(index),
-                        dest_address,
-                    ));
-                }
-            },
-            TransferFormat::FourBytesTwoRegisters => {
-                // Transfer 4 bytes   xx, xx,   xx+1, xx+1  ;eg. for BGnxOFS, M7x
-                for index in 0..4 {
-                    pending_bus_writes.push((
-                        source_address.wrapping_add(index),
-                        dest_address.wrapping_add(index / 2),
-                    ));
-                }
-            },
-            TransferFormat::FourBytesFourRegisters => {
-                // Transfer 4 bytes   xx, xx+1, xx+2, xx+3  ;eg. for BGnSC, Window, APU..
-                for index in 0..4 {
-                    pending_bus_writes.push((
-                        source_address.wrapping_add(index),
-                        dest_address.wrapping_add(index),
-                    ));
-                }
-            },
-            TransferFormat::FourBytesTwoRegistersSequential => {
-                // Transfer 4 bytes   xx, xx+1, xx,   xx+1  ;whatever purpose, VRAM maybe
-                for index in 0..4 {
-                    pending_bus_writes.push((
-                        source_address.wrapping_add(index),
-                        dest_address.wrapping_add(index & 1),
-                    ));
-                }
-            },
+        self.bytes_transferred += 1;
+        // A byte count of 0 transfers 64KB
+        self.number_of_bytes = self.number_of_bytes.wrapping_sub(1);
+        let bank = self.a_bus_address & 0xFF0000;
+        let address = self.a_bus_address as u16;
+        let address = match self.auto_update_a_address {
+            AutoUpdateA::Increment => address.wrapping_add(1),
+            AutoUpdateA::Decrement => address.wrapping_sub(1),
+            AutoUpdateA::Neither => address,
         };
+        self.a_bus_address = bank | address as u32;
+
+        self.save_channel_state(registers);
+        (a_bus_address, b_bus_address)
+    }
+
+    /// A byte count of 0 means 64KB
+    pub fn total_bytes(&self) -> usize {
+        match self.number_of_bytes {
+            0 => 0x10000,
+            count => count as usize,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.bytes_transferred > 0 && self.number_of_bytes == 0
+    }
+}
+
+/// A DMA transfer as it starts, or one HDMA channel's transfer on a line
+#[derive(Debug, Clone, PartialEq)]
+pub struct DMALogEntry {
+    pub frame: u64,
+    pub scanline: u16,
+    pub channel_number: u8,
+    pub is_hdma: bool,
+    pub source: u32,
+    pub destination: u32,
+    pub length: usize,
+}
+
+/// Keeps the last `capacity` transfers
+pub struct DMATransferLog {
+    entries: VecDeque<DMALogEntry>,
+    capacity: usize,
+    /// HDMA logs an entry per channel and line, which quickly pushes the DMA transfers out
+    pub include_hdma: bool,
+}
+
+impl DMATransferLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            include_hdma: true,
+        }
+    }
 
-        if self.number_of_bytes > 0 {
-            self.number_of_bytes -= 1;
+    /// HDMA entries are dropped unless `include_hdma` is set
+    pub fn push(&mut self, entry: DMALogEntry) {
+        if entry.is_hdma && !self.include_hdma {
+            return;
         }
-        match self.auto_update_a_address {
-            AutoUpdateA::Increment => self.a_bus_address = self.a_bus_address.wrapping_add(1),
-            AutoUpdateA::Decrement => self.a_bus_address = self.a_bus_address.wrapping_sub(1),
-            AutoUpdateA::Neither => {},
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
         }
+        self.entries.push_back(entry);
+    }
 
-        self.save_channel_state(registers);
-        pending_bus_writes
+    /// Oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &DMALogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
     }
 }
 
+/// One byte moved by general purpose DMA
+pub struct DMAByteTransfer {
+    pub channel_number: u8,
+    pub direction: TransferDirection,
+    pub a_bus_address: u32,
+    pub b_bus_address: u8,
+    /// What the byte cost, plus the channel and setup overheads on a first byte
+    pub master_cycles: usize,
+}
+
 
 pub struct DMA {
     pub active_dma_transfers: Vec<DMATransferProps>,
     registers: [u8; 0x1D00],
+    /// Set by MDMAEN, until the first byte pays the setup overhead
+    is_setup_pending: bool,
+    /// Last value written to HDMAEN
+    pub hdma_enabled: u8,
+    /// Whether each HDMA channel transfers on the next line (cleared by line counters without the repeat bit)
+    pub hdma_do_transfer: [bool; 8],
+    /// Whether each HDMA channel reached the end of its table this frame
+    pub hdma_terminated: [bool; 8],
+    /// When set, every transfer is recorded here (used by the debugger)
+    pub transfer_log: Option<DMATransferLog>,
+    /// Master cycles spent but not charged to the CPU yet, as the setup overhead
+    /// isn't a whole number of CPU cycles
+    uncharged_master_cycles: usize,
 }
 
 impl DMA {
@@ -231,9 +314,50 @@ impl DMA {
         Self {
             active_dma_transfers: Vec::new(),
             registers: [0; 0x1D00],
+            is_setup_pending: false,
+            hdma_enabled: 0,
+            hdma_do_transfer: [false; 8],
+            hdma_terminated: [false; 8],
+            transfer_log: None,
+            uncharged_master_cycles: 0,
         }
     }
 
+    /// Converts `master_cycles` to whole CPU cycles, carrying the rest over to the next call
+    pub fn charge_master_cycles(&mut self, master_cycles: usize) -> usize {
+        let total = self.uncharged_master_cycles + master_cycles;
+        self.uncharged_master_cycles = total % MASTER_CYCLES_PER_CPU_CYCLE;
+        total / MASTER_CYCLES_PER_CPU_CYCLE
+    }
+
+    pub fn read_channel_register(&self, register: u16, channel_number: u8) -> u8 {
+        DMATransferProps::read_channel_register(&self.registers, register, channel_number)
+    }
+
+    pub fn write_channel_register(&mut self, register: u16, channel_number: u8, value: u8) {
+        DMATransferProps::write_channel_register(&mut self.registers, register, channel_number, value);
+    }
+
+    /// Reads a 16-bit channel register from its low and high halves
+    pub fn read_channel_word(&self, low_register: u16, high_register: u16, channel_number: u8) -> u16 {
+        (self.read_channel_register(high_register, channel_number) as u16) << 8 |
+        self.read_channel_register(low_register, channel_number) as u16
+    }
+
+    pub fn write_channel_word(&mut self, low_register: u16, high_register: u16, channel_number: u8, value: u16) {
+        self.write_channel_register(low_register, channel_number, value as u8);
+        self.write_channel_register(high_register, channel_number, (value >> 8) as u8);
+    }
+
+    pub fn is_hdma_channel_enabled(&self, channel_number: u8) -> bool {
+        self.hdma_enabled & (1 << channel_number) != 0
+    }
+
+    /// HDMA on a channel stops any general purpose DMA still running on it
+    pub fn terminate_dma_on_channel(&mut self, channel_number: u8) {
+        self.active_dma_transfers.retain(|transfer| transfer.channel_number != channel_number);
+    }
+
     fn _read(&self, address: u16) -> u8 {
         self.registers[(address - 0x4300) as usize]
     }
@@ -242,27 +366,21 @@ impl DMA {
         self.registers[(address - 0x4300) as usize] = value;
     }
 
+    /// Queues the channels selected in MDMAEN, lowest channel first
     pub fn prepare_dma_transfer(&mut self, dma_select: u8) {
-        self.active_dma_transfers = Vec::new();
-        let mut active_channels = [false; 8];
-        for (i, active_channel) in active_channels.iter_mut().enumerate() {
-            *active_channel = (dma_select & (1 << i)) != 0;
-        }
-
-        for (channel, is_active) in active_channels.iter().enumerate() {
-            if !is_active {
-                continue;
-            }
-            let props = DMATransferProps::new_from_registers(
-                &self.registers,
-                channel as u8,
-            );
-            self.active_dma_transfers.push(props);
-        }
+        self.active_dma_transfers = (0..8)
+            .filter(|channel| dma_select & (1 << channel) != 0)
+            .map(|channel| DMATransferProps::new_from_registers(&self.registers, channel))
+            .collect();
+        self.is_setup_pending = self.is_active();
     }
 
-    pub fn read(&self, address: u16) -> u8 {
-        self._read(address)
+    /// $43xC-$43xE and everything past the channel registers ($4380-$5FFF) is open bus
+    pub fn read(&self, address: u16, cpu_mdr: u8) -> u8 {
+        match address {
+            0x4300..=0x437F if !matches!(address & 0xF, 0xC..=0xE) => self._read(address),
+            _ => cpu_mdr,
+        }
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
@@ -273,16 +391,51 @@ impl DMA {
         !self.active_dma_transfers.is_empty()
     }
 
-    pub fn tick(&mut self) -> Vec<(u32, u32)> {
-        if !self.is_active() {
-            return vec![];
+    pub fn log_transfer(&mut self, entry: DMALogEntry) {
+        if let Some(transfer_log) = &mut self.transfer_log {
+            transfer_log.push(entry);
         }
+    }
 
-        let pending_bus_writes = self.active_dma_transfers[0].tick(&mut self.registers);
-        if self.active_dma_transfers[0].number_of_bytes == 0 {
+    /// Moves the next byte of the highest priority channel. `frame` and `scanline` only go to the transfer log.
+    pub fn tick(&mut self, frame: u64, scanline: u16) -> Option<DMAByteTransfer> {
+        let transfer = self.active_dma_transfers.first_mut()?;
+        let mut master_cycles = BYTE_CYCLES;
+        if transfer.bytes_transferred == 0 {
+            master_cycles += CHANNEL_CYCLES;
+            if let Some(transfer_log) = &mut self.transfer_log {
+                let b_bus_address = 0x002100 | transfer.b_bus_address as u32;
+                let (source, destination) = match transfer.direction {
+                    TransferDirection::AtoB => (transfer.a_bus_address, b_bus_address),
+                    TransferDirection::BtoA => (b_bus_address, transfer.a_bus_address),
+                };
+                transfer_log.push(DMALogEntry {
+                    frame,
+                    scanline,
+                    channel_number: transfer.channel_number,
+                    is_hdma: false,
+                    source,
+                    destination,
+                    length: transfer.total_bytes(),
+                });
+            }
+        }
+        if self.is_setup_pending {
+            master_cycles += SETUP_CYCLES;
+            self.is_setup_pending = false;
+        }
+        let (a_bus_address, b_bus_address) = transfer.tick(&mut self.registers);
+        let result = DMAByteTransfer {
+            channel_number: transfer.channel_number,
+            direction: transfer.direction,
+            a_bus_address,
+            b_bus_address,
+            master_cycles,
+        };
+        if transfer.is_finished() {
             self.active_dma_transfers.remove(0);
         }
-        pending_bus_writes
+        Some(result)
     }
 }
 
@@ -0,0 +1,308 @@
+// H-Blank DMA. Channels enabled in HDMAEN are set up at the start of each frame
+// and then move a few bytes to the B-bus on every visible line, following a table
+// of line counters and data (or pointers to data, in indirect mode).
+use super::bus::Bus;
+use super::dma::{
+    DMALogEntry, DMAAddressingMode, TransferDirection, TransferFormat, SETUP_CYCLES, CHANNEL_CYCLES, BYTE_CYCLES,
+    DMAPX, BBADX, A1TXL, A1TXH, A1BX, DASXL, DASXH, DASBX, A2AXL, A2AXH, NTRLX,
+};
+
+const DOTS_PER_LINE: usize = 340;
+const LINES_PER_FRAME: usize = 262;
+
+/// Dot of line 0 where the channels are initialized
+pub const INIT_DOT: u16 = 6;
+/// Dot where each line's transfers happen, right after the last visible pixel
+pub const TRANSFER_DOT: u16 = 278;
+pub const LAST_TRANSFER_LINE: u16 = 224;
+
+/// Master cycles to read the 2 bytes of an indirect pointer
+const INDIRECT_ADDRESS_CYCLES: usize = 16;
+
+/// Runs the HDMA work the PPU went past since it was at `previous` (V, H).
+/// Returns the CPU cycles it took, the leftover master cycles carry over like DMA's.
+pub fn tick(bus: &mut Bus, previous: (u16, u16)) -> usize {
+    let current = (bus.ppu.registers.v_count, bus.ppu.registers.h_count);
+    let mut master_cycles = 0;
+    if has_passed(previous, current, (0, INIT_DOT)) {
+        master_cycles += init(bus);
+    }
+    // Runs every instruction, so no allocation here
+    let line_count = if previous.0 == current.0 { 1 } else { 2 };
+    for &line in [previous.0, current.0].iter().take(line_count) {
+        if line <= LAST_TRANSFER_LINE && has_passed(previous, current, (line, TRANSFER_DOT)) {
+            master_cycles += run_line(bus);
+        }
+    }
+    bus.dma.charge_master_cycles(master_cycles)
+}
+
+/// Whether going from `from` to `to` went past `point`, wrapping at the end of the frame
+fn has_passed(from: (u16, u16), to: (u16, u16), point: (u16, u16)) -> bool {
+    let frame_dots = DOTS_PER_LINE * LINES_PER_FRAME;
+    let position = |(v, h): (u16, u16)| v as usize * DOTS_PER_LINE + h as usize;
+    let distance = (position(point) + frame_dots - position(from)) % frame_dots;
+    let step = (position(to) + frame_dots - position(from)) % frame_dots;
+    distance > 0 && distance <= step
+}
+
+/// Restarts every enabled channel at the top of its table. Returns the master cycles it took.
+pub fn init(bus: &mut Bus) -> usize {
+    bus.dma.hdma_do_transfer = [true; 8];
+    bus.dma.hdma_terminated = [false; 8];
+    if bus.dma.hdma_enabled == 0 {
+        return 0;
+    }
+    let mut master_cycles = SETUP_CYCLES;
+    for channel in 0..8 {
+        if !bus.dma.is_hdma_channel_enabled(channel) {
+            continue;
+        }
+        bus.dma.terminate_dma_on_channel(channel);
+        let table_address = bus.dma.read_channel_word(A1TXL, A1TXH, channel);
+        bus.dma.write_channel_word(A2AXL, A2AXH, channel, table_address);
+        master_cycles += CHANNEL_CYCLES + load_table_entry(bus, channel);
+    }
+    master_cycles
+}
+
+/// Transfers the current line's data for every running channel and advances
+/// their line counters. Returns the master cycles it took.
+pub fn run_line(bus: &mut Bus) -> usize {
+    let is_running = |bus: &Bus, channel: u8| {
+        bus.dma.is_hdma_channel_enabled(channel) && !bus.dma.hdma_terminated[channel as usize]
+    };
+    if !(0..8).any(|channel| is_running(bus, channel)) {
+        return 0;
+    }
+    let mut master_cycles = SETUP_CYCLES;
+    for channel in 0..8 {
+        if !is_running(bus, channel) {
+            continue;
+        }
+        bus.dma.terminate_dma_on_channel(channel);
+        master_cycles += CHANNEL_CYCLES;
+        if bus.dma.hdma_do_transfer[channel as usize] {
+            master_cycles += transfer_unit(bus, channel);
+        }
+        let line_counter = bus.dma.read_channel_register(NTRLX, channel).wrapping_sub(1);
+        bus.dma.write_channel_register(NTRLX, channel, line_counter);
+        // With the repeat bit set, the entry's data is transferred on every line
+        bus.dma.hdma_do_transfer[channel as usize] = line_counter & 0x80 != 0;
+        if line_counter & 0x7F == 0 {
+            master_cycles += load_table_entry(bus, channel);
+        }
+    }
+    master_cycles
+}
+
+fn read_table_byte(bus: &mut Bus, channel: u8) -> u8 {
+    let bank = bus.dma.read_channel_register(A1BX, channel) as u32;
+    let address = bus.dma.read_channel_word(A2AXL, A2AXH, channel);
+    bus.dma.write_channel_word(A2AXL, A2AXH, channel, address.wrapping_add(1));
+    bus.read_dma_a_bus((bank << 16) | address as u32)
+}
+
+/// Reads the next line counter, and the data pointer in indirect mode.
+/// A line counter of 0 ends the table for the rest of the frame.
+fn load_table_entry(bus: &mut Bus, channel: u8) -> usize {
+    let mut master_cycles = BYTE_CYCLES;
+    let line_counter = read_table_byte(bus, channel);
+    bus.dma.write_channel_register(NTRLX, channel, line_counter);
+    let params = bus.dma.read_channel_register(DMAPX, channel);
+    if let DMAAddressingMode::Indirect = DMAAddressingMode::from_params(params) {
+        let low = read_table_byte(bus, channel);
+        let high = read_table_byte(bus, channel);
+        bus.dma.write_channel_register(DASXL, channel, low);
+        bus.dma.write_channel_register(DASXH, channel, high);
+        master_cycles += INDIRECT_ADDRESS_CYCLES;
+    }
+    bus.dma.hdma_terminated[channel as usize] = line_counter == 0;
+    bus.dma.hdma_do_transfer[channel as usize] = true;
+    master_cycles
+}
+
+/// Moves one unit of the channel's transfer format, from the table itself in direct
+/// mode or from the DASBx:DASx pointer in indirect mode
+fn transfer_unit(bus: &mut Bus, channel: u8) -> usize {
+    let params = bus.dma.read_channel_register(DMAPX, channel);
+    let b_bus_address = bus.dma.read_channel_register(BBADX, channel);
+    let offsets = TransferFormat::from_params(params).b_bus_offsets();
+    let direction = TransferDirection::from_params(params);
+    let mut start_address = None;
+    for offset in offsets {
+        let a_bus_address = match DMAAddressingMode::from_params(params) {
+            DMAAddressingMode::Direct => {
+                let bank = bus.dma.read_channel_register(A1BX, channel) as u32;
+                let address = bus.dma.read_channel_word(A2AXL, A2AXH, channel);
+                bus.dma.write_channel_word(A2AXL, A2AXH, channel, address.wrapping_add(1));
+                (bank << 16) | address as u32
+            },
+            DMAAddressingMode::Indirect => {
+                let bank = bus.dma.read_channel_register(DASBX, channel) as u32;
+                let address = bus.dma.read_channel_word(DASXL, DASXH, channel);
+                bus.dma.write_channel_word(DASXL, DASXH, channel, address.wrapping_add(1));
+                (bank << 16) | address as u32
+            },
+        };
+        start_address.get_or_insert(a_bus_address);
+        bus.transfer_dma_byte(direction, a_bus_address, b_bus_address.wrapping_add(*offset));
+    }
+    if bus.dma.transfer_log.as_ref().is_some_and(|log| log.include_hdma) {
+        let a_bus_address = start_address.unwrap_or_default();
+        let b_bus_address = 0x002100 | b_bus_address as u32;
+        let (source, destination) = match direction {
+            TransferDirection::AtoB => (a_bus_address, b_bus_address),
+            TransferDirection::BtoA => (b_bus_address, a_bus_address),
+        };
+        bus.dma.log_transfer(DMALogEntry {
+            frame: bus.ppu.frame_count,
+            scanline: bus.ppu.registers.v_count,
+            channel_number: channel,
+            is_hdma: true,
+            source,
+            destination,
+            length: offsets.len(),
+        });
+    }
+    offsets.len() * BYTE_CYCLES
+}
+
+
+#[cfg(test)]
+mod cpu_hdma_tests {
+    use super::*;
+    use crate::cpu::dma::{HDMAEN, MDMAEN};
+    use crate::cpu::bus::{WMADDL, WMADDM, WMADDH};
+    use crate::rom::test_memory::TestMemory;
+
+    fn write_bytes(bus: &mut Bus, address: u32, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            bus.write(address + i as u32, *byte);
+        }
+    }
+
+    fn new_bus() -> Bus {
+        let mut bus = Bus::new();
+        bus.rom = Box::new(TestMemory::new());
+        bus
+    }
+
+    /// Channel 0 writing to the WRAM port, so every transferred byte can be read back.
+    /// Tables live in the cartridge, as WRAM can't be a source for the port.
+    fn setup_channel(bus: &mut Bus, params: u8, table: u32) {
+        bus.write(WMADDL as u32, 0x00);
+        bus.write(WMADDM as u32, 0x00);
+        bus.write(WMADDH as u32, 0x01);
+        bus.write(0x4300, params);
+        bus.write(0x4301, 0x80);
+        bus.write(0x4302, table as u8);
+        bus.write(0x4303, (table >> 8) as u8);
+        bus.write(0x4304, (table >> 16) as u8);
+        bus.write(HDMAEN as u32, 0x01);
+    }
+
+    fn transferred(bus: &Bus, count: u32) -> Vec<u8> {
+        (0..count).map(|i| bus.read_external(0x7F_0000 + i)).collect()
+    }
+
+    #[test]
+    fn test_direct_table() {
+        let mut bus = new_bus();
+        // 2 lines of $11, then 3 lines repeating $21 $22 $23, then the end
+        write_bytes(&mut bus, 0x01_8000, &[0x02, 0x11, 0x83, 0x21, 0x22, 0x23, 0x00]);
+        setup_channel(&mut bus, 0x00, 0x01_8000);
+        init(&mut bus);
+        assert_eq!(bus.dma.read_channel_register(NTRLX, 0), 0x02);
+        for _ in 0..6 {
+            run_line(&mut bus);
+        }
+        assert_eq!(transferred(&bus, 5), vec![0x11, 0x21, 0x22, 0x23, 0x00]);
+        assert!(bus.dma.hdma_terminated[0]);
+        assert_eq!(bus.dma.read_channel_word(A2AXL, A2AXH, 0), 0x8007);
+    }
+
+    #[test]
+    fn test_indirect_table() {
+        let mut bus = new_bus();
+        write_bytes(&mut bus, 0x01_9000, &[0xAA, 0xBB, 0xCC]);
+        // One line from $9000, then two lines repeating from $9001
+        write_bytes(&mut bus, 0x01_8000, &[0x01, 0x00, 0x90, 0x82, 0x01, 0x90, 0x00]);
+        setup_channel(&mut bus, 0x40, 0x01_8000);
+        bus.dma.transfer_log = Some(crate::cpu::dma::DMATransferLog::new(16));
+        bus.write(0x4307, 0x01);
+        init(&mut bus);
+        assert_eq!(bus.dma.read_channel_word(DASXL, DASXH, 0), 0x9000);
+        for _ in 0..3 {
+            run_line(&mut bus);
+        }
+        assert_eq!(transferred(&bus, 4), vec![0xAA, 0xBB, 0xCC, 0x00]);
+        let log = bus.dma.transfer_log.as_ref().unwrap();
+        let sources: Vec<u32> = log.entries().map(|entry| entry.source).collect();
+        assert_eq!(sources, vec![0x01_9000, 0x01_9001, 0x01_9002]);
+        assert!(log.entries().all(|entry| entry.is_hdma && entry.destination == 0x00_2180 && entry.length == 1));
+        assert!(bus.dma.hdma_terminated[0]);
+        // The terminating entry still reads a pointer, from the bytes after the 0
+        assert_eq!(bus.dma.read_channel_word(A2AXL, A2AXH, 0), 0x8009);
+    }
+
+    #[test]
+    fn test_transfer_log_without_hdma() {
+        let mut bus = new_bus();
+        write_bytes(&mut bus, 0x01_8000, &[0x82, 0x11, 0x22, 0x00]);
+        setup_channel(&mut bus, 0x00, 0x01_8000);
+        let mut transfer_log = crate::cpu::dma::DMATransferLog::new(16);
+        transfer_log.include_hdma = false;
+        bus.dma.transfer_log = Some(transfer_log);
+        init(&mut bus);
+        for _ in 0..2 {
+            run_line(&mut bus);
+        }
+        assert_eq!(transferred(&bus, 2), vec![0x11, 0x22]);
+        assert_eq!(bus.dma.transfer_log.as_ref().unwrap().entries().count(), 0);
+    }
+
+    #[test]
+    fn test_disabled_channels_dont_transfer() {
+        let mut bus = new_bus();
+        write_bytes(&mut bus, 0x01_8000, &[0x01, 0x11, 0x00]);
+        setup_channel(&mut bus, 0x00, 0x01_8000);
+        bus.write(HDMAEN as u32, 0x00);
+        assert_eq!(init(&mut bus), 0);
+        assert_eq!(run_line(&mut bus), 0);
+        assert_eq!(transferred(&bus, 1), vec![0x00]);
+    }
+
+    #[test]
+    fn test_hdma_terminates_dma_on_the_same_channel() {
+        let mut bus = new_bus();
+        write_bytes(&mut bus, 0x01_8000, &[0x01, 0x11, 0x00]);
+        setup_channel(&mut bus, 0x00, 0x01_8000);
+        bus.write(0x4305, 0x10);
+        bus.write(0x4315, 0x10);
+        bus.write(MDMAEN as u32, 0x03);
+        assert_eq!(bus.dma.active_dma_transfers.len(), 2);
+        init(&mut bus);
+        assert_eq!(bus.dma.active_dma_transfers.len(), 1);
+    }
+
+    #[test]
+    fn test_tick_schedule() {
+        let mut bus = new_bus();
+        write_bytes(&mut bus, 0x01_8000, &[0x7F, 0x11, 0x00]);
+        setup_channel(&mut bus, 0x00, 0x01_8000);
+        bus.ppu.registers.v_count = 0;
+        bus.ppu.registers.h_count = 10;
+        assert!(tick(&mut bus, (261, 338)) > 0);
+        assert_eq!(bus.dma.read_channel_register(NTRLX, 0), 0x7F);
+        // Not at the transfer dot yet
+        bus.ppu.registers.h_count = 200;
+        assert_eq!(tick(&mut bus, (0, 10)), 0);
+        bus.ppu.registers.h_count = 280;
+        assert!(tick(&mut bus, (0, 276)) > 0);
+        assert_eq!(bus.dma.read_channel_register(NTRLX, 0), 0x7E);
+        // No transfers during V-Blank
+        bus.ppu.registers.v_count = 230;
+        assert_eq!(tick(&mut bus, (230, 276)), 0);
+    }
+}
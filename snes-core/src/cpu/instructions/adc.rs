@@ -1,7 +1,7 @@
 use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::AddressingMode}};
 
 use crate::cpu::cycles;
-use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::{CPUInstruction, SizedInstruction, DecimalInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "ADC";
@@ -11,17 +11,20 @@ pub struct ADC {
 }
 
 impl ADC {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<
+        DecimalInstruction<ADC8BIN, ADC8BCD>,
+        DecimalInstruction<ADC16BIN, ADC16BCD>,
+    > {
         let is_decimal_mode = registers.get_decimal_mode_flag();
         match registers.is_16bit_mode() {
-            true => match is_decimal_mode {
-                true => Box::new(ADC16BCD{addressing_mode: self.addressing_mode}),
-                false => Box::new(ADC16BIN{addressing_mode: self.addressing_mode}),
-            }
-            false => match is_decimal_mode {
-                true => Box::new(ADC8BCD{addressing_mode: self.addressing_mode}),
-                false => Box::new(ADC8BIN{addressing_mode: self.addressing_mode}),
-            }
+            true => SizedInstruction::Bit16(match is_decimal_mode {
+                true => DecimalInstruction::Decimal(ADC16BCD{addressing_mode: self.addressing_mode}),
+                false => DecimalInstruction::Binary(ADC16BIN{addressing_mode: self.addressing_mode}),
+            }),
+            false => SizedInstruction::Bit8(match is_decimal_mode {
+                true => DecimalInstruction::Decimal(ADC8BCD{addressing_mode: self.addressing_mode}),
+                false => DecimalInstruction::Binary(ADC8BIN{addressing_mode: self.addressing_mode}),
+            }),
         }
     }
 }
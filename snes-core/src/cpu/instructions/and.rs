@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::Addr
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "AND";
@@ -11,10 +12,10 @@ pub struct AND {
 }
 
 impl AND {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<AND8, AND16> {
         match registers.is_16bit_mode() {
-            true => Box::new(AND16{addressing_mode: self.addressing_mode}),
-            false => Box::new(AND8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(AND16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(AND8{addressing_mode: self.addressing_mode}),
         }
     }
 }
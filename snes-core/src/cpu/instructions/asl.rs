@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::Addr
 
 use crate::cpu::cycles;
 use super::{read_write_common::{read_16bit_from_address, read_8bit_from_address, write_16bit_to_address, write_8bit_to_address}, CPUInstruction};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "ASL";
@@ -11,10 +12,10 @@ pub struct ASL {
 }
 
 impl ASL {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<ASL8, ASL16> {
         match registers.is_16bit_mode() {
-            true => Box::new(ASL16{addressing_mode: self.addressing_mode}),
-            false => Box::new(ASL8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(ASL16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(ASL8{addressing_mode: self.addressing_mode}),
         }
     }
 }
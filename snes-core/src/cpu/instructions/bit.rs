@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::addressing::Addressing
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, bit_common, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "BIT";
@@ -11,10 +12,10 @@ pub struct BIT {
 }
 
 impl BIT {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<BIT8, BIT16> {
         match registers.is_16bit_mode() {
-            true => Box::new(BIT16{addressing_mode: self.addressing_mode}),
-            false => Box::new(BIT8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(BIT16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(BIT8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::addressing::Addressing
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 use super::comp_common;
 
@@ -12,10 +13,10 @@ pub struct CMP {
 }
 
 impl CMP {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<CMP8, CMP16> {
         match registers.is_16bit_mode() {
-            true => Box::new(CMP16{addressing_mode: self.addressing_mode}),
-            false => Box::new(CMP8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(CMP16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(CMP8{addressing_mode: self.addressing_mode}),
         }
     }
 }
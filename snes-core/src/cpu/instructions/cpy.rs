@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::addressing::Addressing
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 use super::comp_common;
 
@@ -12,10 +13,10 @@ pub struct CPY {
 }
 
 impl CPY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<CPY8, CPY16> {
         match registers.is_16bit_index() {
-            true => Box::new(CPY16{addressing_mode: self.addressing_mode}),
-            false => Box::new(CPY8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(CPY16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(CPY8{addressing_mode: self.addressing_mode}),
         }
     }
 }
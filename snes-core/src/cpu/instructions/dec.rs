@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::addressing::Addressing
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common, read_write_common::{read_8bit_from_address, write_8bit_to_address, read_16bit_from_address, write_16bit_to_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "DEC";
@@ -11,10 +12,10 @@ pub struct DEC {
 }
 
 impl DEC {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<DEC8, DEC16> {
         match registers.is_16bit_mode() {
-            true => Box::new(DEC16{addressing_mode: self.addressing_mode}),
-            false => Box::new(DEC8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(DEC16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(DEC8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -40,7 +41,7 @@ impl CPUInstruction for DEC8 {
         let result = dec_common::do_dec(
             registers,
             read_8bit_from_address(registers, bus, self.addressing_mode),
-        ) as u8;
+        );
         write_8bit_to_address(registers, bus, self.addressing_mode, result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec(registers, self.addressing_mode);
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -60,7 +61,7 @@ impl CPUInstruction for DEC16 {
         let result = dec_common::do_dec(
             registers,
             read_16bit_from_address(registers, bus, self.addressing_mode),
-        ) as u16;
+        );
         write_16bit_to_address(registers, bus, self.addressing_mode, result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec(registers, self.addressing_mode);
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -85,7 +85,7 @@ pub fn mnemonic_direct_page_indirect_long(opcode: u8, instr_name: &str, register
 pub fn mnemonic_absolute_indexed(opcode: u8, instr_name: &str, index: IndexRegister, registers: &Registers, bus: &Bus) -> String {
     let next_byte = bus.read_external(registers.get_pc_address() + 1);
     let next_second_byte = bus.read_external(registers.get_pc_address() + 2);
-    let word = (next_byte as u16) | ((next_byte as u16) << 8);
+    let word = (next_byte as u16) | ((next_second_byte as u16) << 8);
     format!("{:02X} {:02X} {:02X} __ | {} ${:04X}, {}", opcode, next_byte, next_second_byte, instr_name, word, index)
 }
 
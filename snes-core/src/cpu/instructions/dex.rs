@@ -2,6 +2,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "DEX";
@@ -9,10 +10,10 @@ static INSTR_NAME: &str = "DEX";
 pub struct DEX {}
 
 impl DEX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<DEX8, DEX16> {
         match registers.is_16bit_index() {
-            true => Box::new(DEX16{}),
-            false => Box::new(DEX8{}),
+            true => SizedInstruction::Bit16(DEX16{}),
+            false => SizedInstruction::Bit8(DEX8{}),
         }
     }
 }
@@ -36,7 +37,7 @@ impl CPUInstruction for DEX8 {
         let result = dec_common::do_dec(
             registers,
             registers.x as u8,
-        ) as u8;
+        );
         registers.set_low_x(result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -54,7 +55,7 @@ impl CPUInstruction for DEX16 {
         let result = dec_common::do_dec(
             registers,
             registers.x,
-        ) as u16;
+        );
         registers.x = result;
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
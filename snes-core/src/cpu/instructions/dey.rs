@@ -2,6 +2,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "DEY";
@@ -9,10 +10,10 @@ static INSTR_NAME: &str = "DEY";
 pub struct DEY {}
 
 impl DEY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<DEY8, DEY16> {
         match registers.is_16bit_index() {
-            true => Box::new(DEY16{}),
-            false => Box::new(DEY8{}),
+            true => SizedInstruction::Bit16(DEY16{}),
+            false => SizedInstruction::Bit8(DEY8{}),
         }
     }
 }
@@ -36,7 +37,7 @@ impl CPUInstruction for DEY8 {
         let result = dec_common::do_dec(
             registers,
             registers.y as u8,
-        ) as u8;
+        );
         registers.set_low_y(result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -54,7 +55,7 @@ impl CPUInstruction for DEY16 {
         let result = dec_common::do_dec(
             registers,
             registers.y,
-        ) as u16;
+        );
         registers.y = result;
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::addressing::Addressing
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common, read_write_common::{read_8bit_from_address, write_8bit_to_address, read_16bit_from_address, write_16bit_to_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "INC";
@@ -11,10 +12,10 @@ pub struct INC {
 }
 
 impl INC {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<INC8, INC16> {
         match registers.is_16bit_mode() {
-            true => Box::new(INC16{addressing_mode: self.addressing_mode}),
-            false => Box::new(INC8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(INC16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(INC8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -40,7 +41,7 @@ impl CPUInstruction for INC8 {
         let result = dec_common::do_inc(
             registers,
             read_8bit_from_address(registers, bus, self.addressing_mode),
-        ) as u8;
+        );
         write_8bit_to_address(registers, bus, self.addressing_mode, result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec(registers, self.addressing_mode);
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -60,7 +61,7 @@ impl CPUInstruction for INC16 {
         let result = dec_common::do_inc(
             registers,
             read_16bit_from_address(registers, bus, self.addressing_mode),
-        ) as u16;
+        );
         write_16bit_to_address(registers, bus, self.addressing_mode, result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec(registers, self.addressing_mode);
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -2,6 +2,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "INX";
@@ -9,10 +10,10 @@ static INSTR_NAME: &str = "INX";
 pub struct INX {}
 
 impl INX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<INX8, INX16> {
         match registers.is_16bit_index() {
-            true => Box::new(INX16{}),
-            false => Box::new(INX8{}),
+            true => SizedInstruction::Bit16(INX16{}),
+            false => SizedInstruction::Bit8(INX8{}),
         }
     }
 }
@@ -36,7 +37,7 @@ impl CPUInstruction for INX8 {
         let result = dec_common::do_inc(
             registers,
             registers.x as u8,
-        ) as u8;
+        );
         registers.set_low_x(result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -54,7 +55,7 @@ impl CPUInstruction for INX16 {
         let result = dec_common::do_inc(
             registers,
             registers.x,
-        ) as u16;
+        );
         registers.x = result;
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -2,6 +2,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, dec_common};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "INY";
@@ -9,10 +10,10 @@ static INSTR_NAME: &str = "INY";
 pub struct INY {}
 
 impl INY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<INY8, INY16> {
         match registers.is_16bit_index() {
-            true => Box::new(INY16{}),
-            false => Box::new(INY8{}),
+            true => SizedInstruction::Bit16(INY16{}),
+            false => SizedInstruction::Bit8(INY8{}),
         }
     }
 }
@@ -36,7 +37,7 @@ impl CPUInstruction for INY8 {
         let result = dec_common::do_inc(
             registers,
             registers.y as u8,
-        ) as u8;
+        );
         registers.set_low_y(result);
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
@@ -54,7 +55,7 @@ impl CPUInstruction for INY16 {
         let result = dec_common::do_inc(
             registers,
             registers.y,
-        ) as u16;
+        );
         registers.y = result;
         let (bytes, cycles) = cycles::increment_cycles_inc_dec_index();
         registers.increment_pc(bytes); registers.cycles += cycles;
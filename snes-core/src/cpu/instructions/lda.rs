@@ -4,7 +4,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{read_8bit_from_address, read_16bit_from_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "LDA";
@@ -14,10 +14,10 @@ pub struct LDA {
 }
 
 impl LDA {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<LDA8, LDA16> {
         match registers.is_16bit_mode() {
-            true => Box::new(LDA16{addressing_mode: self.addressing_mode}),
-            false => Box::new(LDA8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(LDA16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(LDA8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::Addr
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{write_8bit_to_address, read_8bit_from_address, read_16bit_from_address, write_16bit_to_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "LSR";
@@ -11,10 +12,10 @@ pub struct LSR {
 }
 
 impl LSR {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<LSR8, LSR16> {
         match registers.is_16bit_mode() {
-            true => Box::new(LSR16{addressing_mode: self.addressing_mode}),
-            false => Box::new(LSR8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(LSR16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(LSR8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -2,355 +2,374 @@ use crate::{utils::addressing::{AddressingMode, IndexRegister}, cpu::instruction
 
 use super::CPUInstruction;
 
-pub fn map_opcode_to_instruction(opcode: u8) -> Box<dyn CPUInstruction> {
+type StaticInstruction = &'static (dyn CPUInstruction + Sync);
+
+/// Instructions indexed by opcode, built once at compile time
+static INSTRUCTION_TABLE: [StaticInstruction; 256] = build_instruction_table();
+
+const fn build_instruction_table() -> [StaticInstruction; 256] {
+    let mut table: [StaticInstruction; 256] = [&NOP{}; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        table[opcode] = decode_opcode(opcode as u8);
+        opcode += 1;
+    }
+    table
+}
+
+pub fn map_opcode_to_instruction(opcode: u8) -> &'static dyn CPUInstruction {
+    INSTRUCTION_TABLE[opcode as usize]
+}
+
+const fn decode_opcode(opcode: u8) -> StaticInstruction {
     type A = AddressingMode;
     type I = IndexRegister;
     match opcode {
         // ADC
-        0x69 => Box::new(ADC{addressing_mode: A::Immediate}),
-        0x6D => Box::new(ADC{addressing_mode: A::Absolute}),
-        0x6F => Box::new(ADC{addressing_mode: A::AbsoluteLong}),
-        0x65 => Box::new(ADC{addressing_mode: A::DirectPage}),
-        0x72 => Box::new(ADC{addressing_mode: A::DirectPageIndirect}),
-        0x67 => Box::new(ADC{addressing_mode: A::DirectPageIndirectLong}),
-        0x7D => Box::new(ADC{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x7F => Box::new(ADC{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0x79 => Box::new(ADC{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0x75 => Box::new(ADC{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0x61 => Box::new(ADC{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0x71 => Box::new(ADC{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0x77 => Box::new(ADC{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0x63 => Box::new(ADC{addressing_mode: A::StackRelative}),
-        0x73 => Box::new(ADC{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0x69 => &ADC{addressing_mode: A::Immediate},
+        0x6D => &ADC{addressing_mode: A::Absolute},
+        0x6F => &ADC{addressing_mode: A::AbsoluteLong},
+        0x65 => &ADC{addressing_mode: A::DirectPage},
+        0x72 => &ADC{addressing_mode: A::DirectPageIndirect},
+        0x67 => &ADC{addressing_mode: A::DirectPageIndirectLong},
+        0x7D => &ADC{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x7F => &ADC{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0x79 => &ADC{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0x75 => &ADC{addressing_mode: A::DirectPageIndexed(I::X)},
+        0x61 => &ADC{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0x71 => &ADC{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0x77 => &ADC{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0x63 => &ADC{addressing_mode: A::StackRelative},
+        0x73 => &ADC{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // AND
-        0x29 => Box::new(AND{addressing_mode: A::Immediate}),
-        0x2D => Box::new(AND{addressing_mode: A::Absolute}),
-        0x2F => Box::new(AND{addressing_mode: A::AbsoluteLong}),
-        0x25 => Box::new(AND{addressing_mode: A::DirectPage}),
-        0x32 => Box::new(AND{addressing_mode: A::DirectPageIndirect}),
-        0x27 => Box::new(AND{addressing_mode: A::DirectPageIndirectLong}),
-        0x3D => Box::new(AND{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x3F => Box::new(AND{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0x39 => Box::new(AND{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0x35 => Box::new(AND{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0x21 => Box::new(AND{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0x31 => Box::new(AND{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0x37 => Box::new(AND{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0x23 => Box::new(AND{addressing_mode: A::StackRelative}),
-        0x33 => Box::new(AND{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0x29 => &AND{addressing_mode: A::Immediate},
+        0x2D => &AND{addressing_mode: A::Absolute},
+        0x2F => &AND{addressing_mode: A::AbsoluteLong},
+        0x25 => &AND{addressing_mode: A::DirectPage},
+        0x32 => &AND{addressing_mode: A::DirectPageIndirect},
+        0x27 => &AND{addressing_mode: A::DirectPageIndirectLong},
+        0x3D => &AND{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x3F => &AND{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0x39 => &AND{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0x35 => &AND{addressing_mode: A::DirectPageIndexed(I::X)},
+        0x21 => &AND{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0x31 => &AND{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0x37 => &AND{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0x23 => &AND{addressing_mode: A::StackRelative},
+        0x33 => &AND{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // ASL
-        0x0A => Box::new(ASL{addressing_mode: A::Accumulator}),
-        0x0E => Box::new(ASL{addressing_mode: A::Absolute}),
-        0x06 => Box::new(ASL{addressing_mode: A::DirectPage}),
-        0x1E => Box::new(ASL{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x16 => Box::new(ASL{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x0A => &ASL{addressing_mode: A::Accumulator},
+        0x0E => &ASL{addressing_mode: A::Absolute},
+        0x06 => &ASL{addressing_mode: A::DirectPage},
+        0x1E => &ASL{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x16 => &ASL{addressing_mode: A::DirectPageIndexed(I::X)},
         // BCC
-        0x90 => Box::new(BCC{}),
+        0x90 => &BCC{},
         // BCS
-        0xB0 => Box::new(BCS{}),
+        0xB0 => &BCS{},
         // BEQ
-        0xF0 => Box::new(BEQ{}),
+        0xF0 => &BEQ{},
         // BNE
-        0xD0 => Box::new(BNE{}),
+        0xD0 => &BNE{},
         // BMI
-        0x30 => Box::new(BMI{}),
+        0x30 => &BMI{},
         // BPL
-        0x10 => Box::new(BPL{}),
+        0x10 => &BPL{},
         // BRA
-        0x80 => Box::new(BRA{}),
+        0x80 => &BRA{},
         // BRK
-        0x00 => Box::new(BRK{}),
+        0x00 => &BRK{},
         // BRL
-        0x82 => Box::new(BRL{}),
+        0x82 => &BRL{},
         // BVC
-        0x50 => Box::new(BVC{}),
+        0x50 => &BVC{},
         // BVS
-        0x70 => Box::new(BVS{}),
+        0x70 => &BVS{},
         // BIT
-        0x89 => Box::new(BIT{addressing_mode: A::Immediate}),
-        0x2C => Box::new(BIT{addressing_mode: A::Absolute}),
-        0x24 => Box::new(BIT{addressing_mode: A::DirectPage}),
-        0x3C => Box::new(BIT{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x34 => Box::new(BIT{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x89 => &BIT{addressing_mode: A::Immediate},
+        0x2C => &BIT{addressing_mode: A::Absolute},
+        0x24 => &BIT{addressing_mode: A::DirectPage},
+        0x3C => &BIT{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x34 => &BIT{addressing_mode: A::DirectPageIndexed(I::X)},
         // CLC
-        0x18 => Box::new(CLC{}),
+        0x18 => &CLC{},
         // CLD
-        0xD8 => Box::new(CLD{}),
+        0xD8 => &CLD{},
         // CLI
-        0x58 => Box::new(CLI{}),
+        0x58 => &CLI{},
         // CLV
-        0xB8 => Box::new(CLV{}),
+        0xB8 => &CLV{},
         // CMP
-        0xC9 => Box::new(CMP{addressing_mode: A::Immediate}),
-        0xCD => Box::new(CMP{addressing_mode: A::Absolute}),
-        0xCF => Box::new(CMP{addressing_mode: A::AbsoluteLong}),
-        0xC5 => Box::new(CMP{addressing_mode: A::DirectPage}),
-        0xD2 => Box::new(CMP{addressing_mode: A::DirectPageIndirect}),
-        0xC7 => Box::new(CMP{addressing_mode: A::DirectPageIndirectLong}),
-        0xDD => Box::new(CMP{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xDF => Box::new(CMP{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0xD9 => Box::new(CMP{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0xD5 => Box::new(CMP{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0xC1 => Box::new(CMP{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0xD1 => Box::new(CMP{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0xD7 => Box::new(CMP{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0xC3 => Box::new(CMP{addressing_mode: A::StackRelative}),
-        0xD3 => Box::new(CMP{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0xC9 => &CMP{addressing_mode: A::Immediate},
+        0xCD => &CMP{addressing_mode: A::Absolute},
+        0xCF => &CMP{addressing_mode: A::AbsoluteLong},
+        0xC5 => &CMP{addressing_mode: A::DirectPage},
+        0xD2 => &CMP{addressing_mode: A::DirectPageIndirect},
+        0xC7 => &CMP{addressing_mode: A::DirectPageIndirectLong},
+        0xDD => &CMP{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xDF => &CMP{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0xD9 => &CMP{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0xD5 => &CMP{addressing_mode: A::DirectPageIndexed(I::X)},
+        0xC1 => &CMP{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0xD1 => &CMP{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0xD7 => &CMP{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0xC3 => &CMP{addressing_mode: A::StackRelative},
+        0xD3 => &CMP{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // COP
-        0x02 => Box::new(COP{}),
+        0x02 => &COP{},
         // CPX
-        0xE0 => Box::new(CPX{addressing_mode: A::Immediate}),
-        0xEC => Box::new(CPX{addressing_mode: A::Absolute}),
-        0xE4 => Box::new(CPX{addressing_mode: A::DirectPage}),
+        0xE0 => &CPX{addressing_mode: A::Immediate},
+        0xEC => &CPX{addressing_mode: A::Absolute},
+        0xE4 => &CPX{addressing_mode: A::DirectPage},
         // CPY
-        0xC0 => Box::new(CPY{addressing_mode: A::Immediate}),
-        0xCC => Box::new(CPY{addressing_mode: A::Absolute}),
-        0xC4 => Box::new(CPY{addressing_mode: A::DirectPage}),
+        0xC0 => &CPY{addressing_mode: A::Immediate},
+        0xCC => &CPY{addressing_mode: A::Absolute},
+        0xC4 => &CPY{addressing_mode: A::DirectPage},
         // DEC
-        0x3A => Box::new(DEC{addressing_mode: A::Accumulator}),
-        0xCE => Box::new(DEC{addressing_mode: A::Absolute}),
-        0xC6 => Box::new(DEC{addressing_mode: A::DirectPage}),
-        0xDE => Box::new(DEC{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xD6 => Box::new(DEC{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x3A => &DEC{addressing_mode: A::Accumulator},
+        0xCE => &DEC{addressing_mode: A::Absolute},
+        0xC6 => &DEC{addressing_mode: A::DirectPage},
+        0xDE => &DEC{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xD6 => &DEC{addressing_mode: A::DirectPageIndexed(I::X)},
         // DEX
-        0xCA => Box::new(DEX{}),
+        0xCA => &DEX{},
         // DEY
-        0x88 => Box::new(DEY{}),
+        0x88 => &DEY{},
         // EOR
-        0x49 => Box::new(EOR{addressing_mode: A::Immediate}),
-        0x4D => Box::new(EOR{addressing_mode: A::Absolute}),
-        0x4F => Box::new(EOR{addressing_mode: A::AbsoluteLong}),
-        0x45 => Box::new(EOR{addressing_mode: A::DirectPage}),
-        0x52 => Box::new(EOR{addressing_mode: A::DirectPageIndirect}),
-        0x47 => Box::new(EOR{addressing_mode: A::DirectPageIndirectLong}),
-        0x5D => Box::new(EOR{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x5F => Box::new(EOR{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0x59 => Box::new(EOR{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0x55 => Box::new(EOR{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0x41 => Box::new(EOR{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0x51 => Box::new(EOR{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0x57 => Box::new(EOR{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0x43 => Box::new(EOR{addressing_mode: A::StackRelative}),
-        0x53 => Box::new(EOR{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0x49 => &EOR{addressing_mode: A::Immediate},
+        0x4D => &EOR{addressing_mode: A::Absolute},
+        0x4F => &EOR{addressing_mode: A::AbsoluteLong},
+        0x45 => &EOR{addressing_mode: A::DirectPage},
+        0x52 => &EOR{addressing_mode: A::DirectPageIndirect},
+        0x47 => &EOR{addressing_mode: A::DirectPageIndirectLong},
+        0x5D => &EOR{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x5F => &EOR{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0x59 => &EOR{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0x55 => &EOR{addressing_mode: A::DirectPageIndexed(I::X)},
+        0x41 => &EOR{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0x51 => &EOR{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0x57 => &EOR{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0x43 => &EOR{addressing_mode: A::StackRelative},
+        0x53 => &EOR{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // INC
-        0x1A => Box::new(INC{addressing_mode: A::Accumulator}),
-        0xEE => Box::new(INC{addressing_mode: A::Absolute}),
-        0xE6 => Box::new(INC{addressing_mode: A::DirectPage}),
-        0xFE => Box::new(INC{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xF6 => Box::new(INC{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x1A => &INC{addressing_mode: A::Accumulator},
+        0xEE => &INC{addressing_mode: A::Absolute},
+        0xE6 => &INC{addressing_mode: A::DirectPage},
+        0xFE => &INC{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xF6 => &INC{addressing_mode: A::DirectPageIndexed(I::X)},
         // INX
-        0xE8 => Box::new(INX{}),
+        0xE8 => &INX{},
         // INY
-        0xC8 => Box::new(INY{}),
+        0xC8 => &INY{},
         // JMP
-        0x4C => Box::new(JMP{addressing_mode: A::Absolute}),
-        0x6C => Box::new(JMP{addressing_mode: A::AbsoluteIndirect}),
-        0x7C => Box::new(JMP{addressing_mode: A::AbsoluteIndexedIndirect(I::X)}),
-        0x5C => Box::new(JMP{addressing_mode: A::AbsoluteLong}),
-        0xDC => Box::new(JMP{addressing_mode: A::AbsoluteIndirectLong}),
+        0x4C => &JMP{addressing_mode: A::Absolute},
+        0x6C => &JMP{addressing_mode: A::AbsoluteIndirect},
+        0x7C => &JMP{addressing_mode: A::AbsoluteIndexedIndirect(I::X)},
+        0x5C => &JMP{addressing_mode: A::AbsoluteLong},
+        0xDC => &JMP{addressing_mode: A::AbsoluteIndirectLong},
         // JSR 
-        0x20 => Box::new(JSR{addressing_mode: A::Absolute}),
-        0xFC => Box::new(JSR{addressing_mode: A::AbsoluteIndexedIndirect(I::X)}),
-        0x22 => Box::new(JSR{addressing_mode: A::AbsoluteLong}), // same as JSL
+        0x20 => &JSR{addressing_mode: A::Absolute},
+        0xFC => &JSR{addressing_mode: A::AbsoluteIndexedIndirect(I::X)},
+        0x22 => &JSR{addressing_mode: A::AbsoluteLong}, // same as JSL
         // LDA
-        0xA9 => Box::new(LDA{addressing_mode: A::Immediate}),
-        0xAD => Box::new(LDA{addressing_mode: A::Absolute}),
-        0xAF => Box::new(LDA{addressing_mode: A::AbsoluteLong}),
-        0xA5 => Box::new(LDA{addressing_mode: A::DirectPage}),
-        0xB2 => Box::new(LDA{addressing_mode: A::DirectPageIndirect}),
-        0xA7 => Box::new(LDA{addressing_mode: A::DirectPageIndirectLong}),
-        0xBD => Box::new(LDA{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xBF => Box::new(LDA{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0xB9 => Box::new(LDA{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0xB5 => Box::new(LDA{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0xA1 => Box::new(LDA{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0xB1 => Box::new(LDA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0xB7 => Box::new(LDA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0xA3 => Box::new(LDA{addressing_mode: A::StackRelative}),
-        0xB3 => Box::new(LDA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0xA9 => &LDA{addressing_mode: A::Immediate},
+        0xAD => &LDA{addressing_mode: A::Absolute},
+        0xAF => &LDA{addressing_mode: A::AbsoluteLong},
+        0xA5 => &LDA{addressing_mode: A::DirectPage},
+        0xB2 => &LDA{addressing_mode: A::DirectPageIndirect},
+        0xA7 => &LDA{addressing_mode: A::DirectPageIndirectLong},
+        0xBD => &LDA{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xBF => &LDA{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0xB9 => &LDA{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0xB5 => &LDA{addressing_mode: A::DirectPageIndexed(I::X)},
+        0xA1 => &LDA{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0xB1 => &LDA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0xB7 => &LDA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0xA3 => &LDA{addressing_mode: A::StackRelative},
+        0xB3 => &LDA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // LDX
-        0xA2 => Box::new(LDX{addressing_mode: A::Immediate}),
-        0xAE => Box::new(LDX{addressing_mode: A::Absolute}),
-        0xA6 => Box::new(LDX{addressing_mode: A::DirectPage}),
-        0xBE => Box::new(LDX{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0xB6 => Box::new(LDX{addressing_mode: A::DirectPageIndexed(I::Y)}),
+        0xA2 => &LDX{addressing_mode: A::Immediate},
+        0xAE => &LDX{addressing_mode: A::Absolute},
+        0xA6 => &LDX{addressing_mode: A::DirectPage},
+        0xBE => &LDX{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0xB6 => &LDX{addressing_mode: A::DirectPageIndexed(I::Y)},
         // LDY
-        0xA0 => Box::new(LDY{addressing_mode: A::Immediate}),
-        0xAC => Box::new(LDY{addressing_mode: A::Absolute}),
-        0xA4 => Box::new(LDY{addressing_mode: A::DirectPage}),
-        0xBC => Box::new(LDY{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xB4 => Box::new(LDY{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0xA0 => &LDY{addressing_mode: A::Immediate},
+        0xAC => &LDY{addressing_mode: A::Absolute},
+        0xA4 => &LDY{addressing_mode: A::DirectPage},
+        0xBC => &LDY{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xB4 => &LDY{addressing_mode: A::DirectPageIndexed(I::X)},
         // LSR
-        0x4A => Box::new(LSR{addressing_mode: A::Accumulator}),
-        0x4E => Box::new(LSR{addressing_mode: A::Absolute}),
-        0x46 => Box::new(LSR{addressing_mode: A::DirectPage}),
-        0x5E => Box::new(LSR{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x56 => Box::new(LSR{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x4A => &LSR{addressing_mode: A::Accumulator},
+        0x4E => &LSR{addressing_mode: A::Absolute},
+        0x46 => &LSR{addressing_mode: A::DirectPage},
+        0x5E => &LSR{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x56 => &LSR{addressing_mode: A::DirectPageIndexed(I::X)},
         // MVN
-        0x54 => Box::new(MVN{}),
+        0x54 => &MVN{},
         // MVP
-        0x44 => Box::new(MVP{}),
+        0x44 => &MVP{},
         // NOP
-        0xEA => Box::new(NOP{}),
+        0xEA => &NOP{},
         // ORA
-        0x09 => Box::new(ORA{addressing_mode: A::Immediate}),
-        0x0D => Box::new(ORA{addressing_mode: A::Absolute}),
-        0x0F => Box::new(ORA{addressing_mode: A::AbsoluteLong}),
-        0x05 => Box::new(ORA{addressing_mode: A::DirectPage}),
-        0x12 => Box::new(ORA{addressing_mode: A::DirectPageIndirect}),
-        0x07 => Box::new(ORA{addressing_mode: A::DirectPageIndirectLong}),
-        0x1D => Box::new(ORA{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x1F => Box::new(ORA{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0x19 => Box::new(ORA{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0x15 => Box::new(ORA{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0x01 => Box::new(ORA{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0x11 => Box::new(ORA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0x17 => Box::new(ORA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0x03 => Box::new(ORA{addressing_mode: A::StackRelative}),
-        0x13 => Box::new(ORA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0x09 => &ORA{addressing_mode: A::Immediate},
+        0x0D => &ORA{addressing_mode: A::Absolute},
+        0x0F => &ORA{addressing_mode: A::AbsoluteLong},
+        0x05 => &ORA{addressing_mode: A::DirectPage},
+        0x12 => &ORA{addressing_mode: A::DirectPageIndirect},
+        0x07 => &ORA{addressing_mode: A::DirectPageIndirectLong},
+        0x1D => &ORA{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x1F => &ORA{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0x19 => &ORA{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0x15 => &ORA{addressing_mode: A::DirectPageIndexed(I::X)},
+        0x01 => &ORA{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0x11 => &ORA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0x17 => &ORA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0x03 => &ORA{addressing_mode: A::StackRelative},
+        0x13 => &ORA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // PEA
-        0xF4 => Box::new(PEA{}),
+        0xF4 => &PEA{},
         // PEI
-        0xD4 => Box::new(PEI{}),
+        0xD4 => &PEI{},
         // PER
-        0x62 => Box::new(PER{}),
+        0x62 => &PER{},
         // PHA
-        0x48 => Box::new(PHA{}),
+        0x48 => &PHA{},
         // PHB
-        0x8B => Box::new(PHB{}),
+        0x8B => &PHB{},
         // PHD
-        0x0B => Box::new(PHD{}),
+        0x0B => &PHD{},
         // PHK
-        0x4B => Box::new(PHK{}),
+        0x4B => &PHK{},
         // PHP
-        0x08 => Box::new(PHP{}),
+        0x08 => &PHP{},
         // PHX
-        0xDA => Box::new(PHX{}),
+        0xDA => &PHX{},
         // PHY
-        0x5A => Box::new(PHY{}),
+        0x5A => &PHY{},
         // PLA
-        0x68 => Box::new(PLA{}),
+        0x68 => &PLA{},
         // PLB
-        0xAB => Box::new(PLB{}),
+        0xAB => &PLB{},
         // PLD
-        0x2B => Box::new(PLD{}),
+        0x2B => &PLD{},
         // PLP
-        0x28 => Box::new(PLP{}),
+        0x28 => &PLP{},
         // PLX
-        0xFA => Box::new(PLX{}),
+        0xFA => &PLX{},
         // PLY
-        0x7A => Box::new(PLY{}),
+        0x7A => &PLY{},
         // REP
-        0xC2 => Box::new(REP{}),
+        0xC2 => &REP{},
         // ROL
-        0x2A => Box::new(ROL{addressing_mode: AddressingMode::Accumulator}),
-        0x2E => Box::new(ROL{addressing_mode: AddressingMode::Absolute}),
-        0x26 => Box::new(ROL{addressing_mode: AddressingMode::DirectPage}),
-        0x3E => Box::new(ROL{addressing_mode: AddressingMode::AbsoluteIndexed(I::X)}),
-        0x36 => Box::new(ROL{addressing_mode: AddressingMode::DirectPageIndexed(I::X)}),
+        0x2A => &ROL{addressing_mode: AddressingMode::Accumulator},
+        0x2E => &ROL{addressing_mode: AddressingMode::Absolute},
+        0x26 => &ROL{addressing_mode: AddressingMode::DirectPage},
+        0x3E => &ROL{addressing_mode: AddressingMode::AbsoluteIndexed(I::X)},
+        0x36 => &ROL{addressing_mode: AddressingMode::DirectPageIndexed(I::X)},
         // ROR
-        0x6A => Box::new(ROR{addressing_mode: AddressingMode::Accumulator}),
-        0x6E => Box::new(ROR{addressing_mode: AddressingMode::Absolute}),
-        0x66 => Box::new(ROR{addressing_mode: AddressingMode::DirectPage}),
-        0x7E => Box::new(ROR{addressing_mode: AddressingMode::AbsoluteIndexed(I::X)}),
-        0x76 => Box::new(ROR{addressing_mode: AddressingMode::DirectPageIndexed(I::X)}),
+        0x6A => &ROR{addressing_mode: AddressingMode::Accumulator},
+        0x6E => &ROR{addressing_mode: AddressingMode::Absolute},
+        0x66 => &ROR{addressing_mode: AddressingMode::DirectPage},
+        0x7E => &ROR{addressing_mode: AddressingMode::AbsoluteIndexed(I::X)},
+        0x76 => &ROR{addressing_mode: AddressingMode::DirectPageIndexed(I::X)},
         // RTI
-        0x40 => Box::new(RTI{}),
+        0x40 => &RTI{},
         // RTL
-        0x6B => Box::new(RTL{}),
+        0x6B => &RTL{},
         // RTS
-        0x60 => Box::new(RTS{}),
+        0x60 => &RTS{},
         // SBC
-        0xE9 => Box::new(SBC{addressing_mode: A::Immediate}),
-        0xED => Box::new(SBC{addressing_mode: A::Absolute}),
-        0xEF => Box::new(SBC{addressing_mode: A::AbsoluteLong}),
-        0xE5 => Box::new(SBC{addressing_mode: A::DirectPage}),
-        0xF2 => Box::new(SBC{addressing_mode: A::DirectPageIndirect}),
-        0xE7 => Box::new(SBC{addressing_mode: A::DirectPageIndirectLong}),
-        0xFD => Box::new(SBC{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0xFF => Box::new(SBC{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0xF9 => Box::new(SBC{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0xF5 => Box::new(SBC{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0xE1 => Box::new(SBC{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0xF1 => Box::new(SBC{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0xF7 => Box::new(SBC{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0xE3 => Box::new(SBC{addressing_mode: A::StackRelative}),
-        0xF3 => Box::new(SBC{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0xE9 => &SBC{addressing_mode: A::Immediate},
+        0xED => &SBC{addressing_mode: A::Absolute},
+        0xEF => &SBC{addressing_mode: A::AbsoluteLong},
+        0xE5 => &SBC{addressing_mode: A::DirectPage},
+        0xF2 => &SBC{addressing_mode: A::DirectPageIndirect},
+        0xE7 => &SBC{addressing_mode: A::DirectPageIndirectLong},
+        0xFD => &SBC{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0xFF => &SBC{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0xF9 => &SBC{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0xF5 => &SBC{addressing_mode: A::DirectPageIndexed(I::X)},
+        0xE1 => &SBC{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0xF1 => &SBC{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0xF7 => &SBC{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0xE3 => &SBC{addressing_mode: A::StackRelative},
+        0xF3 => &SBC{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // SEC
-        0x38 => Box::new(SEC{}),
+        0x38 => &SEC{},
         // SED
-        0xF8 => Box::new(SED{}),
+        0xF8 => &SED{},
         // SEI
-        0x78 => Box::new(SEI{}),
+        0x78 => &SEI{},
         // SEP
-        0xE2 => Box::new(SEP{}),
+        0xE2 => &SEP{},
         // STA
-        0x8D => Box::new(STA{addressing_mode: A::Absolute}),
-        0x8F => Box::new(STA{addressing_mode: A::AbsoluteLong}),
-        0x85 => Box::new(STA{addressing_mode: A::DirectPage}),
-        0x92 => Box::new(STA{addressing_mode: A::DirectPageIndirect}),
-        0x87 => Box::new(STA{addressing_mode: A::DirectPageIndirectLong}),
-        0x9D => Box::new(STA{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x9F => Box::new(STA{addressing_mode: A::AbsoluteLongIndexed(I::X)}),
-        0x99 => Box::new(STA{addressing_mode: A::AbsoluteIndexed(I::Y)}),
-        0x95 => Box::new(STA{addressing_mode: A::DirectPageIndexed(I::X)}),
-        0x81 => Box::new(STA{addressing_mode: A::DirectPageIndexedIndirect(I::X)}),
-        0x91 => Box::new(STA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)}),
-        0x97 => Box::new(STA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)}),
-        0x83 => Box::new(STA{addressing_mode: A::StackRelative}),
-        0x93 => Box::new(STA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)}),
+        0x8D => &STA{addressing_mode: A::Absolute},
+        0x8F => &STA{addressing_mode: A::AbsoluteLong},
+        0x85 => &STA{addressing_mode: A::DirectPage},
+        0x92 => &STA{addressing_mode: A::DirectPageIndirect},
+        0x87 => &STA{addressing_mode: A::DirectPageIndirectLong},
+        0x9D => &STA{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x9F => &STA{addressing_mode: A::AbsoluteLongIndexed(I::X)},
+        0x99 => &STA{addressing_mode: A::AbsoluteIndexed(I::Y)},
+        0x95 => &STA{addressing_mode: A::DirectPageIndexed(I::X)},
+        0x81 => &STA{addressing_mode: A::DirectPageIndexedIndirect(I::X)},
+        0x91 => &STA{addressing_mode: A::DirectPageIndirectIndexed(I::Y)},
+        0x97 => &STA{addressing_mode: A::DirectPageIndirectLongIndexed(I::Y)},
+        0x83 => &STA{addressing_mode: A::StackRelative},
+        0x93 => &STA{addressing_mode: A::StackRelativeIndirectIndexed(I::Y)},
         // STP
-        0xDB => Box::new(STP{}),
+        0xDB => &STP{},
         // STX
-        0x8E => Box::new(STX{addressing_mode: A::Absolute}),
-        0x86 => Box::new(STX{addressing_mode: A::DirectPage}),
-        0x96 => Box::new(STX{addressing_mode: A::DirectPageIndexed(I::Y)}),
+        0x8E => &STX{addressing_mode: A::Absolute},
+        0x86 => &STX{addressing_mode: A::DirectPage},
+        0x96 => &STX{addressing_mode: A::DirectPageIndexed(I::Y)},
         // STY
-        0x8C => Box::new(STY{addressing_mode: A::Absolute}),
-        0x84 => Box::new(STY{addressing_mode: A::DirectPage}),
-        0x94 => Box::new(STY{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x8C => &STY{addressing_mode: A::Absolute},
+        0x84 => &STY{addressing_mode: A::DirectPage},
+        0x94 => &STY{addressing_mode: A::DirectPageIndexed(I::X)},
         // STZ
-        0x9C => Box::new(STZ{addressing_mode: A::Absolute}),
-        0x64 => Box::new(STZ{addressing_mode: A::DirectPage}),
-        0x9E => Box::new(STZ{addressing_mode: A::AbsoluteIndexed(I::X)}),
-        0x74 => Box::new(STZ{addressing_mode: A::DirectPageIndexed(I::X)}),
+        0x9C => &STZ{addressing_mode: A::Absolute},
+        0x64 => &STZ{addressing_mode: A::DirectPage},
+        0x9E => &STZ{addressing_mode: A::AbsoluteIndexed(I::X)},
+        0x74 => &STZ{addressing_mode: A::DirectPageIndexed(I::X)},
         // TAX
-        0xAA => Box::new(TAX{}),
+        0xAA => &TAX{},
         // TAY
-        0xA8 => Box::new(TAY{}),
+        0xA8 => &TAY{},
         // TCD
-        0x5B => Box::new(TCD{}),
+        0x5B => &TCD{},
         // TCS
-        0x1B => Box::new(TCS{}),
+        0x1B => &TCS{},
         // TDC
-        0x7B => Box::new(TDC{}),
+        0x7B => &TDC{},
         // TRB
-        0x1C => Box::new(TRB{addressing_mode: A::Absolute}),
-        0x14 => Box::new(TRB{addressing_mode: A::DirectPage}),
+        0x1C => &TRB{addressing_mode: A::Absolute},
+        0x14 => &TRB{addressing_mode: A::DirectPage},
         // TSB
-        0x0C => Box::new(TSB{addressing_mode: A::Absolute}),
-        0x04 => Box::new(TSB{addressing_mode: A::DirectPage}),
+        0x0C => &TSB{addressing_mode: A::Absolute},
+        0x04 => &TSB{addressing_mode: A::DirectPage},
         // TSC
-        0x3B => Box::new(TSC{}),
+        0x3B => &TSC{},
         // TSX
-        0xBA => Box::new(TSX{}),
+        0xBA => &TSX{},
         // TXA
-        0x8A => Box::new(TXA{}),
+        0x8A => &TXA{},
         // TXS
-        0x9A => Box::new(TXS{}),
+        0x9A => &TXS{},
         // TXY
-        0x9B => Box::new(TXY{}),
+        0x9B => &TXY{},
         // TYA
-        0x98 => Box::new(TYA{}),
+        0x98 => &TYA{},
         // TYX
-        0xBB => Box::new(TYX{}),
+        0xBB => &TYX{},
         // WAI
-        0xCB => Box::new(WAI{}),
+        0xCB => &WAI{},
         // WDM
-        0x42 => Box::new(WDM{}),
+        0x42 => &WDM{},
         // XBA
-        0xEB => Box::new(XBA{}),
+        0xEB => &XBA{},
         // XCE
-        0xFB => Box::new(XCE{}),
+        0xFB => &XCE{},
     }
 }
\ No newline at end of file
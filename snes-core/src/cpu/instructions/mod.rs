@@ -106,3 +106,48 @@ pub trait CPUInstruction {
     fn execute(&self, registers: &mut Registers, bus: &mut Bus);
     fn mnemonic(&self, registers: &Registers, bus: &Bus, opcode: u8) -> String;
 }
+
+/// Register width dependent variant of an instruction, resolved at runtime
+/// without having to allocate a new instruction
+pub enum SizedInstruction<T8, T16> {
+    Bit8(T8),
+    Bit16(T16),
+}
+
+impl<T8: CPUInstruction, T16: CPUInstruction> CPUInstruction for SizedInstruction<T8, T16> {
+    fn execute(&self, registers: &mut Registers, bus: &mut Bus) {
+        match self {
+            Self::Bit8(instruction) => instruction.execute(registers, bus),
+            Self::Bit16(instruction) => instruction.execute(registers, bus),
+        }
+    }
+
+    fn mnemonic(&self, registers: &Registers, bus: &Bus, opcode: u8) -> String {
+        match self {
+            Self::Bit8(instruction) => instruction.mnemonic(registers, bus, opcode),
+            Self::Bit16(instruction) => instruction.mnemonic(registers, bus, opcode),
+        }
+    }
+}
+
+/// Binary or BCD variant of an arithmetic instruction, depending on the decimal flag
+pub enum DecimalInstruction<TBin, TBcd> {
+    Binary(TBin),
+    Decimal(TBcd),
+}
+
+impl<TBin: CPUInstruction, TBcd: CPUInstruction> CPUInstruction for DecimalInstruction<TBin, TBcd> {
+    fn execute(&self, registers: &mut Registers, bus: &mut Bus) {
+        match self {
+            Self::Binary(instruction) => instruction.execute(registers, bus),
+            Self::Decimal(instruction) => instruction.execute(registers, bus),
+        }
+    }
+
+    fn mnemonic(&self, registers: &Registers, bus: &Bus, opcode: u8) -> String {
+        match self {
+            Self::Binary(instruction) => instruction.mnemonic(registers, bus, opcode),
+            Self::Decimal(instruction) => instruction.mnemonic(registers, bus, opcode),
+        }
+    }
+}
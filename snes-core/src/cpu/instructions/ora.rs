@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::Addr
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "ORA";
@@ -11,10 +12,10 @@ pub struct ORA {
 }
 
 impl ORA {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<ORA8, ORA16> {
         match registers.is_16bit_mode() {
-            true => Box::new(ORA16{addressing_mode: self.addressing_mode}),
-            false => Box::new(ORA8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(ORA16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(ORA8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -2,6 +2,7 @@ use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::Addr
 
 use crate::cpu::cycles;
 use super::{CPUInstruction, read_write_common::{read_8bit_from_address, write_8bit_to_address, read_16bit_from_address, write_16bit_to_address}};
+use super::SizedInstruction;
 use super::decoder_common;
 
 static INSTR_NAME: &str = "ROR";
@@ -11,10 +12,10 @@ pub struct ROR {
 }
 
 impl ROR {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<ROR8, ROR16> {
         match registers.is_16bit_mode() {
-            true => Box::new(ROR16{addressing_mode: self.addressing_mode}),
-            false => Box::new(ROR8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(ROR16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(ROR8{addressing_mode: self.addressing_mode}),
         }
     }
 }
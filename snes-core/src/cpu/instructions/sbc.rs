@@ -1,7 +1,7 @@
 use crate::{cpu::{bus::Bus, registers::Registers}, utils::{alu, addressing::AddressingMode}};
 
 use crate::cpu::cycles;
-use super::{CPUInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
+use super::{CPUInstruction, SizedInstruction, DecimalInstruction, read_write_common::{read_8bit_from_address, read_16bit_from_address}};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "SBC";
@@ -11,17 +11,20 @@ pub struct SBC {
 }
 
 impl SBC {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<
+        DecimalInstruction<SBC8BIN, SBC8BCD>,
+        DecimalInstruction<SBC16BIN, SBC16BCD>,
+    > {
         let is_decimal_mode = registers.get_decimal_mode_flag();
         match registers.is_16bit_mode() {
-            true => match is_decimal_mode {
-                true => Box::new(SBC16BCD{addressing_mode: self.addressing_mode}),
-                false => Box::new(SBC16BIN{addressing_mode: self.addressing_mode}),
-            }
-            false => match is_decimal_mode {
-                true => Box::new(SBC8BCD{addressing_mode: self.addressing_mode}),
-                false => Box::new(SBC8BIN{addressing_mode: self.addressing_mode}),
-            }
+            true => SizedInstruction::Bit16(match is_decimal_mode {
+                true => DecimalInstruction::Decimal(SBC16BCD{addressing_mode: self.addressing_mode}),
+                false => DecimalInstruction::Binary(SBC16BIN{addressing_mode: self.addressing_mode}),
+            }),
+            false => SizedInstruction::Bit8(match is_decimal_mode {
+                true => DecimalInstruction::Decimal(SBC8BCD{addressing_mode: self.addressing_mode}),
+                false => DecimalInstruction::Binary(SBC8BIN{addressing_mode: self.addressing_mode}),
+            }),
         }
     }
 }
@@ -3,7 +3,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{write_8bit_to_address, write_16bit_to_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "STX";
@@ -13,10 +13,10 @@ pub struct STX {
 }
 
 impl STX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<STX8, STX16> {
         match registers.is_16bit_index() {
-            true => Box::new(STX16{addressing_mode: self.addressing_mode}),
-            false => Box::new(STX8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(STX16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(STX8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -3,7 +3,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{write_8bit_to_address, write_16bit_to_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "STY";
@@ -13,10 +13,10 @@ pub struct STY {
 }
 
 impl STY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<STY8, STY16> {
         match registers.is_16bit_index() {
-            true => Box::new(STY16{addressing_mode: self.addressing_mode}),
-            false => Box::new(STY8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(STY16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(STY8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -3,7 +3,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{write_8bit_to_address, write_16bit_to_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "STZ";
@@ -13,10 +13,10 @@ pub struct STZ {
 }
 
 impl STZ {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<STZ8, STZ16> {
         match registers.is_16bit_mode() {
-            true => Box::new(STZ16{addressing_mode: self.addressing_mode}),
-            false => Box::new(STZ8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(STZ16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(STZ8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TAX";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TAX";
 pub struct TAX {}
 
 impl TAX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TAX8, TAX16> {
         match registers.is_16bit_index() {
-            true => Box::new(TAX16{}),
-            false => Box::new(TAX8{}),
+            true => SizedInstruction::Bit16(TAX16{}),
+            false => SizedInstruction::Bit8(TAX8{}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TAY";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TAY";
 pub struct TAY {}
 
 impl TAY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TAY8, TAY16> {
         match registers.is_16bit_index() {
-            true => Box::new(TAY16{}),
-            false => Box::new(TAY8{}),
+            true => SizedInstruction::Bit16(TAY16{}),
+            false => SizedInstruction::Bit8(TAY8{}),
         }
     }
 }
@@ -3,7 +3,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{read_8bit_from_address, write_8bit_to_address, read_16bit_from_address, write_16bit_to_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TRB";
@@ -13,10 +13,10 @@ pub struct TRB {
 }
 
 impl TRB {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TRB8, TRB16> {
         match registers.is_16bit_mode() {
-            true => Box::new(TRB16{addressing_mode: self.addressing_mode}),
-            false => Box::new(TRB8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(TRB16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(TRB8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -3,7 +3,7 @@ use crate::cpu::{bus::Bus, registers::Registers};
 use crate::utils::addressing::AddressingMode;
 
 use super::read_write_common::{read_8bit_from_address, write_8bit_to_address, read_16bit_from_address, write_16bit_to_address};
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TSB";
@@ -13,10 +13,10 @@ pub struct TSB {
 }
 
 impl TSB {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TSB8, TSB16> {
         match registers.is_16bit_mode() {
-            true => Box::new(TSB16{addressing_mode: self.addressing_mode}),
-            false => Box::new(TSB8{addressing_mode: self.addressing_mode}),
+            true => SizedInstruction::Bit16(TSB16{addressing_mode: self.addressing_mode}),
+            false => SizedInstruction::Bit8(TSB8{addressing_mode: self.addressing_mode}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TSX";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TSX";
 pub struct TSX {}
 
 impl TSX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TSX8, TSX16> {
         match registers.is_16bit_index() {
-            true => Box::new(TSX16{}),
-            false => Box::new(TSX8{}),
+            true => SizedInstruction::Bit16(TSX16{}),
+            false => SizedInstruction::Bit8(TSX8{}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TXA";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TXA";
 pub struct TXA {}
 
 impl TXA {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TXA8, TXA16> {
         match registers.is_16bit_mode() {
-            true => Box::new(TXA16{}),
-            false => Box::new(TXA8{}),
+            true => SizedInstruction::Bit16(TXA16{}),
+            false => SizedInstruction::Bit8(TXA8{}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TXS";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TXS";
 pub struct TXS {}
 
 impl TXS {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TXS8, TXS16> {
         match registers.is_16bit_index() {
-            true => Box::new(TXS16{}),
-            false => Box::new(TXS8{}),
+            true => SizedInstruction::Bit16(TXS16{}),
+            false => SizedInstruction::Bit8(TXS8{}),
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TXY";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TXY";
 pub struct TXY {}
 
 impl TXY {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TXY8, TXY16> {
         match registers.is_16bit_index() {
-            true => Box::new(TXY16{}),
-            false => Box::new(TXY8{}),
+            true => SizedInstruction::Bit16(TXY16{}),
+            false => SizedInstruction::Bit8(TXY8{}),
         }
     }
 }
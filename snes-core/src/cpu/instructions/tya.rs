@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TYA";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TYA";
 pub struct TYA {}
 
 impl TYA {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TYA8, TYA16> {
         match registers.is_16bit_mode() {
-            true => Box::new(TYA16{}),
-            false => Box::new(TYA8{}),
+            true => SizedInstruction::Bit16(TYA16{}),
+            false => SizedInstruction::Bit8(TYA8{}),
         }
     }
 }
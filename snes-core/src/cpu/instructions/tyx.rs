@@ -1,7 +1,7 @@
 use crate::cpu::cycles;
 use crate::cpu::{bus::Bus, registers::Registers};
 
-use super::CPUInstruction;
+use super::{CPUInstruction, SizedInstruction};
 use super::decoder_common;
 
 static INSTR_NAME: &str = "TYX";
@@ -9,10 +9,10 @@ static INSTR_NAME: &str = "TYX";
 pub struct TYX {}
 
 impl TYX {
-    fn determine_instruction(&self, registers: &Registers) -> Box<dyn CPUInstruction> {
+    fn determine_instruction(&self, registers: &Registers) -> SizedInstruction<TYX8, TYX16> {
         match registers.is_16bit_index() {
-            true => Box::new(TYX16{}),
-            false => Box::new(TYX8{}),
+            true => SizedInstruction::Bit16(TYX16{}),
+            false => SizedInstruction::Bit8(TYX8{}),
         }
     }
 }
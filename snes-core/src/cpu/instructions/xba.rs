@@ -10,7 +10,7 @@ pub struct XBA {}
 
 impl CPUInstruction for XBA {
     fn execute(&self, registers: &mut Registers, _bus: &mut Bus) {
-        registers.a = (registers.a << 8) | (registers.a >> 8);
+        registers.a = registers.a.swap_bytes();
         registers.set_negative_flag(((registers.a as u8) >> 7) == 1);
         registers.set_zero_flag((registers.a as u8) == 0);
         let (bytes, cycles) = cycles::increment_cycles_xba();
@@ -1,31 +1,28 @@
-use super::{bus::Bus, cycles, dma, instructions::{mapper::map_opcode_to_instruction, move_common}, registers::Registers};
+use super::{bus::Bus, call_stack::CallStack, cycles, instructions::{mapper::map_opcode_to_instruction, move_common}, registers::Registers, tracer::Tracer};
 
 pub struct CPU {
     pub registers: Registers,
+    /// CPU cycles elapsed since power on
+    pub cycle_count: u64,
+    pub tracer: Option<Tracer>,
+    pub call_stack: CallStack,
 }
 
 impl CPU {
     pub fn new() -> Self {
         Self {
             registers: Registers::new(),
+            cycle_count: 0,
+            tracer: None,
+            call_stack: CallStack::new(),
         }
     }
 
     fn check_running_state(&mut self, bus: &mut Bus) -> bool {
-        // Each byte in a DMA transfer takes 8 master cycles.
-        // And each CPU can take either 6, 8 or 12 master cycles depending
-        // on what's being read from memory. So this won't be accurate.
-        if bus.dma.is_active() {
-            let pending_bus_writes = bus.dma.tick();
-            for (src, dst) in pending_bus_writes {
-                let byte = bus.read(src);
-                bus.write(dst, byte);
-                let (bytes, cycles) = cycles::increment_cycles_while_stopped();
-                self.registers.increment_pc(bytes); self.registers.cycles += cycles;
-            }
-            if !bus.dma.is_active() {
-                bus.write(dma::MDMAEN as u32, 0x00)
-            }
+        // DMA halts the CPU, one byte per tick
+        if let Some(transfer) = bus.dma.tick(bus.ppu.frame_count, bus.ppu.registers.v_count) {
+            bus.transfer_dma_byte(transfer.direction, transfer.a_bus_address, transfer.b_bus_address);
+            self.registers.cycles += bus.dma.charge_master_cycles(transfer.master_cycles);
             return false;
         }
         if self.registers.is_cpu_stopped {
@@ -47,13 +44,36 @@ impl CPU {
         true
     }
 
+    /// Whether the next tick will fetch and execute an instruction
+    pub fn is_ready_to_execute(&self, bus: &Bus) -> bool {
+        !bus.dma.is_active() &&
+        !self.registers.is_cpu_stopped &&
+        !self.registers.is_cpu_waiting_interrupt &&
+        !self.registers.is_moving
+    }
+
     pub fn tick(&mut self, bus: &mut Bus) {
+        let cycles_before = self.registers.cycles;
+        self.execute_next(bus);
+        self.cycle_count += self.registers.cycles.saturating_sub(cycles_before) as u64;
+    }
+
+    fn execute_next(&mut self, bus: &mut Bus) {
         if !self.check_running_state(bus) {
             return;
         }
-        let opcode = bus.read(self.registers.get_pc_address());
+        if let Some(tracer) = &mut self.tracer {
+            tracer.log(&self.registers, bus, self.cycle_count);
+        }
+        let pc_address = self.registers.get_pc_address();
+        let stack_pointer = self.registers.sp;
+        bus.begin_cdl_instruction(&self.registers);
+        bus.begin_cycle_log_instruction(&self.registers);
+        let opcode = bus.read(pc_address);
         let instruction = map_opcode_to_instruction(opcode);
         instruction.execute(&mut self.registers, bus);
+        bus.end_cdl_instruction(opcode, self.registers.get_pc_address());
+        self.call_stack.track_instruction(opcode, pc_address, stack_pointer, &self.registers);
     }
 }
 
@@ -62,3 +82,205 @@ impl Default for CPU {
         Self::new()
     }
 }
+
+
+#[cfg(test)]
+mod cpu_interface_tests {
+    use super::*;
+    use crate::cpu::bus::{WMADDL, WMADDM, WMADDH};
+    use crate::cpu::dma;
+    use crate::rom::test_memory::TestMemory;
+
+    fn setup_wram_port_dma(bus: &mut Bus, source: u32) {
+        bus.write(WMADDL as u32, 0x00);
+        bus.write(WMADDM as u32, 0x10);
+        bus.write(WMADDH as u32, 0x01);
+        // Channel 0: A to B, one register, write once, to $2180
+        bus.write(0x4300, 0x00);
+        bus.write(0x4301, 0x80);
+        bus.write(0x4302, source as u8);
+        bus.write(0x4303, (source >> 8) as u8);
+        bus.write(0x4304, (source >> 16) as u8);
+        bus.write(0x4305, 0x02);
+        bus.write(0x4306, 0x00);
+    }
+
+    #[test]
+    fn test_dma_to_wram_port() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        bus.rom = Box::new(TestMemory::new());
+        bus.write(0x01_8000, 0xAB);
+        bus.write(0x01_8001, 0xCD);
+        setup_wram_port_dma(&mut bus, 0x01_8000);
+        bus.write(dma::MDMAEN as u32, 0x01);
+        cpu.registers.pc = 0x1234;
+        while bus.dma.is_active() {
+            cpu.tick(&mut bus);
+        }
+        assert_eq!(bus.read(0x7F_1000), 0xAB);
+        assert_eq!(bus.read(0x7F_1001), 0xCD);
+        // The CPU is only halted: setup (18) + channel (8) + 2 bytes (8 each) master cycles
+        assert_eq!(cpu.registers.pc, 0x1234);
+        assert_eq!(cpu.cycle_count, 42 / 8);
+        // The 2 cycles left over go to the next transfer
+        setup_wram_port_dma(&mut bus, 0x01_8000);
+        bus.write(dma::MDMAEN as u32, 0x01);
+        while bus.dma.is_active() {
+            cpu.tick(&mut bus);
+        }
+        assert_eq!(cpu.cycle_count, 84 / 8);
+        assert_eq!(bus.dma.read_channel_word(dma::A1TXL, dma::A1TXH, 0), 0x8002);
+        assert_eq!(bus.dma.read_channel_register(dma::A1BX, 0), 0x01);
+        assert_eq!(bus.dma.read_channel_word(dma::DASXL, dma::DASXH, 0), 0x0000);
+    }
+
+    #[test]
+    fn test_dma_transfer_log() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        bus.rom = Box::new(TestMemory::new());
+        bus.dma.transfer_log = Some(dma::DMATransferLog::new(1));
+        bus.ppu.registers.v_count = 100;
+        setup_wram_port_dma(&mut bus, 0x01_8000);
+        bus.write(0x4315, 0x00);
+        bus.write(0x4316, 0x00);
+        bus.write(dma::MDMAEN as u32, 0x03);
+        cpu.tick(&mut bus);
+        cpu.tick(&mut bus);
+        cpu.tick(&mut bus);
+        // Channel 1 has a count of 0, so 64KB, and pushes channel 0 out of the log
+        let entries: Vec<_> = bus.dma.transfer_log.as_ref().unwrap().entries().cloned().collect();
+        assert_eq!(entries, vec![dma::DMALogEntry {
+            frame: 0,
+            scanline: 100,
+            channel_number: 1,
+            is_hdma: false,
+            source: 0x00_0000,
+            destination: 0x00_2100,
+            length: 0x10000,
+        }]);
+    }
+
+    #[test]
+    fn test_dma_channel_priority_and_format() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        bus.rom = Box::new(TestMemory::new());
+        for (i, byte) in [0x11, 0x22, 0x33, 0x44].iter().enumerate() {
+            bus.write(0x01_8000 + i as u32, *byte);
+        }
+        setup_wram_port_dma(&mut bus, 0x01_8000);
+        // Channel 1 runs after channel 0, format 1 alternates $2180 and $2181 (WMADDL)
+        bus.write(0x4310, 0x01);
+        bus.write(0x4311, 0x80);
+        bus.write(0x4312, 0x02);
+        bus.write(0x4313, 0x80);
+        bus.write(0x4314, 0x01);
+        bus.write(0x4315, 0x02);
+        bus.write(dma::MDMAEN as u32, 0x03);
+        while bus.dma.is_active() {
+            cpu.tick(&mut bus);
+        }
+        assert_eq!(bus.read(0x7F_1000), 0x11);
+        assert_eq!(bus.read(0x7F_1001), 0x22);
+        assert_eq!(bus.read(0x7F_1002), 0x33);
+        // The second byte moved the WRAM port address
+        assert_eq!(bus.read(0x7F_1003), 0x00);
+        bus.write(0x2180, 0xEE);
+        assert_eq!(bus.read(0x7F_1044), 0xEE);
+    }
+
+    #[test]
+    fn test_dma_b_bus_to_a_bus_restrictions() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        bus.write(WMADDL as u32, 0x00);
+        bus.write(WMADDM as u32, 0x00);
+        bus.write(WMADDH as u32, 0x00);
+        bus.write(0x7E_0000, 0xAB);
+        // Channel 0: $2180 to $0000-$0001 is WRAM to WRAM, channel 1: $2180 to the DMA registers
+        bus.write(0x4300, 0x80);
+        bus.write(0x4301, 0x80);
+        bus.write(0x4302, 0x10);
+        bus.write(0x4305, 0x01);
+        bus.write(0x4310, 0x80);
+        bus.write(0x4311, 0x80);
+        bus.write(0x4312, 0x7F);
+        bus.write(0x4313, 0x43);
+        bus.write(0x4315, 0x01);
+        bus.write(dma::MDMAEN as u32, 0x03);
+        while bus.dma.is_active() {
+            cpu.tick(&mut bus);
+        }
+        assert_eq!(bus.read(0x00_0010), 0x00);
+        assert_eq!(bus.read(0x00_437F), 0x00);
+        // The A-bus address still moves on
+        assert_eq!(bus.dma.read_channel_word(dma::A1TXL, dma::A1TXH, 1), 0x4380);
+    }
+
+    #[test]
+    fn test_dma_wram_to_wram_is_ignored() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        bus.write(0x7E_0100, 0xAB);
+        bus.write(0x7E_0101, 0xCD);
+        setup_wram_port_dma(&mut bus, 0x7E0100);
+        bus.write(dma::MDMAEN as u32, 0x01);
+        while bus.dma.is_active() {
+            cpu.tick(&mut bus);
+        }
+        assert_eq!(bus.read(0x7F_1000), 0x00);
+        assert_eq!(bus.read(0x7F_1001), 0x00);
+    }
+
+    #[test]
+    fn test_tracer() {
+        use crate::cpu::tracer::{Tracer, TraceFormat};
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        cpu.tracer = Some(Tracer::to_ring_buffer(10, TraceFormat::Mesen));
+        cpu.registers.pbr = 0x7E;
+        cpu.registers.pc = 0x0000;
+        bus.write(0x7E_0000, 0xEA); // NOP
+        bus.write(0x7E_0001, 0x1A); // INC A
+        cpu.tick(&mut bus);
+        cpu.tick(&mut bus);
+        let lines = cpu.tracer.as_ref().unwrap().lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("7E0000  EA"));
+        assert!(lines[0].ends_with("Cyc:0"));
+        assert!(lines[1].starts_with("7E0001  1A"));
+        assert!(lines[1].ends_with("Cyc:2"));
+        assert_eq!(cpu.cycle_count, 4);
+    }
+
+    #[test]
+    fn test_call_stack() {
+        use crate::cpu::call_stack::CallKind;
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        cpu.registers.pbr = 0x7E;
+        cpu.registers.pc = 0x0000;
+        cpu.registers.sp = 0x01FF;
+        let program: &[(u32, &[u8])] = &[
+            (0x7E0000, &[0x20, 0x00, 0x01]),        // JSR $0100
+            (0x7E0100, &[0x22, 0x00, 0x02, 0x7E]),  // JSL $7E0200
+            (0x7E0200, &[0x6B]),                    // RTL
+        ];
+        for (address, bytes) in program {
+            for (i, byte) in bytes.iter().enumerate() {
+                bus.write(address + i as u32, *byte);
+            }
+        }
+        cpu.tick(&mut bus);
+        cpu.tick(&mut bus);
+        let frames = &cpu.call_stack.frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].kind, frames[0].caller, frames[0].target, frames[0].stack_pointer), (CallKind::JSR, 0x7E0000, 0x7E0100, 0x01FF));
+        assert_eq!((frames[1].kind, frames[1].caller, frames[1].target, frames[1].stack_pointer), (CallKind::JSL, 0x7E0100, 0x7E0200, 0x01FD));
+        cpu.tick(&mut bus);
+        assert_eq!(cpu.call_stack.frames.len(), 1);
+        assert!(cpu.call_stack.unbalanced_returns.is_empty());
+    }
+}
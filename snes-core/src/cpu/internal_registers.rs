@@ -1,19 +1,41 @@
 use crate::ppu::registers::PPURegisters;
 use crate::cpu::dma;
+use crate::joypad::Joypad;
 
 pub const INTERNAL_REGISTERS_ADDRESS: u16 = 0x4200;
 
+pub const NMITIMEN: u16     = 0x4200;  // Interrupt Enable and Joypad Request (W)
+pub const WRIO: u16         = 0x4201;  // Programmable I/O port (out-port) (W)
+pub const RDIO: u16         = 0x4213;  // Programmable I/O port (in-port) (R)
+
 // PPU Interrupts
 pub const RDNMI: u16        = 0x4210;  // V-Blank NMI Flag
+pub const TIMEUP: u16       = 0x4211;  // H/V-Timer IRQ Flag (R)
+pub const HVBJOY: u16       = 0x4212;  // H/V-Blank flag and Joypad Busy flag (R)
+
+// Joypad auto-read results
+pub const JOY1L: u16        = 0x4218;  // Joypad 1 (gameport 1, pin 4) (lower 8bit)
+pub const JOY1H: u16        = 0x4219;  // Joypad 1 (gameport 1, pin 4) (upper 8bit)
+pub const JOY2L: u16        = 0x421A;  // Joypad 2 (gameport 2, pin 4) (lower 8bit)
+pub const JOY2H: u16        = 0x421B;  // Joypad 2 (gameport 2, pin 4) (upper 8bit)
+pub const JOY3L: u16        = 0x421C;  // Joypad 3 (gameport 1, pin 5) (lower 8bit)
+pub const JOY3H: u16        = 0x421D;  // Joypad 3 (gameport 1, pin 5) (upper 8bit)
+pub const JOY4L: u16        = 0x421E;  // Joypad 4 (gameport 2, pin 5) (lower 8bit)
+pub const JOY4H: u16        = 0x421F;  // Joypad 4 (gameport 2, pin 5) (upper 8bit)
 
 pub struct InternalRegisters {
     registers: [u8; 256],
+    is_auto_joypad_read_busy: bool,
 }
 
 impl InternalRegisters {
     pub fn new() -> Self {
+        let mut registers = [0; 256];
+        // All I/O pins are high after reset
+        registers[(WRIO - INTERNAL_REGISTERS_ADDRESS) as usize] = 0xFF;
         Self {
-            registers: [0; 256],
+            registers,
+            is_auto_joypad_read_busy: false,
         }
     }
 
@@ -25,17 +47,24 @@ impl InternalRegisters {
         self.registers[(address - 0x4200) as usize] = value
     }
 
-    pub fn read_external(&self, address: u16, ppu_registers: &PPURegisters) -> u8 {
+    pub fn read_external(&self, address: u16, ppu_registers: &PPURegisters, open_bus: u8) -> u8 {
         match address {
-            RDNMI => self.read_vblank_nmi(ppu_registers),
-            _ => self._read(address),
+            RDNMI => self.read_vblank_nmi(ppu_registers, open_bus),
+            // Bits 0-6 are open bus
+            TIMEUP => (self._read(address) & 0x80) | (open_bus & 0x7F),
+            HVBJOY => self.read_hvbjoy(ppu_registers, open_bus),
+            // Nothing else drives the I/O pins, so they read back what was written to WRIO
+            RDIO => self._read(WRIO),
+            0x4214..=0x421F => self._read(address),
+            // Everything else is either write-only or unused
+            _ => open_bus,
         }
     }
 
-    pub fn read(&self, address: u16, ppu_registers: &mut PPURegisters) -> u8 {
+    pub fn read(&self, address: u16, ppu_registers: &mut PPURegisters, open_bus: u8) -> u8 {
         match address {
-            RDNMI => self.read_vblank_nmi_mut(ppu_registers),
-            _ => self._read(address),
+            RDNMI => self.read_vblank_nmi_mut(ppu_registers, open_bus),
+            _ => self.read_external(address, ppu_registers, open_bus),
         }
     }
 
@@ -43,21 +72,73 @@ impl InternalRegisters {
         self._read(address)
     }
 
-    pub fn write(&mut self, address: u16, value: u8, dma: &mut dma::DMA) {
+    pub fn write(&mut self, address: u16, value: u8, dma: &mut dma::DMA, ppu_registers: &mut PPURegisters) {
         self._write(address, value);
-        #[allow(clippy::single_match)] match address {
+        match address {
             dma::MDMAEN => dma.prepare_dma_transfer(value),
+            dma::HDMAEN => dma.hdma_enabled = value,
+            WRIO => ppu_registers.set_external_latch_pin(value & 0x80 != 0),
             _ => {},
         }
     }
 
-    fn read_vblank_nmi(&self, ppu_registers: &PPURegisters) -> u8 {
+    fn read_hvbjoy(&self, ppu_registers: &PPURegisters, open_bus: u8) -> u8 {
+        // Bits 1-5 are open bus
+        ((ppu_registers.is_vblanking() as u8) << 7) |
+        ((ppu_registers.is_hblanking() as u8) << 6) |
+        (open_bus & 0x3E) |
+        (self.is_auto_joypad_read_busy as u8)
+    }
+
+    pub fn is_auto_joypad_read_enabled(&self) -> bool {
+        self._read(NMITIMEN) & 1 == 1
+    }
+
+    pub fn is_auto_joypad_read_busy(&self) -> bool {
+        self.is_auto_joypad_read_busy
+    }
+
+    /// Auto-read starts at the beginning of V-Blank and keeps the busy flag set for about 3 scanlines.
+    /// The whole read is done at once, games are expected to wait for the busy flag anyway.
+    pub fn tick_auto_joypad_read(&mut self, ppu_registers: &PPURegisters, joypad: &mut Joypad) {
+        let is_auto_read_period = (225..=227).contains(&ppu_registers.v_count);
+        if !is_auto_read_period || !self.is_auto_joypad_read_enabled() {
+            self.is_auto_joypad_read_busy = false;
+            return;
+        }
+        if !self.is_auto_joypad_read_busy {
+            self.is_auto_joypad_read_busy = true;
+            self.auto_joypad_read(joypad);
+        }
+    }
+
+    fn auto_joypad_read(&mut self, joypad: &mut Joypad) {
+        joypad.set_latch(true);
+        joypad.set_latch(false);
+        let mut results = [0u16; 4];
+        for _ in 0..16 {
+            let port1 = joypad.read_port(0) as u16;
+            let port2 = joypad.read_port(1) as u16;
+            results[0] = (results[0] << 1) | (port1 & 1);
+            results[1] = (results[1] << 1) | (port2 & 1);
+            results[2] = (results[2] << 1) | (port1 >> 1);
+            results[3] = (results[3] << 1) | (port2 >> 1);
+        }
+        let registers = [(JOY1L, JOY1H), (JOY2L, JOY2H), (JOY3L, JOY3H), (JOY4L, JOY4H)];
+        for ((low, high), result) in registers.iter().zip(results) {
+            self._write(*low, result as u8);
+            self._write(*high, (result >> 8) as u8);
+        }
+    }
+
+    fn read_vblank_nmi(&self, ppu_registers: &PPURegisters, open_bus: u8) -> u8 {
         let byte = self._read(RDNMI);
-        (byte & 0x7F) | ((ppu_registers.vblank_nmi as u8) << 7)
+        // Bits 4-6 are open bus
+        (byte & 0x0F) | (open_bus & 0x70) | ((ppu_registers.vblank_nmi as u8) << 7)
     }
 
-    fn read_vblank_nmi_mut(&self, ppu_registers: &mut PPURegisters) -> u8 {
-        let result = self.read_vblank_nmi(ppu_registers);
+    fn read_vblank_nmi_mut(&self, ppu_registers: &mut PPURegisters, open_bus: u8) -> u8 {
+        let result = self.read_vblank_nmi(ppu_registers, open_bus);
         // When register is read, bit 7 is cleared
         ppu_registers.vblank_nmi = false;
         result
@@ -82,13 +163,95 @@ mod ppu_general_test {
         let mut ppu = PPU::new();
         ppu.registers.h_count = 20;
         ppu.dot_cycle();
-        assert_eq!(registers.read_vblank_nmi(&ppu.registers), 0x00);
+        assert_eq!(registers.read_vblank_nmi(&ppu.registers, 0x00), 0x00);
         ppu.registers.h_count = 339;
         ppu.registers.v_count = 224;
         ppu.dot_cycle();
-        assert_eq!(registers.read_vblank_nmi_mut(&mut ppu.registers), 0x80);
+        assert_eq!(registers.read_vblank_nmi_mut(&mut ppu.registers, 0x00), 0x80);
         // vblank bit is reset after read
         ppu.dot_cycle();
-        assert_eq!(registers.read_vblank_nmi(&ppu.registers), 0x00);
+        assert_eq!(registers.read_vblank_nmi(&ppu.registers, 0x00), 0x00);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_read_open_bus() {
+        let registers = InternalRegisters::new();
+        let mut ppu_registers = PPURegisters::new();
+        ppu_registers.v_count = 100;
+        ppu_registers.h_count = 100;
+        assert_eq!(registers.read(RDNMI, &mut ppu_registers, 0xFF), 0x70);
+        assert_eq!(registers.read(TIMEUP, &mut ppu_registers, 0xFF), 0x7F);
+        assert_eq!(registers.read(HVBJOY, &mut ppu_registers, 0xFF), 0x3E);
+        // Write-only registers
+        assert_eq!(registers.read(0x4200, &mut ppu_registers, 0xAB), 0xAB);
+        assert_eq!(registers.read(dma::MDMAEN, &mut ppu_registers, 0xAB), 0xAB);
+    }
+
+    #[test]
+    fn test_read_dma_open_bus() {
+        let mut dma = dma::DMA::new();
+        dma.write(0x4312, 0x34);
+        dma.write(0x431B, 0x56);
+        dma.write(0x431C, 0x78);
+        assert_eq!(dma.read(0x4312, 0xAB), 0x34);
+        assert_eq!(dma.read(0x431B, 0xAB), 0x56);
+        // Unused channel bytes and the space past the channels
+        assert_eq!(dma.read(0x431C, 0xAB), 0xAB);
+        assert_eq!(dma.read(0x437E, 0xAB), 0xAB);
+        assert_eq!(dma.read(0x4380, 0xAB), 0xAB);
+        assert_eq!(dma.read(0x5FFF, 0xAB), 0xAB);
+    }
+
+    #[test]
+    fn test_auto_joypad_read() {
+        use crate::joypad::controller::{BUTTON_B, BUTTON_R, BUTTON_START};
+        let mut registers = InternalRegisters::new();
+        let mut ppu_registers = PPURegisters::new();
+        let mut joypad = Joypad::new();
+        joypad.ports[0].set_buttons(BUTTON_B | BUTTON_R);
+        joypad.ports[1].set_buttons(BUTTON_START);
+
+        // Nothing happens while auto-read is disabled
+        ppu_registers.v_count = 225;
+        registers.tick_auto_joypad_read(&ppu_registers, &mut joypad);
+        assert!(!registers.is_auto_joypad_read_busy());
+        assert_eq!(registers._read(JOY1H), 0x00);
+
+        registers._write(NMITIMEN, 0x01);
+        ppu_registers.v_count = 224;
+        registers.tick_auto_joypad_read(&ppu_registers, &mut joypad);
+        assert!(!registers.is_auto_joypad_read_busy());
+        ppu_registers.v_count = 225;
+        registers.tick_auto_joypad_read(&ppu_registers, &mut joypad);
+        assert!(registers.is_auto_joypad_read_busy());
+        assert_eq!(registers.read_hvbjoy(&ppu_registers, 0x00) & 0x01, 0x01);
+        assert_eq!(registers.read(JOY1L, &mut ppu_registers, 0x00), 0x10);
+        assert_eq!(registers.read(JOY1H, &mut ppu_registers, 0x00), 0x80);
+        assert_eq!(registers.read(JOY2L, &mut ppu_registers, 0x00), 0x00);
+        assert_eq!(registers.read(JOY2H, &mut ppu_registers, 0x00), 0x10);
+        // Standard controllers don't drive the second data line
+        assert_eq!(registers.read(JOY3H, &mut ppu_registers, 0x00), 0x00);
+        assert_eq!(registers.read(JOY4H, &mut ppu_registers, 0x00), 0x00);
+
+        ppu_registers.v_count = 228;
+        registers.tick_auto_joypad_read(&ppu_registers, &mut joypad);
+        assert!(!registers.is_auto_joypad_read_busy());
+        assert_eq!(registers.read_hvbjoy(&ppu_registers, 0x00) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn test_wrio_rdio() {
+        let mut registers = InternalRegisters::new();
+        let mut ppu_registers = PPURegisters::new();
+        let mut dma = dma::DMA::new();
+        assert_eq!(registers.read(RDIO, &mut ppu_registers, 0x00), 0xFF);
+        ppu_registers.h_count = 50;
+        ppu_registers.v_count = 60;
+        registers.write(WRIO, 0x7F, &mut dma, &mut ppu_registers);
+        assert_eq!(registers.read(RDIO, &mut ppu_registers, 0x00), 0x7F);
+        // 1 to 0 transition of bit 7 latches the counters
+        assert!(ppu_registers.is_counter_latched);
+        assert_eq!(ppu_registers.h_count_latch, 50);
+        assert_eq!(ppu_registers.v_count_latch, 60);
+    }
+}
@@ -3,7 +3,14 @@ pub use interface::CPU;
 pub mod bus;
 pub mod registers;
 pub mod dma;
+pub mod hdma;
 pub mod instructions;
 pub mod vectors;
 pub mod cycles;
 pub mod internal_registers;
+pub mod disasm;
+pub mod tracer;
+pub mod call_stack;
+
+/// The PPU runs two dots per CPU cycle, four master cycles each
+pub const MASTER_CYCLES_PER_CPU_CYCLE: usize = 8;
@@ -0,0 +1,170 @@
+//! Opt-in execution trace logger, one line per executed instruction.
+//!
+//! Lines follow the layout of Mesen's and bsnes's trace loggers so they can be
+//! diffed against them. H is the PPU dot counter and the cycle count is in CPU cycles.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::bus::Bus;
+use super::disasm::{self, WidthState};
+use super::registers::Registers;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TraceFormat {
+    /// 808000  A9 12     LDA #$12    A:0000 X:0000 Y:0000 S:01FF D:0000 DB:00 P:nvMXdIzc E V:0   H:0   Cyc:0
+    Mesen,
+    /// 808000 lda #$12    A:0000 X:0000 Y:0000 S:01ff D:0000 B:00 nvMXdIzc E V:  0 H:   0 C:0
+    /// (bsnes doesn't log the instruction bytes)
+    Bsnes,
+}
+
+pub enum TraceOutput {
+    File(BufWriter<File>),
+    RingBuffer(VecDeque<String>, usize),
+}
+
+pub struct Tracer {
+    pub format: TraceFormat,
+    output: TraceOutput,
+}
+
+impl Tracer {
+    pub fn to_file(filename: &str, format: TraceFormat) -> std::io::Result<Self> {
+        let file = File::create(filename)?;
+        Ok(Self {
+            format,
+            output: TraceOutput::File(BufWriter::new(file)),
+        })
+    }
+
+    /// Keeps only the last `capacity` lines in memory
+    pub fn to_ring_buffer(capacity: usize, format: TraceFormat) -> Self {
+        Self {
+            format,
+            output: TraceOutput::RingBuffer(VecDeque::with_capacity(capacity), capacity),
+        }
+    }
+
+    /// Returns the lines kept in the ring buffer, oldest first
+    pub fn lines(&self) -> Vec<&str> {
+        match &self.output {
+            TraceOutput::RingBuffer(lines, _) => lines.iter().map(|l| l.as_str()).collect(),
+            TraceOutput::File(_) => vec![],
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.output {
+            TraceOutput::File(writer) => writer.flush(),
+            TraceOutput::RingBuffer(_, _) => Ok(()),
+        }
+    }
+
+    /// Logs the instruction about to be executed at PC
+    pub fn log(&mut self, registers: &Registers, bus: &Bus, cycle_count: u64) {
+        let line = Self::format_line(self.format, registers, bus, cycle_count);
+        match &mut self.output {
+            TraceOutput::File(writer) => {
+                // Tracing must never interrupt emulation, a failing disk only truncates the log
+                let _ = writeln!(writer, "{}", line);
+            },
+            TraceOutput::RingBuffer(lines, capacity) => {
+                if *capacity == 0 {
+                    return;
+                }
+                if lines.len() >= *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            },
+        }
+    }
+
+    fn format_flags(registers: &Registers) -> String {
+        "NVMXDIZC".chars().enumerate()
+            .map(|(i, flag)| match (registers.p >> (7 - i)) & 1 {
+                1 => flag,
+                _ => flag.to_ascii_lowercase(),
+            })
+            .collect()
+    }
+
+    pub fn format_line(format: TraceFormat, registers: &Registers, bus: &Bus, cycle_count: u64) -> String {
+        let mut state = WidthState::from_registers(registers);
+        let instruction = disasm::decode(|address| bus.read_external(address), registers.get_pc_address(), &mut state);
+        let flags = Self::format_flags(registers);
+        let emulation_flag = if registers.emulation_mode { "E" } else { "e" };
+        let h = bus.ppu.registers.h_count;
+        let v = bus.ppu.registers.v_count;
+        match format {
+            TraceFormat::Mesen => {
+                let bytes: Vec<String> = instruction.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+                format!(
+                    "{:06X}  {:<12}{:<24}A:{:04X} X:{:04X} Y:{:04X} S:{:04X} D:{:04X} DB:{:02X} P:{} {} V:{:<3} H:{:<3} Cyc:{}",
+                    instruction.address, bytes.join(" "), instruction.to_string(),
+                    registers.a, registers.x, registers.y, registers.sp, registers.d, registers.dbr,
+                    flags, emulation_flag, v, h, cycle_count,
+                )
+            },
+            TraceFormat::Bsnes => format!(
+                "{:06x} {:<24}A:{:04x} X:{:04x} Y:{:04x} S:{:04x} D:{:04x} B:{:02x} {} {} V:{:>3} H:{:>4} C:{}",
+                instruction.address, instruction.to_string().to_lowercase(),
+                registers.a, registers.x, registers.y, registers.sp, registers.d, registers.dbr,
+                flags, emulation_flag, v, h, cycle_count,
+            ),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod cpu_tracer_tests {
+    use super::*;
+
+    fn setup() -> (Registers, Bus) {
+        let mut registers = Registers::new();
+        let mut bus = Bus::new();
+        registers.pbr = 0x7E;
+        registers.pc = 0x1000;
+        registers.a = 0x1234;
+        registers.p = 0b0011_0101;
+        bus.write(0x7E1000, 0xA9);
+        bus.write(0x7E1001, 0x12);
+        bus.ppu.registers.h_count = 100;
+        bus.ppu.registers.v_count = 20;
+        (registers, bus)
+    }
+
+    #[test]
+    fn test_mesen_format() {
+        let (registers, bus) = setup();
+        assert_eq!(
+            Tracer::format_line(TraceFormat::Mesen, &registers, &bus, 42),
+            "7E1000  A9 12       LDA #$12                A:1234 X:0000 Y:0000 S:01FF D:0000 DB:00 P:nvMXdIzC E V:20  H:100 Cyc:42",
+        );
+    }
+
+    #[test]
+    fn test_bsnes_format() {
+        let (registers, bus) = setup();
+        assert_eq!(
+            Tracer::format_line(TraceFormat::Bsnes, &registers, &bus, 42),
+            "7e1000 lda #$12                A:1234 X:0000 Y:0000 S:01ff D:0000 B:00 nvMXdIzC E V: 20 H: 100 C:42",
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let (mut registers, bus) = setup();
+        let mut tracer = Tracer::to_ring_buffer(2, TraceFormat::Mesen);
+        for cycles in 0..3 {
+            registers.x = cycles as u16;
+            tracer.log(&registers, &bus, cycles);
+        }
+        let lines = tracer.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("X:0001"));
+        assert!(lines[1].contains("X:0002"));
+    }
+}
@@ -1,8 +1,10 @@
-use super::{instructions::push_common, interface::CPU, internal_registers::RDNMI};
+use super::{call_stack::{CallFrame, CallKind}, instructions::push_common, interface::CPU, internal_registers::RDNMI, registers::Registers};
 use crate::cpu::bus::Bus;
 
 
-#[allow(clippy::upper_case_acronyms, dead_code)] enum Vector {
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Vector {
     Reset,
     COP,
     Break,
@@ -14,50 +16,85 @@ use crate::cpu::bus::Bus;
 }
 
 impl Vector {
-    pub fn get_base_address(&self) -> u32 {
-        match self {
-            Self::COP                   => 0x00FFE4,
-            Self::Break                 => 0x00FFE6,
-            Self::Abort                 => 0x00FFE8,
-            Self::NMI | Self::VBlank    => 0x00FFEA,
-            Self::Reset                 => 0x00FFFC,
-            Self::IRQ | Self::HVTimer   => 0x00FFEE,
+    pub fn get_base_address(&self, emulation_mode: bool) -> u32 {
+        match emulation_mode {
+            false => match self {
+                Self::COP                   => 0x00FFE4,
+                Self::Break                 => 0x00FFE6,
+                Self::Abort                 => 0x00FFE8,
+                Self::NMI | Self::VBlank    => 0x00FFEA,
+                // There is no native mode reset vector, the CPU always resets in emulation mode
+                Self::Reset                 => 0x00FFFC,
+                Self::IRQ | Self::HVTimer   => 0x00FFEE,
+            },
+            true => match self {
+                Self::COP                   => 0x00FFF4,
+                Self::Abort                 => 0x00FFF8,
+                Self::NMI | Self::VBlank    => 0x00FFFA,
+                Self::Reset                 => 0x00FFFC,
+                // BRK shares the IRQ vector, the B flag on the stack tells them apart
+                Self::Break | Self::IRQ | Self::HVTimer => 0x00FFFE,
+            },
         }
     }
+
+    pub fn is_software_interrupt(&self) -> bool {
+        matches!(self, Self::COP | Self::Break)
+    }
 }
 
-impl CPU {
-    fn get_vector(base_address: u32, bus: &mut Bus) -> u16 {
-        (bus.read(base_address) as u16) | ((bus.read(base_address + 1) as u16) << 8)
+fn get_vector(base_address: u32, bus: &mut Bus) -> u16 {
+    (bus.read_vector(base_address) as u16) | ((bus.read_vector(base_address + 1) as u16) << 8)
+}
+
+/// Pushes the return state of an interrupt.
+/// Native mode: PBR, PCH, PCL, P
+/// Emulation mode: PCH, PCL, P (with the B flag set only for BRK and COP)
+pub fn push_interrupt(registers: &mut Registers, bus: &mut Bus, vector: Vector) {
+    if !registers.emulation_mode {
+        push_common::do_push(registers, bus, &[registers.pbr]);
     }
+    push_common::do_push(registers, bus, &[
+        (registers.pc >> 8) as u8,
+        registers.pc as u8,
+    ]);
+    let p = match (registers.emulation_mode, vector.is_software_interrupt()) {
+        (true, true) => registers.p | 0b0001_0000,
+        (true, false) => registers.p & 0b1110_1111,
+        (false, _) => registers.p,
+    };
+    push_common::do_push(registers, bus, &[p]);
+}
 
+/// Pushes the current state and jumps to the interrupt handler.
+/// The PC is expected to point to the instruction to return to.
+pub fn enter_interrupt(registers: &mut Registers, bus: &mut Bus, vector: Vector) {
+    push_interrupt(registers, bus, vector);
+    registers.set_irq_disable_flag(true);
+    registers.set_decimal_mode_flag(false);
+    let base_address = vector.get_base_address(registers.emulation_mode);
+    registers.pc = get_vector(base_address, bus);
+    registers.pbr = 0x00;
+}
+
+impl CPU {
     pub fn reset_vector(&mut self, bus: &mut Bus) {
-        let base_address = Vector::Reset.get_base_address();
-        let reset_vector = CPU::get_vector(base_address, bus);
+        let base_address = Vector::Reset.get_base_address(self.registers.emulation_mode);
+        let reset_vector = get_vector(base_address, bus);
         self.registers.pc = reset_vector;
         self.registers.is_cpu_stopped = false;
     }
 
-    fn push_interrupt(&mut self, bus: &mut Bus) {
-        let pbr = self.registers.pbr;
-        if !self.registers.emulation_mode {
-            push_common::do_push(&mut self.registers, bus, &[pbr]);
-        }
-        let values = [
-            (self.registers.pc >> 8) as u8,
-            self.registers.pc as u8,
-        ];
-        push_common::do_push(&mut self.registers, bus, &values);
-        let p = self.registers.p;
-        push_common::do_push(&mut self.registers, bus, &[p]);
-    }
-
     fn handle_interrupt(&mut self, bus: &mut Bus, vector: Vector) {
-        self.push_interrupt(bus);
-        let base_address = vector.get_base_address();
-        let effective_vector = CPU::get_vector(base_address, bus);
-        self.registers.pc = effective_vector;
-        self.registers.pbr = 0x00;
+        let caller = self.registers.get_pc_address();
+        let stack_pointer = self.registers.sp;
+        enter_interrupt(&mut self.registers, bus, vector);
+        self.call_stack.push(CallFrame {
+            kind: CallKind::Interrupt(vector),
+            caller,
+            target: self.registers.get_pc_address(),
+            stack_pointer,
+        });
     }
 
     pub fn check_interrupts(&mut self, bus: &mut Bus) {
@@ -88,4 +125,59 @@ mod cpu_vectors_tests {
         cpu.reset_vector(&mut bus);
         assert!(!cpu.registers.is_cpu_stopped);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_vector_base_addresses() {
+        assert_eq!(Vector::COP.get_base_address(false), 0x00FFE4);
+        assert_eq!(Vector::Break.get_base_address(false), 0x00FFE6);
+        assert_eq!(Vector::NMI.get_base_address(false), 0x00FFEA);
+        assert_eq!(Vector::IRQ.get_base_address(false), 0x00FFEE);
+        assert_eq!(Vector::COP.get_base_address(true), 0x00FFF4);
+        assert_eq!(Vector::NMI.get_base_address(true), 0x00FFFA);
+        assert_eq!(Vector::Reset.get_base_address(true), 0x00FFFC);
+        assert_eq!(Vector::Break.get_base_address(true), 0x00FFFE);
+        assert_eq!(Vector::IRQ.get_base_address(true), 0x00FFFE);
+    }
+
+    #[test]
+    fn test_enter_interrupt_native_mode() {
+        let mut registers = Registers::new();
+        let mut bus = Bus::new();
+        registers.emulation_mode = false;
+        registers.sp = 0x1FF;
+        registers.pbr = 0x12;
+        registers.pc = 0x3456;
+        registers.p = 0b0000_1000;
+        enter_interrupt(&mut registers, &mut bus, Vector::NMI);
+        assert_eq!(bus.read(0x1FF), 0x12);
+        assert_eq!(bus.read(0x1FE), 0x34);
+        assert_eq!(bus.read(0x1FD), 0x56);
+        assert_eq!(bus.read(0x1FC), 0b0000_1000);
+        assert_eq!(registers.sp, 0x1FB);
+        assert_eq!(registers.pbr, 0x00);
+        assert!(registers.get_irq_disable_flag());
+        assert!(!registers.get_decimal_mode_flag());
+    }
+
+    #[test]
+    fn test_enter_interrupt_emulation_mode() {
+        let mut registers = Registers::new();
+        let mut bus = Bus::new();
+        registers.sp = 0x1FF;
+        registers.pc = 0x3456;
+        registers.p = 0b0011_0000;
+        enter_interrupt(&mut registers, &mut bus, Vector::NMI);
+        assert_eq!(bus.read(0x1FF), 0x34);
+        assert_eq!(bus.read(0x1FE), 0x56);
+        // B flag is clear for hardware interrupts
+        assert_eq!(bus.read(0x1FD), 0b0010_0000);
+        assert_eq!(registers.sp, 0x1FC);
+
+        registers.sp = 0x1FF;
+        registers.pc = 0x3456;
+        registers.p = 0b0010_0000;
+        enter_interrupt(&mut registers, &mut bus, Vector::Break);
+        // B flag is set for BRK and COP
+        assert_eq!(bus.read(0x1FD), 0b0011_0000);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::common::memory_access::{AccessKind, MemoryAccess, MemorySpace};
+
+use super::expression::{EvalContext, Expression};
+
+/// Stops execution before the instruction at any address in `start..=end` runs
+pub struct Breakpoint {
+    pub id: usize,
+    pub start: u32,
+    pub end: u32,
+    pub condition: Option<Expression>,
+    pub is_enabled: bool,
+}
+
+impl Breakpoint {
+    pub fn matches(&self, pc_address: u32, context: &EvalContext) -> bool {
+        self.is_enabled &&
+        (self.start..=self.end).contains(&pc_address) &&
+        self.condition.as_ref().is_none_or(|c| c.is_true(context))
+    }
+}
+
+/// Stops execution after an instruction accesses any address in `start..=end`.
+/// Execute watchpoints stop before the instruction runs, like breakpoints.
+pub struct Watchpoint {
+    pub id: usize,
+    pub space: MemorySpace,
+    pub start: u32,
+    pub end: u32,
+    pub on_read: bool,
+    pub on_write: bool,
+    pub on_execute: bool,
+    pub condition: Option<Expression>,
+    pub is_enabled: bool,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: &MemoryAccess, context: &EvalContext) -> bool {
+        let is_watched_kind = match access.kind {
+            AccessKind::Read => self.on_read,
+            AccessKind::Write => self.on_write,
+            AccessKind::Execute => self.on_execute,
+        };
+        self.is_enabled &&
+        is_watched_kind &&
+        self.space == access.space &&
+        (self.start..=self.end).contains(&access.address) &&
+        self.condition.as_ref().is_none_or(|c| c.is_true(context))
+    }
+}
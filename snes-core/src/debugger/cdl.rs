@@ -0,0 +1,278 @@
+//! Code/Data Logger: marks each ROM byte with how the game used it.
+//!
+//! Files follow Mesen's SNES layout: `CDLv2`, the ROM CRC32 (little-endian) and
+//! one byte of flags per ROM byte. Mesen reads exactly that many bytes, so the
+//! flags it has no room for are appended after them as an `XCDL` block.
+use std::path::{Path, PathBuf};
+
+use crate::cpu::disasm::{self, OperandMode};
+use crate::cpu::registers::Registers;
+
+// Mesen compatible flags
+pub const CODE: u8              = 0x01;
+pub const DATA: u8              = 0x02;
+pub const JUMP_TARGET: u8       = 0x04;
+pub const SUB_ENTRY_POINT: u8   = 0x08;
+pub const INDEX_MODE_8: u8      = 0x10;
+pub const MEMORY_MODE_8: u8     = 0x20;
+
+// Extra flags
+pub const OPCODE: u8            = 0x01;
+pub const OPERAND: u8           = 0x02;
+pub const DMA_SOURCE: u8        = 0x04;
+/// Read through a pointer, e.g. LDA ($10),Y
+pub const INDIRECT_TARGET: u8   = 0x08;
+
+const HEADER: &[u8; 5] = b"CDLv2";
+const EXTRA_HEADER: &[u8; 4] = b"XCDL";
+
+/// Instruction currently executing, its own bytes are code rather than data
+#[derive(Copy, Clone)]
+struct Instruction {
+    address: u32,
+    length: u8,
+    is_indirect: bool,
+}
+
+impl Instruction {
+    fn contains(&self, address: u32) -> bool {
+        let offset = (address as u16).wrapping_sub(self.address as u16);
+        (address & 0xFF0000) == (self.address & 0xFF0000) && offset < self.length as u16
+    }
+}
+
+pub struct CodeDataLogger {
+    flags: Vec<u8>,
+    extra_flags: Vec<u8>,
+    current_instruction: Option<Instruction>,
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// `game.sfc` logs to `game.cdl`
+pub fn cdl_path(rom_path: &str) -> PathBuf {
+    Path::new(rom_path).with_extension("cdl")
+}
+
+impl CodeDataLogger {
+    pub fn new(rom_size: usize) -> Self {
+        Self {
+            flags: vec![0; rom_size],
+            extra_flags: vec![0; rom_size],
+            current_instruction: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.flags.fill(0);
+        self.extra_flags.fill(0);
+    }
+
+    /// Mesen compatible flags, one byte per ROM byte
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    pub fn extra_flags(&self) -> &[u8] {
+        &self.extra_flags
+    }
+
+    pub fn mark(&mut self, offset: usize, flags: u8, extra_flags: u8) {
+        if offset < self.flags.len() {
+            self.flags[offset] |= flags;
+            self.extra_flags[offset] |= extra_flags;
+        }
+    }
+
+    /// Marks the bytes of the instruction about to run as code.
+    /// `rom_offset` maps CPU addresses to ROM offsets, `None` for anything outside ROM.
+    pub fn begin_instruction(&mut self, registers: &Registers, opcode: u8, rom_offset: impl Fn(u32) -> Option<usize>) {
+        let (_, mode) = disasm::opcode_info(opcode);
+        let state = disasm::WidthState::from_registers(registers);
+        let instruction = Instruction {
+            address: registers.get_pc_address(),
+            length: 1 + mode.operand_length(&state),
+            is_indirect: matches!(
+                mode,
+                OperandMode::DirectPageIndirect | OperandMode::DirectPageIndirectLong |
+                OperandMode::DirectPageIndexedIndirect | OperandMode::DirectPageIndirectIndexed |
+                OperandMode::DirectPageIndirectLongIndexed | OperandMode::StackRelativeIndirectIndexed
+            ),
+        };
+        let mut mode_flags = 0;
+        if !registers.is_16bit_mode() {
+            mode_flags |= MEMORY_MODE_8;
+        }
+        if !registers.is_16bit_index() {
+            mode_flags |= INDEX_MODE_8;
+        }
+        for i in 0..instruction.length {
+            let address = (instruction.address & 0xFF0000) | (instruction.address as u16).wrapping_add(i as u16) as u32;
+            if let Some(offset) = rom_offset(address) {
+                self.mark(offset, CODE | mode_flags, if i == 0 { OPCODE } else { OPERAND });
+            }
+        }
+        self.current_instruction = Some(instruction);
+    }
+
+    /// Marks where the instruction that just ran jumped to, if anywhere
+    pub fn end_instruction(&mut self, opcode: u8, pc_address: u32, rom_offset: impl Fn(u32) -> Option<usize>) {
+        let Some(instruction) = self.current_instruction.take() else {
+            return
+        };
+        let next_address = (instruction.address & 0xFF0000) |
+            (instruction.address as u16).wrapping_add(instruction.length as u16) as u32;
+        if pc_address == next_address {
+            return;
+        }
+        let flag = match opcode {
+            // JSR, JSL, JSR (addr,X)
+            0x20 | 0x22 | 0xFC => SUB_ENTRY_POINT,
+            // Branches, BRA, BRL, JMP, JML
+            0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xB0 | 0xD0 | 0xF0 |
+            0x80 | 0x82 | 0x4C | 0x5C | 0x6C | 0x7C | 0xDC => JUMP_TARGET,
+            _ => return,
+        };
+        if let Some(offset) = rom_offset(pc_address) {
+            self.mark(offset, flag, 0);
+        }
+    }
+
+    /// Marks a ROM read by the CPU, reads of the executing instruction's own bytes are skipped
+    pub fn log_read(&mut self, address: u32, offset: usize) {
+        match self.current_instruction {
+            Some(instruction) if instruction.contains(address) => {},
+            Some(instruction) if instruction.is_indirect => self.mark(offset, DATA, INDIRECT_TARGET),
+            _ => self.mark(offset, DATA, 0),
+        }
+    }
+
+    pub fn log_dma_read(&mut self, offset: usize) {
+        self.mark(offset, DATA, DMA_SOURCE);
+    }
+
+    pub fn to_bytes(&self, rom: &[u8]) -> Vec<u8> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend(crc32(rom).to_le_bytes());
+        bytes.extend(&self.flags);
+        bytes.extend(EXTRA_HEADER);
+        bytes.extend(&self.extra_flags);
+        bytes
+    }
+
+    /// Reads a CDL file for `rom`. Files logged for a different ROM, or too short, start empty.
+    /// Mesen's older files without a header are accepted too.
+    pub fn from_bytes(bytes: &[u8], rom: &[u8]) -> Self {
+        let mut logger = Self::new(rom.len());
+        let size = rom.len();
+        let data = match bytes.strip_prefix(HEADER) {
+            Some(rest) if rest.len() >= 4 + size && rest[..4] == crc32(rom).to_le_bytes() => &rest[4..],
+            Some(_) => return logger,
+            None if bytes.len() >= size => bytes,
+            None => return logger,
+        };
+        logger.flags.copy_from_slice(&data[..size]);
+        if let Some(extra) = data[size..].strip_prefix(EXTRA_HEADER) {
+            if extra.len() >= size {
+                logger.extra_flags.copy_from_slice(&extra[..size]);
+            }
+        }
+        logger
+    }
+
+    pub fn save(&self, filename: &Path, rom: &[u8]) -> std::io::Result<()> {
+        std::fs::write(filename, self.to_bytes(rom))
+    }
+
+    pub fn load(filename: &Path, rom: &[u8]) -> std::io::Result<Self> {
+        Ok(Self::from_bytes(&std::fs::read(filename)?, rom))
+    }
+}
+
+
+#[cfg(test)]
+mod debugger_cdl_tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let rom = vec![0xEA; 0x100];
+        let mut logger = CodeDataLogger::new(rom.len());
+        logger.mark(0x10, CODE | MEMORY_MODE_8, OPCODE);
+        logger.mark(0x20, DATA, DMA_SOURCE);
+        let bytes = logger.to_bytes(&rom);
+        assert_eq!(&bytes[..5], b"CDLv2");
+        assert_eq!(bytes[5 + 4 + 0x10], CODE | MEMORY_MODE_8);
+
+        let loaded = CodeDataLogger::from_bytes(&bytes, &rom);
+        assert_eq!(loaded.flags(), logger.flags());
+        assert_eq!(loaded.extra_flags(), logger.extra_flags());
+
+        // Mesen files have no extra block
+        let mesen_file = &bytes[..5 + 4 + rom.len()];
+        let loaded = CodeDataLogger::from_bytes(mesen_file, &rom);
+        assert_eq!(loaded.flags(), logger.flags());
+        assert!(loaded.extra_flags().iter().all(|f| *f == 0));
+
+        // Different ROM
+        let loaded = CodeDataLogger::from_bytes(&bytes, &[0x00; 0x100]);
+        assert!(loaded.flags().iter().all(|f| *f == 0));
+    }
+
+    #[test]
+    fn test_logging() {
+        use crate::emulator::Emulator;
+        let program: &[u8] = &[
+            0xAD, 0x20, 0x80,   // $8000 LDA $8020
+            0xA9, 0x30,         // $8003 LDA #$30
+            0x85, 0x00,         // $8005 STA $00
+            0xA9, 0x80,         // $8007 LDA #$80
+            0x85, 0x01,         // $8009 STA $01
+            0xB1, 0x00,         // $800B LDA ($00),Y
+            0x20, 0x12, 0x80,   // $800D JSR $8012
+            0x80, 0xFE,         // $8010 BRA $8010
+            0x60,               // $8012 RTS
+        ];
+        let mut rom = vec![0; 0x8000];
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x7FFD] = 0x80;
+        let path = std::env::temp_dir().join(format!("snes-cdl-test-{}.sfc", std::process::id()));
+        std::fs::write(&path, &rom).unwrap();
+
+        let mut emulator = Emulator::new();
+        emulator.bus.rom.load(&path.to_string_lossy()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        emulator.hard_reset();
+        emulator.bus.cdl = Some(CodeDataLogger::new(rom.len()));
+        for _ in 0..9 {
+            emulator.tick();
+        }
+
+        let cdl = emulator.bus.cdl.as_ref().unwrap();
+        let (flags, extra_flags) = (cdl.flags(), cdl.extra_flags());
+        assert_eq!(flags[0x00], CODE | MEMORY_MODE_8 | INDEX_MODE_8);
+        assert_eq!((extra_flags[0x00], extra_flags[0x01]), (OPCODE, OPERAND));
+        assert_eq!((flags[0x20], extra_flags[0x20]), (DATA, 0));
+        assert_eq!((flags[0x30], extra_flags[0x30]), (DATA, INDIRECT_TARGET));
+        // The instruction's own operand isn't data
+        assert_eq!(flags[0x0C] & DATA, 0);
+        assert_ne!(flags[0x12] & SUB_ENTRY_POINT, 0);
+        assert_ne!(flags[0x10] & JUMP_TARGET, 0);
+        assert_eq!(flags[0x13], 0);
+    }
+}
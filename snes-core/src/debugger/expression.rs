@@ -0,0 +1,361 @@
+//! Conditional expressions for breakpoints and watchpoints, e.g. `A == $1234 && X > 4`.
+//!
+//! Operands: registers (A, X, Y, S/SP, D, DB/DBR, PB/PBR, PC, P), `HCOUNT`/`VCOUNT`,
+//! `VALUE`/`ADDRESS` of the access that triggered a watchpoint, `[addr]` for a byte in memory,
+//! and numbers written as `$1F`, `0x1F`, `%11111` or `31`.
+//! Operators, from lowest to highest precedence: `||`, `&&`, `== != < <= > >=`, `|`, `^`, `&`, `+ -`, `!`.
+use crate::common::memory_access::MemoryAccess;
+use crate::cpu::bus::Bus;
+use crate::cpu::registers::Registers;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Variable {
+    A,
+    X,
+    Y,
+    SP,
+    D,
+    DBR,
+    PBR,
+    PC,
+    P,
+    HCount,
+    VCount,
+    Value,
+    Address,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(i64),
+    Variable(Variable),
+    Memory(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+/// State an expression is evaluated against
+pub struct EvalContext<'a> {
+    pub registers: &'a Registers,
+    pub bus: &'a Bus,
+    pub access: Option<&'a MemoryAccess>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "(", ")", "[", "]",
+];
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = text.strip_prefix('$') {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = text.strip_prefix('%') {
+        i64::from_str_radix(binary, 2)
+    } else {
+        text.parse::<i64>()
+    };
+    result.map_err(|_| format!("Invalid number '{}'", text))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || c == '$' || c == '%' || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if c.is_ascii_digit() || c == '$' || c == '%' {
+                tokens.push(Token::Number(parse_number(&word)?));
+            } else {
+                tokens.push(Token::Identifier(word.to_uppercase()));
+            }
+            continue;
+        }
+        let rest: String = chars[i..].iter().take(2).collect();
+        match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            Some(op) => {
+                tokens.push(Token::Operator(op));
+                i += op.len();
+            },
+            None => return Err(format!("Unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek_operator(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        match self.peek_operator() {
+            Some(op) if op == operator => {
+                self.position += 1;
+                Ok(())
+            },
+            _ => Err(format!("Expected '{}'", operator)),
+        }
+    }
+
+    /// Parses a left-associative chain of the given operators
+    fn parse_binary(
+        &mut self,
+        operators: &[(&str, BinaryOperator)],
+        next: fn(&mut Self) -> Result<Expression, String>,
+    ) -> Result<Expression, String> {
+        let mut left = next(self)?;
+        while let Some((_, operator)) = self.peek_operator()
+            .and_then(|op| operators.iter().find(|(text, _)| *text == op))
+        {
+            self.position += 1;
+            let right = next(self)?;
+            left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("||", BinaryOperator::Or)], Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("&&", BinaryOperator::And)], Self::parse_comparison)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[
+            ("==", BinaryOperator::Equal),
+            ("!=", BinaryOperator::NotEqual),
+            ("<", BinaryOperator::Less),
+            ("<=", BinaryOperator::LessEqual),
+            (">", BinaryOperator::Greater),
+            (">=", BinaryOperator::GreaterEqual),
+        ], Self::parse_bit_or)
+    }
+
+    fn parse_bit_or(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("|", BinaryOperator::BitOr)], Self::parse_bit_xor)
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("^", BinaryOperator::BitXor)], Self::parse_bit_and)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[("&", BinaryOperator::BitAnd)], Self::parse_sum)
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, String> {
+        self.parse_binary(&[
+            ("+", BinaryOperator::Add),
+            ("-", BinaryOperator::Sub),
+        ], Self::parse_unary)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Operator("!")) => Ok(Expression::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Operator("(")) => {
+                let expression = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expression)
+            },
+            Some(Token::Operator("[")) => {
+                let expression = self.parse_or()?;
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(expression)))
+            },
+            Some(Token::Number(value)) => Ok(Expression::Number(value)),
+            Some(Token::Identifier(name)) => {
+                let variable = match name.as_str() {
+                    "A" | "C" => Variable::A,
+                    "X" => Variable::X,
+                    "Y" => Variable::Y,
+                    "S" | "SP" => Variable::SP,
+                    "D" | "DP" => Variable::D,
+                    "DB" | "DBR" => Variable::DBR,
+                    "PB" | "PBR" | "K" => Variable::PBR,
+                    "PC" => Variable::PC,
+                    "P" => Variable::P,
+                    "H" | "HCOUNT" => Variable::HCount,
+                    "V" | "VCOUNT" => Variable::VCount,
+                    "VALUE" => Variable::Value,
+                    "ADDRESS" => Variable::Address,
+                    _ => return Err(format!("Unknown variable '{}'", name)),
+                };
+                Ok(Expression::Variable(variable))
+            },
+            Some(Token::Operator(op)) => Err(format!("Unexpected '{}'", op)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let expression = parser.parse_or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(String::from("Unexpected trailing input"));
+        }
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, context: &EvalContext) -> i64 {
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Variable(variable) => {
+                let registers = context.registers;
+                match variable {
+                    Variable::A => registers.a as i64,
+                    Variable::X => registers.x as i64,
+                    Variable::Y => registers.y as i64,
+                    Variable::SP => registers.sp as i64,
+                    Variable::D => registers.d as i64,
+                    Variable::DBR => registers.dbr as i64,
+                    Variable::PBR => registers.pbr as i64,
+                    Variable::PC => registers.pc as i64,
+                    Variable::P => registers.p as i64,
+                    Variable::HCount => context.bus.ppu.registers.h_count as i64,
+                    Variable::VCount => context.bus.ppu.registers.v_count as i64,
+                    Variable::Value => context.access.map(|a| a.value as i64).unwrap_or(0),
+                    Variable::Address => context.access.map(|a| a.address as i64).unwrap_or(0),
+                }
+            },
+            Expression::Memory(address) => {
+                let address = address.evaluate(context) as u32 & 0xFFFFFF;
+                context.bus.read_external(address) as i64
+            },
+            Expression::Not(expression) => (expression.evaluate(context) == 0) as i64,
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(context);
+                // Short-circuit the logical operators
+                match operator {
+                    BinaryOperator::Or if left != 0 => return 1,
+                    BinaryOperator::And if left == 0 => return 0,
+                    _ => {},
+                };
+                let right = right.evaluate(context);
+                match operator {
+                    BinaryOperator::Or | BinaryOperator::And => (right != 0) as i64,
+                    BinaryOperator::Equal => (left == right) as i64,
+                    BinaryOperator::NotEqual => (left != right) as i64,
+                    BinaryOperator::Less => (left < right) as i64,
+                    BinaryOperator::LessEqual => (left <= right) as i64,
+                    BinaryOperator::Greater => (left > right) as i64,
+                    BinaryOperator::GreaterEqual => (left >= right) as i64,
+                    BinaryOperator::BitOr => left | right,
+                    BinaryOperator::BitXor => left ^ right,
+                    BinaryOperator::BitAnd => left & right,
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Sub => left.wrapping_sub(right),
+                }
+            },
+        }
+    }
+
+    pub fn is_true(&self, context: &EvalContext) -> bool {
+        self.evaluate(context) != 0
+    }
+}
+
+
+#[cfg(test)]
+mod debugger_expression_tests {
+    use super::*;
+
+    fn evaluate(input: &str, registers: &Registers, bus: &Bus) -> i64 {
+        let context = EvalContext {registers, bus, access: None};
+        Expression::parse(input).unwrap().evaluate(&context)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut registers = Registers::new();
+        let mut bus = Bus::new();
+        registers.a = 0x1234;
+        registers.x = 5;
+        bus.write(0x7E0010, 0xAB);
+        assert_eq!(evaluate("A == $1234 && X > 4", &registers, &bus), 1);
+        assert_eq!(evaluate("A == $1234 && X > 5", &registers, &bus), 0);
+        assert_eq!(evaluate("a != 0x1234 || x >= 5", &registers, &bus), 1);
+        assert_eq!(evaluate("!(X < 5)", &registers, &bus), 1);
+        assert_eq!(evaluate("A & $FF == $34", &registers, &bus), 1);
+        assert_eq!(evaluate("X + 1 - %10", &registers, &bus), 4);
+        assert_eq!(evaluate("[$7E0000 + $10] == 171", &registers, &bus), 1);
+    }
+
+    #[test]
+    fn test_access_variables() {
+        use crate::common::memory_access::{AccessKind, MemorySpace};
+        let registers = Registers::new();
+        let bus = Bus::new();
+        let access = MemoryAccess {
+            space: MemorySpace::Bus,
+            kind: AccessKind::Write,
+            address: 0x2118,
+            value: 0x42,
+        };
+        let context = EvalContext {registers: &registers, bus: &bus, access: Some(&access)};
+        assert!(Expression::parse("VALUE == $42 && ADDRESS == $2118").unwrap().is_true(&context));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expression::parse("A ==").is_err());
+        assert!(Expression::parse("(A == 1").is_err());
+        assert!(Expression::parse("FOO == 1").is_err());
+        assert!(Expression::parse("A == $XYZ").is_err());
+        assert!(Expression::parse("A == 1 )").is_err());
+        assert!(Expression::parse("A # 1").is_err());
+    }
+}
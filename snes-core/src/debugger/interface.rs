@@ -0,0 +1,434 @@
+use crate::common::memory_access::{AccessKind, MemoryAccess, MemorySpace};
+use crate::cpu::disasm::{self, WidthState};
+use crate::emulator::Emulator;
+use crate::symbols::SymbolTable;
+
+use super::breakpoint::{Breakpoint, Watchpoint};
+use super::expression::{EvalContext, Expression};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StopReason {
+    Breakpoint(usize),
+    Watchpoint(usize, MemoryAccess),
+    Step,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum StepMode {
+    Run,
+    Into,
+    Over { return_address: u32, stack_pointer: u16 },
+    Out { stack_pointer: u16 },
+    RunTo(u32),
+}
+
+/// Drives an `Emulator` while checking breakpoints, watchpoints and stepping requests.
+/// Used in place of `Emulator::tick` and `Emulator::loop_frame`.
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub last_stop_reason: Option<StopReason>,
+    next_id: usize,
+    step_mode: StepMode,
+    /// Address execution stopped at, so resuming doesn't hit the same breakpoint again
+    resume_address: Option<u32>,
+}
+
+/// Parses an address, or an address range, like `$808000` or `$808000-$8080FF`
+pub fn parse_address_range(input: &str) -> Result<(u32, u32), String> {
+    parse_range(input, |text| {
+        let hex = text.strip_prefix('$')
+            .or_else(|| text.strip_prefix("0x"))
+            .unwrap_or(text);
+        u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid address '{}'", text))
+    })
+}
+
+/// Like `parse_address_range`, but either end can also be a label, like `UpdatePlayer`
+pub fn parse_symbolic_address_range(input: &str, symbols: &SymbolTable) -> Result<(u32, u32), String> {
+    parse_range(input, |text| symbols.resolve(text))
+}
+
+fn parse_range(input: &str, parse: impl Fn(&str) -> Result<u32, String>) -> Result<(u32, u32), String> {
+    match input.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse(start.trim())?, parse(end.trim())?);
+            if start > end {
+                return Err(String::from("Range start is past its end"));
+            }
+            Ok((start, end))
+        },
+        None => {
+            let address = parse(input.trim())?;
+            Ok((address, address))
+        },
+    }
+}
+
+fn parse_condition(condition: Option<&str>) -> Result<Option<Expression>, String> {
+    match condition.map(str::trim) {
+        Some(text) if !text.is_empty() => Ok(Some(Expression::parse(text)?)),
+        _ => Ok(None),
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: vec![],
+            watchpoints: vec![],
+            last_stop_reason: None,
+            next_id: 1,
+            step_mode: StepMode::Run,
+            resume_address: None,
+        }
+    }
+
+    fn take_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn add_breakpoint(&mut self, start: u32, end: u32, condition: Option<&str>) -> Result<usize, String> {
+        let condition = parse_condition(condition)?;
+        let id = self.take_id();
+        self.breakpoints.push(Breakpoint {id, start, end, condition, is_enabled: true});
+        Ok(id)
+    }
+
+    pub fn add_watchpoint(&mut self, space: MemorySpace, start: u32, end: u32, kinds: &[AccessKind], condition: Option<&str>) -> Result<usize, String> {
+        let condition = parse_condition(condition)?;
+        let id = self.take_id();
+        self.watchpoints.push(Watchpoint {
+            id,
+            space,
+            start,
+            end,
+            on_read: kinds.contains(&AccessKind::Read),
+            on_write: kinds.contains(&AccessKind::Write),
+            on_execute: kinds.contains(&AccessKind::Execute),
+            condition,
+            is_enabled: true,
+        });
+        Ok(id)
+    }
+
+    /// Removes a breakpoint or watchpoint, returns whether it existed
+    pub fn remove(&mut self, id: usize) -> bool {
+        let count = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|b| b.id != id);
+        self.watchpoints.retain(|w| w.id != id);
+        count != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    pub fn set_enabled(&mut self, id: usize, is_enabled: bool) {
+        for breakpoint in self.breakpoints.iter_mut().filter(|b| b.id == id) {
+            breakpoint.is_enabled = is_enabled;
+        }
+        for watchpoint in self.watchpoints.iter_mut().filter(|w| w.id == id) {
+            watchpoint.is_enabled = is_enabled;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn resume(&mut self) {
+        self.step_mode = StepMode::Run;
+    }
+
+    /// Stops after the next instruction
+    pub fn step_into(&mut self) {
+        self.step_mode = StepMode::Into;
+    }
+
+    /// Like `step_into`, but runs JSR/JSL subroutines to completion
+    pub fn step_over(&mut self, emulator: &Emulator) {
+        let registers = &emulator.cpu.registers;
+        let mut state = WidthState::from_registers(registers);
+        let instruction = disasm::decode(|a| emulator.bus.read_external(a), registers.get_pc_address(), &mut state);
+        self.step_mode = match instruction.opcode {
+            // JSR, JSL, JSR (addr,X)
+            0x20 | 0x22 | 0xFC => StepMode::Over {
+                return_address: (instruction.address & 0xFF0000) |
+                    (instruction.address as u16).wrapping_add(instruction.length as u16) as u32,
+                stack_pointer: registers.sp,
+            },
+            _ => StepMode::Into,
+        };
+    }
+
+    /// Runs until the current subroutine or interrupt handler returns
+    pub fn step_out(&mut self, emulator: &Emulator) {
+        self.step_mode = StepMode::Out {stack_pointer: emulator.cpu.registers.sp};
+    }
+
+    /// Runs until the instruction at `address` is about to execute
+    pub fn run_to(&mut self, address: u32) {
+        self.step_mode = StepMode::RunTo(address);
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        self.step_mode != StepMode::Run
+    }
+
+    fn stop(&mut self, emulator: &Emulator, reason: StopReason) -> Option<StopReason> {
+        self.step_mode = StepMode::Run;
+        self.resume_address = Some(emulator.cpu.registers.get_pc_address());
+        self.last_stop_reason = Some(reason);
+        Some(reason)
+    }
+
+    fn check_breakpoints(&self, emulator: &Emulator, pc_address: u32) -> Option<StopReason> {
+        let context = EvalContext {
+            registers: &emulator.cpu.registers,
+            bus: &emulator.bus,
+            access: None,
+        };
+        if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.matches(pc_address, &context)) {
+            return Some(StopReason::Breakpoint(breakpoint.id));
+        }
+        let access = MemoryAccess {
+            space: MemorySpace::Bus,
+            kind: AccessKind::Execute,
+            address: pc_address,
+            value: emulator.bus.read_external(pc_address),
+        };
+        let context = EvalContext {access: Some(&access), ..context};
+        self.watchpoints.iter()
+            .find(|w| w.matches(&access, &context))
+            .map(|w| StopReason::Watchpoint(w.id, access))
+    }
+
+    fn check_watchpoints(&self, emulator: &Emulator, accesses: &[MemoryAccess]) -> Option<StopReason> {
+        for access in accesses {
+            let context = EvalContext {
+                registers: &emulator.cpu.registers,
+                bus: &emulator.bus,
+                access: Some(access),
+            };
+            if let Some(watchpoint) = self.watchpoints.iter().find(|w| w.matches(access, &context)) {
+                return Some(StopReason::Watchpoint(watchpoint.id, *access));
+            }
+        }
+        None
+    }
+
+    fn is_step_complete(&self, emulator: &Emulator, executed_opcode: u8) -> bool {
+        let registers = &emulator.cpu.registers;
+        match self.step_mode {
+            StepMode::Run => false,
+            StepMode::Into => true,
+            StepMode::Over {return_address, stack_pointer} => {
+                registers.get_pc_address() == return_address && registers.sp >= stack_pointer
+            },
+            // RTS, RTL, RTI
+            StepMode::Out {stack_pointer} => {
+                matches!(executed_opcode, 0x60 | 0x6B | 0x40) && registers.sp > stack_pointer
+            },
+            StepMode::RunTo(address) => registers.get_pc_address() == address,
+        }
+    }
+
+    /// Runs one `Emulator::tick`, returns why execution stopped, if it did.
+    /// Breakpoints stop before the instruction runs, watchpoints right after.
+    pub fn tick(&mut self, emulator: &mut Emulator) -> Option<StopReason> {
+        let is_executing = emulator.cpu.is_ready_to_execute(&emulator.bus);
+        let pc_address = emulator.cpu.registers.get_pc_address();
+        if is_executing {
+            if self.resume_address != Some(pc_address) {
+                if let Some(reason) = self.check_breakpoints(emulator, pc_address) {
+                    return self.stop(emulator, reason);
+                }
+            }
+            self.resume_address = None;
+        }
+
+        let opcode = emulator.bus.read_external(pc_address);
+        let is_watching = self.watchpoints.iter().any(|w| w.is_enabled && (w.on_read || w.on_write));
+        if is_watching {
+            emulator.bus.access_log = Some(vec![]);
+            emulator.bus.ppu.registers.access_log = Some(vec![]);
+        }
+        emulator.tick();
+        if is_watching {
+            let mut accesses = emulator.bus.access_log.take().unwrap_or_default();
+            accesses.extend(emulator.bus.ppu.registers.access_log.take().unwrap_or_default());
+            if let Some(reason) = self.check_watchpoints(emulator, &accesses) {
+                return self.stop(emulator, reason);
+            }
+        }
+
+        if is_executing && self.is_step_complete(emulator, opcode) {
+            return self.stop(emulator, StopReason::Step);
+        }
+        None
+    }
+
+    /// Same as `Emulator::loop_frame`, but returns early when execution stops
+    pub fn run_frame(&mut self, emulator: &mut Emulator) -> Option<StopReason> {
+        let mut frame_started = true;
+        loop {
+            if let Some(reason) = self.tick(emulator) {
+                return Some(reason);
+            }
+            if !frame_started && emulator.bus.ppu.registers.v_count == 260 {
+                return None;
+            }
+            if emulator.bus.ppu.registers.v_count >= 261 {
+                frame_started = false;
+            }
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod debugger_interface_tests {
+    use super::*;
+
+    fn setup() -> Emulator {
+        let mut emulator = Emulator::new();
+        let program: &[(u32, &[u8])] = &[
+            (0x7E0000, &[
+                0xEA,                   // NOP
+                0x20, 0x00, 0x01,       // JSR $0100
+                0xEA,                   // NOP
+                0x80, 0xFE,             // BRA $0005
+            ]),
+            (0x7E0100, &[
+                0xA9, 0x42,             // LDA #$42
+                0x8D, 0x00, 0x02,       // STA $0200
+                0x8F, 0x18, 0x21, 0x00, // STA $002118
+                0x60,                   // RTS
+            ]),
+        ];
+        for (address, bytes) in program {
+            for (i, byte) in bytes.iter().enumerate() {
+                emulator.bus.write(address + i as u32, *byte);
+            }
+        }
+        emulator.cpu.registers.pbr = 0x7E;
+        emulator.cpu.registers.dbr = 0x7E;
+        emulator.cpu.registers.pc = 0x0000;
+        emulator
+    }
+
+    fn run(debugger: &mut Debugger, emulator: &mut Emulator) -> Option<StopReason> {
+        for _ in 0..100 {
+            if let Some(reason) = debugger.tick(emulator) {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_parse_address_range() {
+        assert_eq!(parse_address_range("$808000"), Ok((0x808000, 0x808000)));
+        assert_eq!(parse_address_range("0x8000 - 80FF"), Ok((0x8000, 0x80FF)));
+        assert!(parse_address_range("$80FF-$8000").is_err());
+        assert!(parse_address_range("label").is_err());
+
+        let mut symbols = SymbolTable::new();
+        symbols.add_label(0x809A3C, "UpdatePlayer");
+        assert_eq!(parse_symbolic_address_range("UpdatePlayer", &symbols), Ok((0x809A3C, 0x809A3C)));
+        assert_eq!(parse_symbolic_address_range("UpdatePlayer-$809AFF", &symbols), Ok((0x809A3C, 0x809AFF)));
+        assert!(parse_symbolic_address_range("Missing", &symbols).is_err());
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut emulator = setup();
+        let mut debugger = Debugger::new();
+        let id = debugger.add_breakpoint(0x7E0100, 0x7E0101, None).unwrap();
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Breakpoint(id)));
+        // Stops before executing the instruction
+        assert_eq!(emulator.cpu.registers.pc, 0x0100);
+        assert_eq!(emulator.cpu.registers.a, 0x0000);
+        // Resuming doesn't hit the same breakpoint again
+        assert!(debugger.tick(&mut emulator).is_none());
+        assert_eq!(emulator.cpu.registers.a, 0x0042);
+
+        debugger.set_enabled(id, false);
+        emulator.cpu.registers.pc = 0x0000;
+        assert_eq!(run(&mut debugger, &mut emulator), None);
+        assert!(debugger.remove(id));
+        assert!(!debugger.remove(id));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut emulator = setup();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x7E0004, 0x7E0004, Some("A == $43")).unwrap();
+        assert_eq!(run(&mut debugger, &mut emulator), None);
+
+        let mut emulator = setup();
+        let id = debugger.add_breakpoint(0x7E0004, 0x7E0004, Some("A == $42 && X < 1")).unwrap();
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Breakpoint(id)));
+        assert!(debugger.add_breakpoint(0, 0, Some("A ==")).is_err());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut emulator = setup();
+        let mut debugger = Debugger::new();
+        let id = debugger.add_watchpoint(MemorySpace::Bus, 0x7E0200, 0x7E0200, &[AccessKind::Write], Some("VALUE == $42")).unwrap();
+        let access = MemoryAccess {space: MemorySpace::Bus, kind: AccessKind::Write, address: 0x7E0200, value: 0x42};
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Watchpoint(id, access)));
+        // Stops right after the instruction
+        assert_eq!(emulator.cpu.registers.pc, 0x0105);
+        debugger.remove(id);
+
+        let id = debugger.add_watchpoint(MemorySpace::VRAM, 0x0000, 0x0001, &[AccessKind::Write], None).unwrap();
+        let access = MemoryAccess {space: MemorySpace::VRAM, kind: AccessKind::Write, address: 0x0000, value: 0x42};
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Watchpoint(id, access)));
+        assert_eq!(emulator.cpu.registers.pc, 0x0109);
+        debugger.remove(id);
+
+        let id = debugger.add_watchpoint(MemorySpace::Bus, 0x7E0004, 0x7E0004, &[AccessKind::Execute], None).unwrap();
+        let access = MemoryAccess {space: MemorySpace::Bus, kind: AccessKind::Execute, address: 0x7E0004, value: 0xEA};
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Watchpoint(id, access)));
+        assert_eq!(emulator.cpu.registers.pc, 0x0004);
+    }
+
+    #[test]
+    fn test_step_into_and_over() {
+        let mut emulator = setup();
+        let mut debugger = Debugger::new();
+        debugger.step_into();
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Step));
+        assert_eq!(emulator.cpu.registers.pc, 0x0001);
+        debugger.step_over(&emulator);
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Step));
+        assert_eq!(emulator.cpu.registers.pc, 0x0004);
+        assert_eq!(emulator.cpu.registers.a, 0x0042);
+        // Not a subroutine call, so it behaves like step into
+        debugger.step_over(&emulator);
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Step));
+        assert_eq!(emulator.cpu.registers.pc, 0x0005);
+    }
+
+    #[test]
+    fn test_step_out_and_run_to() {
+        let mut emulator = setup();
+        let mut debugger = Debugger::new();
+        debugger.run_to(0x7E0102);
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Step));
+        assert_eq!(emulator.cpu.registers.pc, 0x0102);
+        debugger.step_out(&emulator);
+        assert_eq!(run(&mut debugger, &mut emulator), Some(StopReason::Step));
+        assert_eq!(emulator.cpu.registers.pc, 0x0004);
+        assert!(!debugger.is_stepping());
+    }
+}
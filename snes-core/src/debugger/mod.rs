@@ -0,0 +1,6 @@
+pub mod interface;
+pub use interface::Debugger;
+pub mod breakpoint;
+pub mod expression;
+pub mod cdl;
+pub mod profiler;
@@ -0,0 +1,295 @@
+//! Attributes the time spent running to the subroutines on the call stack.
+//!
+//! Routines are keyed by their JSR/JSL target (or interrupt handler address),
+//! code running outside of any call counts towards the top level entry.
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::cpu::call_stack::{CallKind, CallStack};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutineStats {
+    /// `None` for the top level code, outside any call
+    pub address: Option<u32>,
+    /// How the routine was last entered
+    pub kind: Option<CallKind>,
+    pub call_count: u64,
+    /// Master cycles spent in the routine and everything it called
+    pub inclusive_cycles: u64,
+    /// Master cycles spent in the routine itself
+    pub exclusive_cycles: u64,
+}
+
+impl RoutineStats {
+    fn new(address: Option<u32>) -> Self {
+        Self {
+            address,
+            kind: None,
+            call_count: 0,
+            inclusive_cycles: 0,
+            exclusive_cycles: 0,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self.address {
+            Some(address) => format!("${:06X}", address),
+            None => String::from("[top level]"),
+        }
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            Some(CallKind::JSR) => "JSR",
+            Some(CallKind::JSL) => "JSL",
+            Some(CallKind::Interrupt(_)) => "Interrupt",
+            None => "",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProfilerColumn {
+    Address,
+    Calls,
+    Inclusive,
+    Exclusive,
+}
+
+pub struct Profiler {
+    routines: HashMap<Option<u32>, RoutineStats>,
+    pub total_cycles: u64,
+    pub frame_count: u64,
+    seen_calls: u64,
+    last_v_count: u16,
+    // Scratch space for deduplicating recursive calls
+    active_routines: Vec<u32>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            routines: HashMap::new(),
+            total_cycles: 0,
+            frame_count: 0,
+            seen_calls: 0,
+            last_v_count: 0,
+            active_routines: vec![],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.routines.clear();
+        self.total_cycles = 0;
+        self.frame_count = 0;
+        self.seen_calls = 0;
+        self.last_v_count = 0;
+    }
+
+    pub fn routines(&self) -> impl Iterator<Item = &RoutineStats> {
+        self.routines.values()
+    }
+
+    pub fn routine(&self, address: Option<u32>) -> Option<&RoutineStats> {
+        self.routines.get(&address)
+    }
+
+    /// Records the cycles the last tick took, `call_stack` being the stack after it ran.
+    /// Frames are counted whenever `v_count` wraps around.
+    pub fn record(&mut self, call_stack: &CallStack, master_cycles: u64, v_count: u16) {
+        if v_count < self.last_v_count {
+            self.frame_count += 1;
+        }
+        self.last_v_count = v_count;
+        self.total_cycles += master_cycles;
+
+        // The call stack counter restarts whenever the CPU is replaced
+        if call_stack.total_calls < self.seen_calls {
+            self.seen_calls = 0;
+        }
+        let new_calls = call_stack.total_calls - self.seen_calls;
+        self.seen_calls = call_stack.total_calls;
+        if new_calls > 0 {
+            if let Some(frame) = call_stack.frames.last() {
+                let stats = self.routines.entry(Some(frame.target)).or_insert_with(|| RoutineStats::new(Some(frame.target)));
+                stats.call_count += 1;
+                stats.kind = Some(frame.kind);
+            }
+        }
+
+        let current = call_stack.frames.last().map(|frame| frame.target);
+        self.routines.entry(current).or_insert_with(|| RoutineStats::new(current))
+            .exclusive_cycles += master_cycles;
+        self.routines.entry(None).or_insert_with(|| RoutineStats::new(None))
+            .inclusive_cycles += master_cycles;
+        self.active_routines.clear();
+        for frame in &call_stack.frames {
+            if self.active_routines.contains(&frame.target) {
+                continue;
+            }
+            self.active_routines.push(frame.target);
+            self.routines.entry(Some(frame.target)).or_insert_with(|| RoutineStats::new(Some(frame.target)))
+                .inclusive_cycles += master_cycles;
+        }
+    }
+
+    /// Average over the frames seen so far, counting a partial frame as one
+    pub fn per_frame(&self, cycles: u64) -> f64 {
+        cycles as f64 / self.frame_count.max(1) as f64
+    }
+
+    pub fn sorted_routines(&self, column: ProfilerColumn, is_descending: bool) -> Vec<&RoutineStats> {
+        let mut routines: Vec<&RoutineStats> = self.routines.values().collect();
+        routines.sort_by(|a, b| {
+            let ordering = match column {
+                // Top level first
+                ProfilerColumn::Address => a.address.cmp(&b.address),
+                ProfilerColumn::Calls => a.call_count.cmp(&b.call_count),
+                ProfilerColumn::Inclusive => a.inclusive_cycles.cmp(&b.inclusive_cycles),
+                ProfilerColumn::Exclusive => a.exclusive_cycles.cmp(&b.exclusive_cycles),
+            }.then(a.address.cmp(&b.address));
+            if is_descending { ordering.reverse() } else { ordering }
+        });
+        routines
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "routine,type,calls,inclusive_cycles,exclusive_cycles,inclusive_per_frame,exclusive_per_frame,inclusive_percent,exclusive_percent\n"
+        );
+        let total = self.total_cycles.max(1) as f64;
+        for stats in self.sorted_routines(ProfilerColumn::Inclusive, true) {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{:.1},{:.1},{:.2},{:.2}\n",
+                stats.name(),
+                stats.kind_name(),
+                stats.call_count,
+                stats.inclusive_cycles,
+                stats.exclusive_cycles,
+                self.per_frame(stats.inclusive_cycles),
+                self.per_frame(stats.exclusive_cycles),
+                stats.inclusive_cycles as f64 * 100.0 / total,
+                stats.exclusive_cycles as f64 * 100.0 / total,
+            ));
+        }
+        csv
+    }
+
+    pub fn save_csv(&self, filename: &Path) -> std::io::Result<()> {
+        std::fs::write(filename, self.to_csv())
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod debugger_profiler_tests {
+    use super::*;
+    use crate::cpu::call_stack::{CallFrame, ReturnKind};
+    use crate::cpu::vectors::Vector;
+    use crate::emulator::Emulator;
+
+    fn call(call_stack: &mut CallStack, kind: CallKind, target: u32) {
+        let stack_pointer = 0x1FF - call_stack.frames.len() as u16 * 3;
+        call_stack.push(CallFrame {kind, caller: 0x008000, target, stack_pointer});
+    }
+
+    fn ret(call_stack: &mut CallStack, kind: ReturnKind) {
+        let stack_pointer = call_stack.frames.last().unwrap().stack_pointer;
+        call_stack.pop(kind, 0, 0x008000, stack_pointer);
+    }
+
+    #[test]
+    fn test_attribution() {
+        let mut profiler = Profiler::new();
+        let mut call_stack = CallStack::new();
+        profiler.record(&call_stack, 10, 0);
+        call(&mut call_stack, CallKind::JSR, 0x009000);
+        profiler.record(&call_stack, 20, 1);
+        call(&mut call_stack, CallKind::JSL, 0x01A000);
+        profiler.record(&call_stack, 30, 2);
+        ret(&mut call_stack, ReturnKind::RTL);
+        profiler.record(&call_stack, 5, 3);
+        // Recursion counts once towards inclusive time
+        call(&mut call_stack, CallKind::JSR, 0x009000);
+        profiler.record(&call_stack, 7, 4);
+        ret(&mut call_stack, ReturnKind::RTS);
+        ret(&mut call_stack, ReturnKind::RTS);
+        profiler.record(&call_stack, 1, 5);
+
+        let top_level = profiler.routine(None).unwrap();
+        assert_eq!((top_level.inclusive_cycles, top_level.exclusive_cycles), (73, 11));
+        let outer = profiler.routine(Some(0x009000)).unwrap();
+        assert_eq!(outer.call_count, 2);
+        assert_eq!((outer.inclusive_cycles, outer.exclusive_cycles), (62, 32));
+        let inner = profiler.routine(Some(0x01A000)).unwrap();
+        assert_eq!((inner.call_count, inner.kind), (1, Some(CallKind::JSL)));
+        assert_eq!((inner.inclusive_cycles, inner.exclusive_cycles), (30, 30));
+        assert_eq!(profiler.total_cycles, 73);
+    }
+
+    #[test]
+    fn test_frames_and_sorting() {
+        let mut profiler = Profiler::new();
+        let mut call_stack = CallStack::new();
+        profiler.record(&call_stack, 100, 200);
+        call(&mut call_stack, CallKind::Interrupt(Vector::NMI), 0x00C000);
+        profiler.record(&call_stack, 40, 225);
+        ret(&mut call_stack, ReturnKind::RTI);
+        profiler.record(&call_stack, 100, 10);
+        call(&mut call_stack, CallKind::Interrupt(Vector::NMI), 0x00C000);
+        profiler.record(&call_stack, 60, 225);
+        assert_eq!(profiler.frame_count, 1);
+        assert_eq!(profiler.per_frame(profiler.routine(Some(0x00C000)).unwrap().inclusive_cycles), 100.0);
+
+        let sorted = profiler.sorted_routines(ProfilerColumn::Exclusive, true);
+        assert_eq!(sorted[0].address, None);
+        let sorted = profiler.sorted_routines(ProfilerColumn::Address, false);
+        assert_eq!(sorted[1].address, Some(0x00C000));
+
+        let csv = profiler.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "[top level],,0,300,200,300.0,200.0,100.00,66.67");
+        assert_eq!(lines[2], "$00C000,Interrupt,2,100,100,100.0,100.0,33.33,33.33");
+
+        profiler.reset();
+        assert_eq!(profiler.routines().count(), 0);
+    }
+
+    #[test]
+    fn test_calls_after_hard_reset() {
+        let mut emulator = Emulator::new();
+        emulator.profiler = Some(Profiler::new());
+        let record = |emulator: &mut Emulator| {
+            emulator.profiler.as_mut().unwrap().record(&emulator.cpu.call_stack, 10, 0);
+        };
+        for target in [0x009000, 0x00A000, 0x00B000] {
+            call(&mut emulator.cpu.call_stack, CallKind::JSR, target);
+            record(&mut emulator);
+        }
+        emulator.hard_reset();
+        // The new CPU's call counter starts from 0 again
+        call(&mut emulator.cpu.call_stack, CallKind::JSR, 0x009000);
+        record(&mut emulator);
+        let profiler = emulator.profiler.as_ref().unwrap();
+        assert_eq!(profiler.routine(Some(0x009000)).unwrap().call_count, 1);
+        assert_eq!(profiler.total_cycles, 10);
+
+        // Without a hard reset the profiler still notices the counter going back
+        let mut profiler = Profiler::new();
+        let mut call_stack = CallStack::new();
+        call(&mut call_stack, CallKind::JSR, 0x009000);
+        call(&mut call_stack, CallKind::JSR, 0x00A000);
+        profiler.record(&call_stack, 10, 0);
+        let mut call_stack = CallStack::new();
+        call(&mut call_stack, CallKind::JSR, 0x00B000);
+        profiler.record(&call_stack, 10, 0);
+        assert_eq!(profiler.routine(Some(0x00B000)).unwrap().call_count, 1);
+    }
+}
@@ -1,9 +1,12 @@
-use crate::cpu::CPU;
+use crate::cpu::{CPU, MASTER_CYCLES_PER_CPU_CYCLE};
 use crate::cpu::bus::Bus;
+use crate::cpu::hdma;
+use crate::debugger::profiler::Profiler;
 
 pub struct Emulator {
     pub cpu: CPU,
     pub bus: Bus,
+    pub profiler: Option<Profiler>,
 }
 
 impl Emulator {
@@ -11,12 +14,23 @@ impl Emulator {
         Self {
             cpu: CPU::new(),
             bus: Bus::new(),
+            profiler: None,
         }
     }
 
     pub fn tick(&mut self) {
+        let ppu_position = (self.bus.ppu.registers.v_count, self.bus.ppu.registers.h_count);
         self.cpu.tick(&mut self.bus);
         self.bus.ppu.tick(self.cpu.registers.cycles);
+        // The CPU is halted while HDMA runs
+        let hdma_cycles = hdma::tick(&mut self.bus, ppu_position);
+        self.bus.ppu.tick(hdma_cycles);
+        self.bus.internal_registers.tick_auto_joypad_read(&self.bus.ppu.registers, &mut self.bus.joypad);
+        if let Some(profiler) = &mut self.profiler {
+            // HDMA time counts towards the routine it interrupted
+            let master_cycles = ((self.cpu.registers.cycles + hdma_cycles) * MASTER_CYCLES_PER_CPU_CYCLE) as u64;
+            profiler.record(&self.cpu.call_stack, master_cycles, self.bus.ppu.registers.v_count);
+        }
 
         self.cpu.registers.cycles = 0;
     }
@@ -39,9 +53,15 @@ impl Emulator {
     }
 
     pub fn hard_reset(&mut self) {
+        let tracer = self.cpu.tracer.take();
         self.cpu = CPU::new();
+        self.cpu.tracer = tracer;
         self.bus.hard_reset();
         self.reset_vector();
+        // Stats from before the reset (likely another ROM) would be misleading
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset();
+        }
     }
 }
 
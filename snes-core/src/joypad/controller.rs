@@ -0,0 +1,125 @@
+// Standard controller buttons, in the order they are shifted out (bit 15 first)
+pub const BUTTON_B: u16         = 0x8000;
+pub const BUTTON_Y: u16         = 0x4000;
+pub const BUTTON_SELECT: u16    = 0x2000;
+pub const BUTTON_START: u16     = 0x1000;
+pub const BUTTON_UP: u16        = 0x0800;
+pub const BUTTON_DOWN: u16      = 0x0400;
+pub const BUTTON_LEFT: u16      = 0x0200;
+pub const BUTTON_RIGHT: u16     = 0x0100;
+pub const BUTTON_A: u16         = 0x0080;
+pub const BUTTON_X: u16         = 0x0040;
+pub const BUTTON_L: u16         = 0x0020;
+pub const BUTTON_R: u16         = 0x0010;
+
+
+/// A device plugged into one of the two controller ports
+pub trait ControllerPort {
+    /// Sets the state of the latch line (JOYWR bit 0)
+    fn latch(&mut self, value: bool);
+    /// Returns the data lines (bit 0 = D0, bit 1 = D1) and clocks the device
+    fn read_data(&mut self) -> u8;
+    /// Returns the data lines without clocking the device
+    fn peek_data(&self) -> u8;
+    /// Updates the state of the buttons, meant to be called by the frontend once per frame
+    fn set_buttons(&mut self, buttons: u16);
+    /// Back to the power-on state, the buttons held are kept
+    fn reset(&mut self);
+}
+
+
+pub struct StandardController {
+    buttons: u16,
+    shift_register: u16,
+    is_latched: bool,
+}
+
+impl StandardController {
+    pub fn new() -> Self {
+        Self {
+            buttons: 0,
+            shift_register: 0,
+            is_latched: false,
+        }
+    }
+}
+
+impl ControllerPort for StandardController {
+    fn latch(&mut self, value: bool) {
+        self.is_latched = value;
+        if value {
+            self.shift_register = self.buttons;
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        // While latched, the buttons are continuously reloaded so the first bit is always returned
+        if self.is_latched {
+            self.shift_register = self.buttons;
+        }
+        let result = self.peek_data();
+        if !self.is_latched {
+            // After the 16 bits have been shifted out, the controller keeps returning 1
+            self.shift_register = (self.shift_register << 1) | 1;
+        }
+        result
+    }
+
+    fn peek_data(&self) -> u8 {
+        (self.shift_register >> 15) as u8
+    }
+
+    fn set_buttons(&mut self, buttons: u16) {
+        // The lowest 4 bits are the controller ID, always 0 for a standard controller
+        self.buttons = buttons & 0xFFF0;
+    }
+
+    fn reset(&mut self) {
+        self.shift_register = 0;
+        self.is_latched = false;
+    }
+}
+
+impl Default for StandardController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod joypad_controller_tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_read() {
+        let mut controller = StandardController::new();
+        controller.set_buttons(BUTTON_B | BUTTON_START | BUTTON_R);
+        controller.latch(true);
+        // Reading while latched always returns the first button
+        assert_eq!(controller.read_data(), 1);
+        assert_eq!(controller.read_data(), 1);
+        controller.latch(false);
+        let mut result = 0u16;
+        for _ in 0..16 {
+            result = (result << 1) | (controller.read_data() as u16);
+        }
+        assert_eq!(result, BUTTON_B | BUTTON_START | BUTTON_R);
+        // Extra reads return 1
+        assert_eq!(controller.read_data(), 1);
+        assert_eq!(controller.read_data(), 1);
+    }
+
+    #[test]
+    fn test_buttons_are_latched() {
+        let mut controller = StandardController::new();
+        controller.set_buttons(BUTTON_B);
+        controller.latch(true);
+        controller.latch(false);
+        // Button changes after latching are not seen until the next latch
+        controller.set_buttons(0);
+        assert_eq!(controller.peek_data(), 1);
+        assert_eq!(controller.read_data(), 1);
+        assert_eq!(controller.read_data(), 0);
+    }
+}
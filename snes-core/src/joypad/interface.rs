@@ -0,0 +1,108 @@
+use super::controller::{ControllerPort, StandardController};
+
+pub const JOYWR: u16        = 0x4016;  // Joypad Output (W)
+pub const JOYSER0: u16      = 0x4016;  // Joypad Input Register A (R)
+pub const JOYSER1: u16      = 0x4017;  // Joypad Input Register B (R)
+
+pub struct Joypad {
+    pub ports: [Box<dyn ControllerPort>; 2],
+    latch: bool,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            ports: [
+                Box::new(StandardController::new()),
+                Box::new(StandardController::new()),
+            ],
+            latch: false,
+        }
+    }
+
+    /// Clears the latch and the ports' shift registers, the plugged devices stay
+    pub fn reset(&mut self) {
+        self.latch = false;
+        for port in self.ports.iter_mut() {
+            port.reset();
+        }
+    }
+
+    pub fn set_latch(&mut self, value: bool) {
+        self.latch = value;
+        for port in self.ports.iter_mut() {
+            port.latch(value);
+        }
+    }
+
+    pub fn is_latched(&self) -> bool {
+        self.latch
+    }
+
+    /// Returns the data lines of a port (bit 0 = D0, bit 1 = D1) and clocks it
+    pub fn read_port(&mut self, port: usize) -> u8 {
+        self.ports[port].read_data() & 0b11
+    }
+
+    fn format_serial_read(address: u16, data: u8, open_bus: u8) -> u8 {
+        match address {
+            // Bits 2-7 are open bus
+            JOYSER0 => (open_bus & 0xFC) | data,
+            // Bits 2-4 are always set, bits 5-7 are open bus
+            JOYSER1 => (open_bus & 0xE0) | 0x1C | data,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn read_external(&self, address: u16, open_bus: u8) -> u8 {
+        let port = (address - JOYSER0) as usize;
+        let data = self.ports[port].peek_data() & 0b11;
+        Joypad::format_serial_read(address, data, open_bus)
+    }
+
+    pub fn read(&mut self, address: u16, open_bus: u8) -> u8 {
+        let port = (address - JOYSER0) as usize;
+        let data = self.read_port(port);
+        Joypad::format_serial_read(address, data, open_bus)
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        if address == JOYWR {
+            self.set_latch(value & 1 == 1);
+        }
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod joypad_interface_tests {
+    use super::*;
+    use crate::joypad::controller::{BUTTON_A, BUTTON_B, BUTTON_Y};
+
+    #[test]
+    fn test_serial_ports() {
+        let mut joypad = Joypad::new();
+        joypad.ports[0].set_buttons(BUTTON_B);
+        joypad.ports[1].set_buttons(BUTTON_Y | BUTTON_A);
+        joypad.write(JOYWR, 0x01);
+        assert!(joypad.is_latched());
+        joypad.write(JOYWR, 0x00);
+        assert!(!joypad.is_latched());
+
+        assert_eq!(joypad.read_external(JOYSER0, 0xFF), 0xFD);
+        assert_eq!(joypad.read(JOYSER0, 0x00), 0x01);
+        assert_eq!(joypad.read(JOYSER0, 0x00), 0x00);
+        assert_eq!(joypad.read(JOYSER1, 0x00), 0x1C);
+        assert_eq!(joypad.read(JOYSER1, 0xFF), 0xFD);
+        for _ in 0..6 {
+            joypad.read(JOYSER1, 0x00);
+        }
+        assert_eq!(joypad.read(JOYSER1, 0x00), 0x1D);
+    }
+}
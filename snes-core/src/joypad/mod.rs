@@ -0,0 +1,3 @@
+pub mod interface;
+pub use interface::Joypad;
+pub mod controller;
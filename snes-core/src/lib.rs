@@ -1,6 +1,10 @@
 pub mod cpu;
 pub mod ppu;
 pub mod rom;
+pub mod joypad;
 pub mod utils;
 pub mod common;
 pub mod emulator;
+pub mod debugger;
+pub mod symbols;
+pub mod nwa;
@@ -0,0 +1,350 @@
+//! NWA (emulator network access) commands and replies.
+//!
+//! Commands are text lines, `NAME arg1;arg2;...`. A `b` prefix means a binary
+//! block (`0x00`, 32-bit big-endian length, data) follows the line.
+//! Text replies are `\n` followed by `key:value\n` lines and an empty line,
+//! binary replies use the same block layout as requests.
+//! https://github.com/usb2snes/emulator-networkaccess
+use crate::common::memory_domain::MemoryDomain;
+use crate::emulator::Emulator;
+
+pub const PROTOCOL_VERSION: &str = "1.0";
+/// LoROM internal header title
+// TODO: read the header location from the cartridge once other mappings are supported
+const HEADER_TITLE_OFFSET: usize = 0x7FC0;
+const HEADER_TITLE_LENGTH: usize = 21;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ErrorKind {
+    ProtocolError,
+    InvalidCommand,
+    InvalidArgument,
+    NotAllowed,
+}
+
+impl ErrorKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::ProtocolError => "protocol_error",
+            ErrorKind::InvalidCommand => "invalid_command",
+            ErrorKind::InvalidArgument => "invalid_argument",
+            ErrorKind::NotAllowed => "not_allowed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// Key/value pairs, keys can repeat (for lists)
+    Ascii(Vec<(String, String)>),
+    Binary(Vec<u8>),
+    Error(ErrorKind, String),
+}
+
+impl Reply {
+    fn ascii(pairs: &[(&str, &str)]) -> Self {
+        Reply::Ascii(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    fn invalid_argument(reason: &str) -> Self {
+        Reply::Error(ErrorKind::InvalidArgument, reason.to_string())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let ascii = |pairs: &[(String, String)]| {
+            let mut text = String::from("\n");
+            for (key, value) in pairs {
+                text.push_str(&format!("{}:{}\n", key, value));
+            }
+            text.push('\n');
+            text.into_bytes()
+        };
+        match self {
+            Reply::Ascii(pairs) => ascii(pairs),
+            Reply::Binary(data) => encode_block(data),
+            Reply::Error(kind, reason) => ascii(&[
+                (String::from("error"), kind.name().to_string()),
+                (String::from("reason"), reason.clone()),
+            ]),
+        }
+    }
+}
+
+/// Binary block: a zero byte, the data length as a big-endian u32 and the data
+pub fn encode_block(data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0];
+    bytes.extend((data.len() as u32).to_be_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+/// `offset;size` pair, a missing size means up to the end of the memory
+pub type Region = (usize, Option<usize>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    EmulatorInfo,
+    EmulationStatus,
+    EmulationPause,
+    EmulationResume,
+    EmulationReset,
+    LoadGame(String),
+    CoreMemories,
+    CoreRead(MemoryDomain, Vec<Region>),
+    /// The data comes in the binary block following the command
+    CoreWrite(MemoryDomain, Vec<Region>),
+    MyNameIs(String),
+}
+
+const COMMAND_NAMES: [&str; 10] = [
+    "EMULATOR_INFO",
+    "EMULATION_STATUS",
+    "EMULATION_PAUSE",
+    "EMULATION_RESUME",
+    "EMULATION_RESET",
+    "LOAD_GAME",
+    "CORE_MEMORIES",
+    "CORE_READ",
+    "CORE_WRITE",
+    "MY_NAME_IS",
+];
+
+/// Offsets and sizes are decimal, or hexadecimal with a `$` or `0x` prefix
+fn parse_number(text: &str) -> Result<usize, Reply> {
+    let text = text.trim();
+    let result = match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    result.map_err(|_| Reply::invalid_argument(&format!("Invalid number '{}'", text)))
+}
+
+fn parse_memory_arguments(arguments: &[&str]) -> Result<(MemoryDomain, Vec<Region>), Reply> {
+    let Some(name) = arguments.first() else {
+        return Err(Reply::invalid_argument("Missing memory name"));
+    };
+    let domain = MemoryDomain::from_name(name)
+        .ok_or_else(|| Reply::invalid_argument(&format!("Unknown memory '{}'", name)))?;
+    let mut regions = vec![];
+    for pair in arguments[1..].chunks(2) {
+        let size = match pair.get(1) {
+            Some(size) => Some(parse_number(size)?),
+            None => None,
+        };
+        regions.push((parse_number(pair[0])?, size));
+    }
+    if regions.is_empty() {
+        regions.push((0, None));
+    }
+    Ok((domain, regions))
+}
+
+impl Command {
+    /// Whether a binary block follows the command line, valid command or not
+    pub fn has_binary_block(line: &str) -> bool {
+        line.trim_start().starts_with('b')
+    }
+
+    /// Parses a command line, returns the command and whether a binary block follows it
+    pub fn parse(line: &str) -> Result<(Self, bool), Reply> {
+        let line = line.trim();
+        let (name, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        let is_binary = Self::has_binary_block(line);
+        let name = if is_binary { &name[1..] } else { name };
+        let arguments: Vec<&str> = arguments.split(';')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+        let command = match name {
+            "EMULATOR_INFO" => Command::EmulatorInfo,
+            "EMULATION_STATUS" => Command::EmulationStatus,
+            "EMULATION_PAUSE" => Command::EmulationPause,
+            "EMULATION_RESUME" => Command::EmulationResume,
+            "EMULATION_RESET" => Command::EmulationReset,
+            "LOAD_GAME" if !arguments.is_empty() => Command::LoadGame(arguments.join(";")),
+            "LOAD_GAME" => return Err(Reply::invalid_argument("Missing game path")),
+            "CORE_MEMORIES" => Command::CoreMemories,
+            "CORE_READ" => {
+                let (domain, regions) = parse_memory_arguments(&arguments)?;
+                Command::CoreRead(domain, regions)
+            },
+            "CORE_WRITE" if is_binary => {
+                let (domain, regions) = parse_memory_arguments(&arguments)?;
+                Command::CoreWrite(domain, regions)
+            },
+            "CORE_WRITE" => return Err(Reply::Error(ErrorKind::ProtocolError, String::from("CORE_WRITE must be sent as bCORE_WRITE"))),
+            "MY_NAME_IS" => Command::MyNameIs(arguments.join(";")),
+            _ => return Err(Reply::Error(ErrorKind::InvalidCommand, format!("Unknown command '{}'", name))),
+        };
+        Ok((command, is_binary))
+    }
+
+    /// Runs the command on `emulator`, `data` is the binary block sent with it
+    pub fn execute(self, data: Option<&[u8]>, emulator: &mut Emulator, is_paused: &mut bool) -> Reply {
+        match self {
+            Command::EmulatorInfo => Reply::ascii(&[
+                ("name", "SNES Emulator"),
+                ("version", env!("CARGO_PKG_VERSION")),
+                ("id", "snes-core"),
+                ("nwa_version", PROTOCOL_VERSION),
+                ("commands", &COMMAND_NAMES.join(",")),
+            ]),
+            Command::EmulationStatus => {
+                let rom = emulator.bus.rom.data();
+                let state = match (rom.is_empty(), *is_paused) {
+                    (true, _) => "no_game",
+                    (false, true) => "paused",
+                    (false, false) => "running",
+                };
+                let title = rom.get(HEADER_TITLE_OFFSET..HEADER_TITLE_OFFSET + HEADER_TITLE_LENGTH)
+                    .map(|title| String::from_utf8_lossy(title).trim().to_string())
+                    .unwrap_or_default();
+                Reply::ascii(&[("state", state), ("game", &title)])
+            },
+            Command::EmulationPause => {
+                *is_paused = true;
+                Reply::Ascii(vec![])
+            },
+            Command::EmulationResume => {
+                *is_paused = false;
+                Reply::Ascii(vec![])
+            },
+            Command::EmulationReset => {
+                emulator.hard_reset();
+                Reply::Ascii(vec![])
+            },
+            // The frontend does the loading, see `NwaServer::poll`
+            Command::LoadGame(_) => Reply::Error(ErrorKind::NotAllowed, String::from("Games are loaded by the frontend")),
+            Command::CoreMemories => {
+                let mut pairs = vec![];
+                for domain in MemoryDomain::ALL {
+                    pairs.push((String::from("name"), domain.name().to_string()));
+                    pairs.push((String::from("access"), String::from("rw")));
+                    pairs.push((String::from("size"), emulator.bus.domain_size(domain).to_string()));
+                }
+                Reply::Ascii(pairs)
+            },
+            Command::CoreRead(domain, regions) => {
+                let regions = match resolve_regions(emulator, domain, &regions, None) {
+                    Ok(regions) => regions,
+                    Err(reply) => return reply,
+                };
+                let data = regions.iter()
+                    .flat_map(|(offset, size)| *offset..offset + size)
+                    .map(|offset| emulator.bus.read_domain(domain, offset))
+                    .collect();
+                Reply::Binary(data)
+            },
+            Command::CoreWrite(domain, regions) => {
+                let data = data.unwrap_or_default();
+                let regions = match resolve_regions(emulator, domain, &regions, Some(data.len())) {
+                    Ok(regions) => regions,
+                    Err(reply) => return reply,
+                };
+                let offsets = regions.iter().flat_map(|(offset, size)| *offset..offset + size);
+                for (offset, value) in offsets.zip(data) {
+                    emulator.bus.write_domain(domain, offset, *value);
+                }
+                Reply::Ascii(vec![])
+            },
+            Command::MyNameIs(name) => Reply::ascii(&[("name", &name)]),
+        }
+    }
+}
+
+/// Fills in missing sizes and checks the regions fit in the memory.
+/// Writes must provide exactly as much data as the regions cover.
+fn resolve_regions(emulator: &Emulator, domain: MemoryDomain, regions: &[Region], data_length: Option<usize>) -> Result<Vec<(usize, usize)>, Reply> {
+    let domain_size = emulator.bus.domain_size(domain);
+    let mut resolved = vec![];
+    for (offset, size) in regions {
+        let size = match (size, data_length) {
+            (Some(size), _) => *size,
+            // Single region writes can omit the size
+            (None, Some(length)) if regions.len() == 1 => length,
+            (None, _) => domain_size.saturating_sub(*offset),
+        };
+        if offset + size > domain_size {
+            return Err(Reply::invalid_argument(&format!(
+                "${:X}-${:X} is out of {} (size ${:X})",
+                offset, offset + size, domain.name(), domain_size,
+            )));
+        }
+        resolved.push((*offset, size));
+    }
+    let total: usize = resolved.iter().map(|(_, size)| size).sum();
+    match data_length {
+        Some(length) if length != total => Err(Reply::invalid_argument(&format!(
+            "Got {} bytes of data for {} bytes of memory", length, total,
+        ))),
+        _ => Ok(resolved),
+    }
+}
+
+
+#[cfg(test)]
+mod nwa_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("EMULATOR_INFO\n"), Ok((Command::EmulatorInfo, false)));
+        assert_eq!(
+            Command::parse("CORE_READ WRAM;$10;2;0x20;4;256"),
+            Ok((Command::CoreRead(MemoryDomain::WRAM, vec![(0x10, Some(2)), (0x20, Some(4)), (256, None)]), false)),
+        );
+        assert_eq!(Command::parse("CORE_READ CGRAM"), Ok((Command::CoreRead(MemoryDomain::CGRAM, vec![(0, None)]), false)));
+        assert_eq!(
+            Command::parse("bCORE_WRITE VRAM;$100;2"),
+            Ok((Command::CoreWrite(MemoryDomain::VRAM, vec![(0x100, Some(2))]), true)),
+        );
+        assert_eq!(Command::parse("LOAD_GAME /roms/game.sfc"), Ok((Command::LoadGame(String::from("/roms/game.sfc")), false)));
+        assert!(matches!(Command::parse("CORE_WRITE WRAM;0;1"), Err(Reply::Error(ErrorKind::ProtocolError, _))));
+        assert!(matches!(Command::parse("CORE_READ APURAM;0;1"), Err(Reply::Error(ErrorKind::InvalidArgument, _))));
+        assert!(matches!(Command::parse("CORE_READ WRAM;zz"), Err(Reply::Error(ErrorKind::InvalidArgument, _))));
+        assert!(matches!(Command::parse("SAVE_STATE 1"), Err(Reply::Error(ErrorKind::InvalidCommand, _))));
+    }
+
+    #[test]
+    fn test_reply_bytes() {
+        assert_eq!(Reply::Ascii(vec![]).to_bytes(), b"\n\n");
+        assert_eq!(Reply::ascii(&[("name", "test")]).to_bytes(), b"\nname:test\n\n");
+        assert_eq!(
+            Reply::Error(ErrorKind::InvalidCommand, String::from("nope")).to_bytes(),
+            b"\nerror:invalid_command\nreason:nope\n\n",
+        );
+        assert_eq!(Reply::Binary(vec![0xAB, 0xCD]).to_bytes(), vec![0x00, 0x00, 0x00, 0x00, 0x02, 0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut emulator = Emulator::new();
+        let mut is_paused = false;
+        let mut run = |command: &str, data: Option<&[u8]>, emulator: &mut Emulator| {
+            Command::parse(command).unwrap().0.execute(data, emulator, &mut is_paused)
+        };
+
+        assert_eq!(run("EMULATION_STATUS", None, &mut emulator), Reply::ascii(&[("state", "no_game"), ("game", "")]));
+        assert_eq!(run("bCORE_WRITE WRAM;$10;2;$20;1", Some(&[1, 2, 3]), &mut emulator), Reply::Ascii(vec![]));
+        assert_eq!(emulator.bus.read_external(0x7E0011), 2);
+        assert_eq!(emulator.bus.read_external(0x7E0020), 3);
+        assert_eq!(run("CORE_READ WRAM;$10;2;$20;1", None, &mut emulator), Reply::Binary(vec![1, 2, 3]));
+        assert_eq!(run("CORE_READ OAM;$200", None, &mut emulator), Reply::Binary(vec![0; 32]));
+        // No mapper provides SRAM yet, it's listed but empty
+        assert_eq!(run("CORE_READ SRAM;0", None, &mut emulator), Reply::Binary(vec![]));
+        let Reply::Ascii(memories) = run("CORE_MEMORIES", None, &mut emulator) else {
+            panic!("CORE_MEMORIES should reply with ASCII")
+        };
+        let sram = memories.iter().position(|pair| *pair == (String::from("name"), String::from("SRAM"))).unwrap();
+        assert_eq!(memories[sram + 2], (String::from("size"), String::from("0")));
+        assert!(matches!(run("bCORE_WRITE WRAM;$10;2", Some(&[1]), &mut emulator), Reply::Error(ErrorKind::InvalidArgument, _)));
+        assert!(matches!(run("CORE_READ CGRAM;$1FF;2", None, &mut emulator), Reply::Error(ErrorKind::InvalidArgument, _)));
+
+        assert_eq!(run("EMULATION_PAUSE", None, &mut emulator), Reply::Ascii(vec![]));
+        assert!(is_paused);
+        assert!(matches!(
+            Command::LoadGame(String::from("/nonexistent.sfc")).execute(None, &mut emulator, &mut is_paused),
+            Reply::Error(ErrorKind::NotAllowed, _),
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod command;
+pub mod server;
+pub use server::NwaServer;
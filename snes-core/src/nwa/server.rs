@@ -0,0 +1,259 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::emulator::Emulator;
+
+use super::command::{Command, ErrorKind, Reply};
+
+/// Port the NWA clients try first
+pub const DEFAULT_PORT: u16 = 0xBEEF;
+/// Largest binary block accepted, above the size of any memory domain
+pub const MAX_BLOCK_LENGTH: usize = 0x100_0000;
+
+struct PendingRequest {
+    command: Command,
+    data: Option<Vec<u8>>,
+    reply: Sender<Reply>,
+}
+
+/// A `LOAD_GAME` request, loading a ROM involves frontend state (symbols, .cdl files...)
+/// so the frontend runs it and then calls `finish`
+pub struct PendingLoad {
+    pub path: String,
+    reply: Sender<Reply>,
+}
+
+impl PendingLoad {
+    pub fn finish(self, result: Result<(), String>) {
+        let reply = match result {
+            Ok(_) => Reply::Ascii(vec![]),
+            Err(err) => Reply::Error(ErrorKind::NotAllowed, err),
+        };
+        // The client may have disconnected meanwhile
+        let _ = self.reply.send(reply);
+    }
+}
+
+/// Accepts NWA clients on background threads.
+/// Their commands run on the emulator's thread whenever it calls `poll`.
+/// Dropping it stops accepting clients and frees the port.
+pub struct NwaServer {
+    pub port: u16,
+    requests: Receiver<PendingRequest>,
+    is_stopped: Arc<AtomicBool>,
+}
+
+/// Reads the binary block following a command. Blocks longer than `MAX_BLOCK_LENGTH`
+/// are skipped without being stored, so the next command line is still in sync.
+fn read_block(reader: &mut impl Read) -> std::io::Result<Result<Vec<u8>, Reply>> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if header[0] != 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid binary block header"));
+    }
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if length > MAX_BLOCK_LENGTH {
+        std::io::copy(&mut reader.take(length as u64), &mut std::io::sink())?;
+        return Ok(Err(Reply::Error(
+            ErrorKind::ProtocolError,
+            format!("Binary block of {} bytes is over the {} bytes limit", length, MAX_BLOCK_LENGTH),
+        )));
+    }
+    let mut data = vec![0; length];
+    reader.read_exact(&mut data)?;
+    Ok(Ok(data))
+}
+
+fn handle_client(stream: TcpStream, requests: Sender<PendingRequest>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The block has to be consumed even when the command is invalid
+        let data = match Command::has_binary_block(&line) {
+            true => Some(read_block(&mut reader)?).transpose(),
+            false => Ok(None),
+        };
+        let reply = match (Command::parse(&line), data) {
+            (Err(reply), _) | (_, Err(reply)) => reply,
+            (Ok((command, _)), Ok(data)) => {
+                let (reply_sender, reply_receiver) = mpsc::channel();
+                let request = PendingRequest {command, data, reply: reply_sender};
+                if requests.send(request).is_err() {
+                    return Ok(());
+                }
+                reply_receiver.recv().unwrap_or_else(|_| Reply::Error(
+                    ErrorKind::NotAllowed,
+                    String::from("The emulator closed"),
+                ))
+            },
+        };
+        writer.write_all(&reply.to_bytes())?;
+    }
+}
+
+impl NwaServer {
+    /// Listens on localhost, port 0 picks any free port
+    pub fn start(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let port = listener.local_addr()?.port();
+        let (sender, receiver) = mpsc::channel();
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let is_listener_stopped = is_stopped.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if is_listener_stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let requests = sender.clone();
+                std::thread::spawn(move || handle_client(stream, requests));
+            }
+        });
+        Ok(Self {port, requests: receiver, is_stopped})
+    }
+
+    /// Runs the commands received since the last call. Stops at a `LOAD_GAME` and returns it,
+    /// the commands after it run on the next call.
+    pub fn poll(&self, emulator: &mut Emulator, is_paused: &mut bool) -> Option<PendingLoad> {
+        while let Ok(request) = self.requests.try_recv() {
+            if let Command::LoadGame(path) = request.command {
+                return Some(PendingLoad {path, reply: request.reply});
+            }
+            let reply = request.command.execute(request.data.as_deref(), emulator, is_paused);
+            // The client may have disconnected meanwhile
+            let _ = request.reply.send(reply);
+        }
+        None
+    }
+}
+
+
+impl Drop for NwaServer {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        // Wakes the listener thread up so it sees the flag and closes the port
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+
+#[cfg(test)]
+mod nwa_server_tests {
+    use super::*;
+    use super::super::command::encode_block;
+
+    #[test]
+    fn test_client_session() {
+        let server = NwaServer::start(0).unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut emulator = Emulator::new();
+        let mut is_paused = false;
+
+        // Sends a command and polls until the reply arrives
+        let mut send = |bytes: &[u8], reply_length: usize, emulator: &mut Emulator, is_paused: &mut bool| {
+            writer.write_all(bytes).unwrap();
+            let mut reply = vec![0; reply_length];
+            let reading = std::thread::scope(|scope| {
+                let handle = scope.spawn(|| reader.read_exact(&mut reply));
+                while !handle.is_finished() {
+                    server.poll(emulator, is_paused);
+                    std::thread::yield_now();
+                }
+                handle.join().unwrap()
+            });
+            reading.unwrap();
+            reply
+        };
+
+        let reply = send(b"MY_NAME_IS tracker\n", 15, &mut emulator, &mut is_paused);
+        assert_eq!(reply, b"\nname:tracker\n\n");
+        let reply = send(b"EMULATION_PAUSE\n", 2, &mut emulator, &mut is_paused);
+        assert_eq!(reply, b"\n\n");
+        assert!(is_paused);
+
+        let mut write = b"bCORE_WRITE WRAM;$100;3\n".to_vec();
+        write.extend(encode_block(&[0x11, 0x22, 0x33]));
+        assert_eq!(send(&write, 2, &mut emulator, &mut is_paused), b"\n\n");
+        assert_eq!(emulator.bus.read_external(0x7E0102), 0x33);
+        // Blocks of invalid or oversized commands are skipped, the next command still works
+        let mut invalid = b"bCORE_WRITE APURAM;0;2\n".to_vec();
+        invalid.extend(encode_block(b"X\n"));
+        let error = b"\nerror:invalid_argument\nreason:Unknown memory 'APURAM'\n\n";
+        assert_eq!(send(&invalid, error.len(), &mut emulator, &mut is_paused), error);
+        let mut oversized = b"bCORE_WRITE WRAM;0\n".to_vec();
+        oversized.extend(encode_block(&vec![0x0A; MAX_BLOCK_LENGTH + 1]));
+        let error = format!(
+            "\nerror:protocol_error\nreason:Binary block of {} bytes is over the {} bytes limit\n\n",
+            MAX_BLOCK_LENGTH + 1, MAX_BLOCK_LENGTH,
+        );
+        assert_eq!(send(&oversized, error.len(), &mut emulator, &mut is_paused), error.as_bytes());
+        let reply = send(b"CORE_READ WRAM;$101;2\n", 7, &mut emulator, &mut is_paused);
+        assert_eq!(reply, encode_block(&[0x22, 0x33]));
+
+        // Errors are answered without reaching the emulator
+        let error = b"\nerror:invalid_command\nreason:Unknown command 'FOO'\n\n";
+        assert_eq!(send(b"FOO\n", error.len(), &mut emulator, &mut is_paused), error);
+    }
+
+    #[test]
+    fn test_load_game_is_returned_by_poll() {
+        let server = NwaServer::start(0).unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut emulator = Emulator::new();
+        let mut is_paused = false;
+
+        writer.write_all(b"LOAD_GAME /roms/game.sfc\nEMULATION_PAUSE\n").unwrap();
+        let pending_load = loop {
+            if let Some(pending_load) = server.poll(&mut emulator, &mut is_paused) {
+                break pending_load;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(pending_load.path, "/roms/game.sfc");
+        assert!(!is_paused);
+        pending_load.finish(Err(String::from("No such file")));
+        let error = b"\nerror:not_allowed\nreason:No such file\n\n";
+        let mut reply = vec![0; error.len()];
+        reader.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, error);
+
+        // The client thread only reads the next command once the load is answered
+        while !is_paused {
+            assert!(server.poll(&mut emulator, &mut is_paused).is_none());
+            std::thread::yield_now();
+        }
+        let mut reply = vec![0; 2];
+        reader.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, b"\n\n");
+    }
+
+    #[test]
+    fn test_drop_frees_the_port() {
+        let server = NwaServer::start(0).unwrap();
+        let port = server.port;
+        drop(server);
+        // The listener thread closes the port once it wakes up
+        let mut restarted = NwaServer::start(port);
+        for _ in 0..100 {
+            if restarted.is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            restarted = NwaServer::start(port);
+        }
+        assert!(restarted.is_ok());
+    }
+}
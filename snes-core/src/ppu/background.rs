@@ -0,0 +1,283 @@
+// Background layer fetching for modes 0-6: tilemap lookup with scroll, screen
+// arrangement and tile size, then character decoding for 2, 4 and 8 bits per pixel.
+use super::registers::{PPURegisters, Background, BgMode, BgSize};
+
+/// A non-transparent background pixel
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BgPixel {
+    pub cgram_index: u8,
+    pub is_high_priority: bool,
+}
+
+/// One tilemap word: vhopppcc cccccccc
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TilemapEntry {
+    pub char_index: u16,
+    pub palette: u8,
+    pub is_high_priority: bool,
+    pub h_flip: bool,
+    pub v_flip: bool,
+}
+
+impl TilemapEntry {
+    pub fn from_word(word: u16) -> Self {
+        Self {
+            char_index: word & 0x3FF,
+            palette: ((word >> 10) & 0b111) as u8,
+            is_high_priority: word & 0x2000 != 0,
+            h_flip: word & 0x4000 != 0,
+            v_flip: word & 0x8000 != 0,
+        }
+    }
+}
+
+/// Bits per pixel of a background in the current mode, `None` when it isn't a tile layer
+pub fn bits_per_pixel(mode: BgMode) -> Option<usize> {
+    match mode {
+        BgMode::Color2BPP => Some(2),
+        BgMode::Color4BPP => Some(4),
+        BgMode::Color8BPP => Some(8),
+        BgMode::OffsetPerTile | BgMode::ExtBg => None,
+    }
+}
+
+/// Word address of the tilemap entry for tile (`tile_x`, `tile_y`). Each 32x32 screen is
+/// 0x400 words, laid out left to right then top to bottom.
+pub fn tilemap_address(base_address: u16, bg_size: BgSize, tile_x: usize, tile_y: usize) -> usize {
+    let (screen_x, screen_y) = (tile_x / 32, tile_y / 32);
+    let screen = match bg_size {
+        BgSize::T32x32 => 0,
+        BgSize::T64x32 => screen_x,
+        BgSize::T32x64 => screen_y,
+        BgSize::T64x64 => screen_x + screen_y * 2,
+    };
+    (base_address as usize + screen * 0x400 + (tile_y % 32) * 32 + (tile_x % 32)) & 0x7FFF
+}
+
+/// Color indices of the 8 pixels in a row of a pair of bitplanes, leftmost first
+pub fn mix_pixel_bitplanes(lsb_bitplane: u8, msb_bitplane: u8) -> [u8; 8] {
+    let mut pixels = [0; 8];
+    for (column, pixel) in pixels.iter_mut().enumerate() {
+        let bit = 7 - column;
+        *pixel = ((lsb_bitplane >> bit) & 1) | (((msb_bitplane >> bit) & 1) << 1);
+    }
+    pixels
+}
+
+/// Color index (0 is transparent) of pixel (`column`, `row`) of an 8x8 character.
+/// Bitplanes come in pairs, each pair being 8 words with one row per word.
+pub fn char_pixel(vram: &[u16], char_address: usize, bpp: usize, column: usize, row: usize) -> u8 {
+    let mut color = 0;
+    for pair in 0..(bpp / 2) {
+        let word = vram[(char_address + pair * 8 + row) & 0x7FFF];
+        let pixels = mix_pixel_bitplanes(word as u8, (word >> 8) as u8);
+        color |= pixels[column] << (pair * 2);
+    }
+    color
+}
+
+fn is_high_res_mode(registers: &PPURegisters) -> bool {
+    matches!(registers.get_bg_mode(), 5 | 6)
+}
+
+/// Tile width and height in pixels. Modes 5 and 6 always use 16 pixel wide tiles.
+fn tile_dimensions(registers: &PPURegisters, background: Background) -> (usize, usize) {
+    let (width, height) = registers.get_bg_tile_size(background).to_usize();
+    if is_high_res_mode(registers) {
+        return (16, height);
+    }
+    (width, height)
+}
+
+/// Pixel of `background` at screen position (`x`, `y`), after scrolling.
+/// `None` when it's transparent.
+pub fn background_pixel(registers: &PPURegisters, background: Background, bpp: usize, x: usize, y: usize) -> Option<BgPixel> {
+    // TODO: only 256 pixels are drawn per line, so high resolution modes show every other pixel
+    let x = if is_high_res_mode(registers) { x * 2 } else { x };
+    let (h_scroll, v_scroll) = registers.get_bg_scroll(background);
+    let (tile_width, tile_height) = tile_dimensions(registers, background);
+    let bg_size = registers.get_bg_size(background);
+    let (tiles_wide, tiles_high) = bg_size.to_usize();
+    let bg_x = (x + h_scroll as usize) % (tiles_wide * tile_width);
+    let bg_y = (y + v_scroll as usize) % (tiles_high * tile_height);
+
+    let base_address = registers.get_bg_tile_base_address(background);
+    let vram = registers.vram();
+    let entry_address = tilemap_address(base_address, bg_size, bg_x / tile_width, bg_y / tile_height);
+    let entry = TilemapEntry::from_word(vram[entry_address]);
+
+    let mut pixel_x = bg_x % tile_width;
+    let mut pixel_y = bg_y % tile_height;
+    if entry.h_flip {
+        pixel_x = tile_width - 1 - pixel_x;
+    }
+    if entry.v_flip {
+        pixel_y = tile_height - 1 - pixel_y;
+    }
+    // 16 pixel tiles are made of the neighbouring characters, the one below being 16 characters on
+    let char_index = entry.char_index as usize + (pixel_x / 8) + (pixel_y / 8) * 16;
+    let char_address = registers.get_bg_char_base_address(background) as usize + (char_index & 0x3FF) * bpp * 4;
+    let color = char_pixel(vram, char_address, bpp, pixel_x % 8, pixel_y % 8);
+    if color == 0 {
+        return None;
+    }
+    let palette_base = match bpp {
+        // Mode 0 gives each background its own 32 colors
+        2 if registers.get_bg_mode() == 0 => (background as usize) * 32 + (entry.palette as usize) * 4,
+        2 => (entry.palette as usize) * 4,
+        4 => (entry.palette as usize) * 16,
+        _ => 0,
+    };
+    Some(BgPixel {
+        cgram_index: (palette_base + color as usize) as u8,
+        is_high_priority: entry.is_high_priority,
+    })
+}
+
+
+#[cfg(test)]
+mod ppu_background_tests {
+    use super::*;
+    use crate::ppu::registers::{BGMODE, BG1SC, BG2SC, BG12NBA, BG1HOFS, BG1VOFS};
+
+    /// Writes an 8x8 character where every pixel has `color`
+    fn write_solid_char(registers: &mut PPURegisters, char_address: usize, bpp: usize, color: u8) {
+        for pair in 0..(bpp / 2) {
+            let low = if color >> (pair * 2) & 1 == 1 { 0x00FF } else { 0 };
+            let high = if color >> (pair * 2 + 1) & 1 == 1 { 0xFF00 } else { 0 };
+            for row in 0..8 {
+                registers.vram_mut()[char_address + pair * 8 + row] = low | high;
+            }
+        }
+    }
+
+    #[test]
+    fn test_tilemap_entry() {
+        let entry = TilemapEntry::from_word(0b1110_1100_0000_0011);
+        assert_eq!(entry, TilemapEntry {char_index: 0x003, palette: 0b011, is_high_priority: true, h_flip: true, v_flip: true});
+    }
+
+    #[test]
+    fn test_tilemap_address() {
+        assert_eq!(tilemap_address(0x1000, BgSize::T32x32, 40, 3), 0x1000 + 3 * 32 + 8);
+        assert_eq!(tilemap_address(0x1000, BgSize::T64x32, 40, 3), 0x1400 + 3 * 32 + 8);
+        assert_eq!(tilemap_address(0x1000, BgSize::T32x64, 5, 33), 0x1400 + 32 + 5);
+        assert_eq!(tilemap_address(0x1000, BgSize::T64x64, 5, 33), 0x1800 + 32 + 5);
+        assert_eq!(tilemap_address(0x1000, BgSize::T64x64, 33, 33), 0x1C00 + 32 + 1);
+    }
+
+    #[test]
+    fn test_mix_pixel_bitplanes() {
+        assert_eq!(
+            mix_pixel_bitplanes(0b00000000, 0b00000000),
+            [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00],
+        );
+        assert_eq!(
+            mix_pixel_bitplanes(0b11111111, 0b11111111),
+            [0b11, 0b11, 0b11, 0b11, 0b11, 0b11, 0b11, 0b11],
+        );
+        assert_eq!(
+            mix_pixel_bitplanes(0b11111111, 0b00000000),
+            [0b01, 0b01, 0b01, 0b01, 0b01, 0b01, 0b01, 0b01],
+        );
+        assert_eq!(
+            mix_pixel_bitplanes(0b00000000, 0b11111111),
+            [0b10, 0b10, 0b10, 0b10, 0b10, 0b10, 0b10, 0b10],
+        );
+        assert_eq!(
+            mix_pixel_bitplanes(0b11110000, 0b00001111),
+            [0b01, 0b01, 0b01, 0b01, 0b10, 0b10, 0b10, 0b10],
+        );
+    }
+
+    #[test]
+    fn test_char_pixel() {
+        let mut vram = vec![0u16; 0x8000];
+        // Row 2: plane 0 = 10000000, plane 1 = 11000000, plane 2 = 01000000
+        vram[0x102] = 0xC080;
+        vram[0x10A] = 0x0040;
+        assert_eq!(char_pixel(&vram, 0x100, 2, 0, 2), 0b11);
+        assert_eq!(char_pixel(&vram, 0x100, 2, 1, 2), 0b10);
+        assert_eq!(char_pixel(&vram, 0x100, 4, 1, 2), 0b0110);
+        assert_eq!(char_pixel(&vram, 0x100, 4, 2, 2), 0);
+    }
+
+    #[test]
+    fn test_2bpp_palettes() {
+        let mut registers = PPURegisters::new();
+        registers.write(BGMODE, 0);
+        registers.write(BG1SC, 0x04);      // Tilemap at $0400
+        registers.write(BG2SC, 0x04);
+        registers.write(BG12NBA, 0x11);    // Characters at $1000
+        write_solid_char(&mut registers, 0x1000 + 8, 2, 3);
+        // Character 1, palette 2
+        registers.vram_mut()[0x0400] = 0x0801;
+        let pixel = background_pixel(&registers, Background::Bg1, 2, 0, 0);
+        assert_eq!(pixel, Some(BgPixel {cgram_index: 8 + 3, is_high_priority: false}));
+        // BG2 uses the next 32 colors in mode 0 only
+        assert_eq!(background_pixel(&registers, Background::Bg2, 2, 0, 0).unwrap().cgram_index, 32 + 8 + 3);
+        registers.write(BGMODE, 1);
+        assert_eq!(background_pixel(&registers, Background::Bg2, 2, 0, 0).unwrap().cgram_index, 8 + 3);
+        // Color 0 is transparent
+        registers.vram_mut()[0x0400] = 0x0000;
+        assert_eq!(background_pixel(&registers, Background::Bg1, 2, 0, 0), None);
+    }
+
+    #[test]
+    fn test_4bpp_and_8bpp() {
+        let mut registers = PPURegisters::new();
+        registers.write(BGMODE, 3);
+        registers.write(BG12NBA, 0x00);
+        write_solid_char(&mut registers, 16, 4, 0x0A);
+        write_solid_char(&mut registers, 32, 8, 0xC5);
+        registers.vram_mut()[0x0000] = 0x2C01;  // Character 1, palette 3, high priority
+        assert_eq!(
+            background_pixel(&registers, Background::Bg2, 4, 0, 0),
+            Some(BgPixel {cgram_index: 48 + 0x0A, is_high_priority: true}),
+        );
+        // 8bpp ignores the palette, character 1 is 32 words in
+        assert_eq!(background_pixel(&registers, Background::Bg1, 8, 0, 0).unwrap().cgram_index, 0xC5);
+    }
+
+    #[test]
+    fn test_scroll_and_flips() {
+        let mut registers = PPURegisters::new();
+        registers.write(BG1SC, 0x04);
+        registers.write(BG12NBA, 0x01);
+        // Character 1 has a single pixel, at column 0 of row 0
+        registers.vram_mut()[0x1008] = 0x0080;
+        // Tile (1, 1)
+        registers.vram_mut()[0x0400 + 32 + 1] = 0x0001;
+        assert!(background_pixel(&registers, Background::Bg1, 2, 8, 8).is_some());
+        assert!(background_pixel(&registers, Background::Bg1, 2, 9, 8).is_none());
+        registers.vram_mut()[0x0400 + 32 + 1] = 0xC001;
+        assert!(background_pixel(&registers, Background::Bg1, 2, 15, 15).is_some());
+
+        registers.write(BG1HOFS, 0x05);
+        registers.write(BG1HOFS, 0x00);
+        registers.write(BG1VOFS, 0x03);
+        registers.write(BG1VOFS, 0x00);
+        assert!(background_pixel(&registers, Background::Bg1, 2, 10, 12).is_some());
+        // Scrolling wraps around the 256x256 map
+        registers.write(BG1HOFS, 0x05);
+        registers.write(BG1HOFS, 0x01);
+        assert!(background_pixel(&registers, Background::Bg1, 2, 10, 12).is_some());
+    }
+
+    #[test]
+    fn test_16x16_tiles() {
+        let mut registers = PPURegisters::new();
+        registers.write(BGMODE, 0x10);
+        registers.write(BG1SC, 0x04);
+        registers.write(BG12NBA, 0x01);
+        // The bottom right quarter of tile 2 is character 2 + 17
+        write_solid_char(&mut registers, 0x1000 + 19 * 8, 2, 1);
+        registers.vram_mut()[0x0400] = 0x0002;
+        assert!(background_pixel(&registers, Background::Bg1, 2, 12, 12).is_some());
+        assert!(background_pixel(&registers, Background::Bg1, 2, 4, 12).is_none());
+        // Flipping moves it to the top left
+        registers.vram_mut()[0x0400] = 0xC002;
+        assert!(background_pixel(&registers, Background::Bg1, 2, 4, 4).is_some());
+        assert!(background_pixel(&registers, Background::Bg1, 2, 12, 12).is_none());
+    }
+}
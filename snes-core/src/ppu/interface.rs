@@ -1,13 +1,51 @@
+use super::background::{self, BgPixel};
+use super::sprites::{self, ObjPixel, OBJ_LINE_WIDTH};
 use super::registers::{PPURegisters, Background, MAX_TV_HEIGHT, MAX_TV_WIDTH};
 use crate::utils::color::rgb555_to_rgb888;
 
 const FRAMEBUFFER_SIZE: usize = MAX_TV_HEIGHT * MAX_TV_WIDTH * 4;
 
+/// A main screen layer: a background's high or low priority tiles, or sprites of a priority
+#[derive(Debug, Copy, Clone)]
+enum Layer {
+    Bg(Background, bool),
+    Obj(u8),
+}
+
+/// Layers of a mode from front to back
+fn layer_order(mode: u8, is_bg3_priority_enabled: bool) -> &'static [Layer] {
+    use Background::*;
+    use Layer::*;
+    match mode {
+        0 => &[
+            Obj(3), Bg(Bg1, true), Bg(Bg2, true), Obj(2), Bg(Bg1, false), Bg(Bg2, false),
+            Obj(1), Bg(Bg3, true), Bg(Bg4, true), Obj(0), Bg(Bg3, false), Bg(Bg4, false),
+        ],
+        1 if is_bg3_priority_enabled => &[
+            Bg(Bg3, true), Obj(3), Bg(Bg1, true), Bg(Bg2, true), Obj(2), Bg(Bg1, false), Bg(Bg2, false),
+            Obj(1), Obj(0), Bg(Bg3, false),
+        ],
+        1 => &[
+            Obj(3), Bg(Bg1, true), Bg(Bg2, true), Obj(2), Bg(Bg1, false), Bg(Bg2, false),
+            Obj(1), Bg(Bg3, true), Obj(0), Bg(Bg3, false),
+        ],
+        2..=6 => &[
+            Obj(3), Bg(Bg1, true), Obj(2), Bg(Bg2, true), Obj(1), Bg(Bg1, false), Obj(0), Bg(Bg2, false),
+        ],
+        // TODO: mode 7 background
+        _ => &[Obj(3), Obj(2), Obj(1), Obj(0)],
+    }
+}
+
 pub struct PPU {
     framebuffer: Vec<u8>,
     pub registers: PPURegisters,
     was_vblank_nmi_set: bool,
     pub is_irq_set: bool,
+    /// Frames completed since power on
+    pub frame_count: u64,
+    /// Sprites of the line being drawn
+    obj_pixels: [Option<ObjPixel>; OBJ_LINE_WIDTH],
 }
 
 impl PPU {
@@ -17,6 +55,8 @@ impl PPU {
             registers: PPURegisters::new(),
             was_vblank_nmi_set: false,
             is_irq_set: false,
+            frame_count: 0,
+            obj_pixels: [None; OBJ_LINE_WIDTH],
         }
     }
 
@@ -38,6 +78,9 @@ impl PPU {
     }
 
     pub fn dot_cycle(&mut self) {
+        if self.registers.h_count == 0 && !self.registers.is_vblanking() {
+            self.evaluate_obj_line();
+        }
         if !self.registers.is_vblanking() && !self.registers.is_hblanking() {
             self.put_pixel(self.compute_pixel())
         }
@@ -52,10 +95,18 @@ impl PPU {
             if self.registers.v_count > 224 && !self.was_vblank_nmi_set {
                 self.registers.vblank_nmi = true;
                 self.was_vblank_nmi_set = true;
+                if !self.registers.is_forced_blank() {
+                    self.registers.reload_oam_address();
+                }
             }
             if self.registers.v_count > 261 {
                 self.was_vblank_nmi_set = false;
                 self.registers.v_count = 0;
+                self.frame_count += 1;
+                if !self.registers.is_forced_blank() {
+                    self.registers.is_obj_range_over = false;
+                    self.registers.is_obj_time_over = false;
+                }
             }
         }
         if !self.registers.is_vblanking() {
@@ -68,104 +119,64 @@ impl PPU {
     }
 
     fn compute_pixel(&self) -> (u8, u8, u8) {
-        // Objectives:
-        // 1. first try to render one of the backgrounds. Background 0 for now.
-        // 2. render the rest of the backgrounds
-        // 3. render sprites
-        // 4. figure out the priorities of each background
-        self.compute_background_pixel(Background::Bg1)
-        // (0xFF, 0x00, 0xFF)
-    }
-
-    // TODO: wirte tests for this function
-    fn compute_background_pixel(&self, background: Background) -> (u8, u8, u8) {
-        // 0. detect video mode?
-        // 1. get base tileset vram address
-        // 2. get base charset vram address
-        // 3. know the height and width of background
-        // 4. calculate which is the current tile: (x / tile width) + ((y / tile height) * background width)
-        // 4.1: TODO: consider scroll values of the background for this calculation (x += x scroll, y += y scroll)
-        // 5. get the tile information from vram
-        // 6. get character index for the tile
-        // 7. calculate the vram address of the character
-        // 7.1: TODO: consider that the character vram address also depends on the background's BPP mode
-        // TODO: consider that each tile can be mirrored either vertically or horizontally. Keep this in mind when fetching the character information from vram
-        // 8. look up color palette
-        // ----
-        // possible optimizations:
-        // - Fetch all of the necessary data before starting to render the scanline
-        // - Pre-render each background in a framebuffer and only update the framebuffer if either the tileset or charset changes
-        let tileset_vram_base_address = self.registers.get_bg_tile_base_address(background) as usize;
-        let charset_vram_base_address = self.registers.get_bg_char_base_address(background) as usize;
-        let (bg_size_width, _) = self.registers.get_bg_size(background).to_usize();
-        let (tile_size_width, tile_size_height) = self.registers.get_bg_tile_size(background).to_usize();
-        let vram = self.registers.vram();
+        if self.registers.is_forced_blank() {
+            return (0x00, 0x00, 0x00);
+        }
         let x = (self.registers.h_count as usize) - 22; // H count ranges from 0 to 339. Pixels become visible at 22.
-        let y = (self.registers.v_count as usize) - 1; // V count ranges from 0 to 261 (depending on the region and video mode). Pixels become visible at 1.
-
-        let current_tile = (x / tile_size_width) + ((y / tile_size_height) * bg_size_width);
-        let tile_byte = vram[tileset_vram_base_address + current_tile];
-        let char_index = tile_byte & 0b11_11111111;
-        let current_char_column = x.rem_euclid(tile_size_width);
-        let current_char_row = y.rem_euclid(tile_size_height);
-
-        let effective_vram_address =
-            charset_vram_base_address +
-            ((char_index as usize) * tile_size_width) +
-            current_char_row;
-
-        let vram_word = vram[effective_vram_address];
-        let lsb_bitplane= vram_word as u8;
-        let msb_bitplane= (vram_word >> 8) as u8;
-
-        let pixels = Self::mix_pixel_bitplanes(lsb_bitplane, msb_bitplane);
-        let effective_pixel_index = pixels[current_char_column];
-
-        // TODO: this cgram lookup is experimental, it will eventually be replaced by actual cgram lookup
-        let base_cgram_address: u8 = 0x00;
-        let rgb555_pixel = self.registers.read_cgram(base_cgram_address.wrapping_add(effective_pixel_index));
-        rgb555_to_rgb888((
+        // The first visible line (V count 1) already shows background line 1
+        let y = self.registers.v_count as usize;
+        let rgb555_pixel = self.registers.read_cgram(self.compute_main_screen_cgram_index(x, y));
+        let (red, green, blue) = rgb555_to_rgb888((
             (rgb555_pixel & 0b11111) as u8,
             ((rgb555_pixel >> 5) & 0b11111) as u8,
             ((rgb555_pixel >> 10) & 0b11111) as u8,
-        ))
+        ));
+        let brightness = self.registers.get_brightness() as u16;
+        let apply_brightness = |component: u8| ((component as u16 * brightness) / 15) as u8;
+        (apply_brightness(red), apply_brightness(green), apply_brightness(blue))
+    }
+
+    /// Sprites show one line lower than backgrounds, so the first visible line is sprite line 0
+    fn evaluate_obj_line(&mut self) {
+        if self.registers.is_forced_blank() {
+            self.obj_pixels = [None; OBJ_LINE_WIDTH];
+            return;
+        }
+        let obj_line = sprites::evaluate_line(&self.registers, (self.registers.v_count - 1) as usize);
+        self.obj_pixels = obj_line.pixels;
+        self.registers.is_obj_range_over |= obj_line.is_range_over;
+        self.registers.is_obj_time_over |= obj_line.is_time_over;
     }
 
-    fn mix_pixel_bitplanes(lsb_bitplane: u8, msb_bitplane: u8) -> [u8; 8] {
-        [
-            (
-                (lsb_bitplane >> 7) |
-                ((msb_bitplane >> 7) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 6) & 1) |
-                (((msb_bitplane >> 6) & 1) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 5) & 1) |
-                (((msb_bitplane >> 5) & 1) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 4) & 1) |
-                (((msb_bitplane >> 4) & 1) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 3) & 1) |
-                (((msb_bitplane >> 3) & 1) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 2) & 1) |
-                (((msb_bitplane >> 2) & 1) << 1)
-            ),
-            (
-                ((lsb_bitplane >> 1) & 1) |
-                (((msb_bitplane >> 1) & 1) << 1)
-            ),
-            (
-                (lsb_bitplane & 1) |
-                ((msb_bitplane & 1) << 1)
-            ),
-        ]
+    /// CGRAM index of the frontmost opaque layer, or the backdrop (color 0)
+    // TODO: offset-per-tile, mode 7, mosaic, windows and color math
+    fn compute_main_screen_cgram_index(&self, x: usize, y: usize) -> u8 {
+        let (bg1, bg2, bg3, bg4) = self.registers.get_bg_modes();
+        let backgrounds = [Background::Bg1, Background::Bg2, Background::Bg3, Background::Bg4];
+        let mut pixels: [Option<BgPixel>; 4] = [None; 4];
+        for (index, mode) in [bg1, bg2, bg3, bg4].into_iter().enumerate() {
+            let background = backgrounds[index];
+            let Some(bpp) = mode.and_then(background::bits_per_pixel) else {
+                continue
+            };
+            if self.registers.is_bg_on_main_screen(background) {
+                pixels[index] = background::background_pixel(&self.registers, background, bpp, x, y);
+            }
+        }
+        let obj_pixel = self.obj_pixels[x].filter(|_| self.registers.is_obj_on_main_screen());
+        for layer in layer_order(self.registers.get_bg_mode(), self.registers.is_bg3_priority_enabled()) {
+            match *layer {
+                Layer::Bg(background, is_high_priority) => match pixels[background as usize] {
+                    Some(pixel) if pixel.is_high_priority == is_high_priority => return pixel.cgram_index,
+                    _ => {},
+                },
+                Layer::Obj(priority) => match obj_pixel {
+                    Some(pixel) if pixel.priority == priority => return pixel.cgram_index,
+                    _ => {},
+                },
+            }
+        }
+        0
     }
 
     fn put_pixel(&mut self, pixel: (u8, u8, u8)) {
@@ -249,26 +260,92 @@ mod ppu_general_test {
     }
 
     #[test]
-    fn test_mix_pixel_bitplanes() {
-        assert_eq!(
-            PPU::mix_pixel_bitplanes(0b00000000, 0b00000000),
-            [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00],
-        );
-        assert_eq!(
-            PPU::mix_pixel_bitplanes(0b11111111, 0b11111111),
-            [0b11, 0b11, 0b11, 0b11, 0b11, 0b11, 0b11, 0b11],
-        );
-        assert_eq!(
-            PPU::mix_pixel_bitplanes(0b11111111, 0b00000000),
-            [0b01, 0b01, 0b01, 0b01, 0b01, 0b01, 0b01, 0b01],
-        );
-        assert_eq!(
-            PPU::mix_pixel_bitplanes(0b00000000, 0b11111111),
-            [0b10, 0b10, 0b10, 0b10, 0b10, 0b10, 0b10, 0b10],
-        );
-        assert_eq!(
-            PPU::mix_pixel_bitplanes(0b11110000, 0b00001111),
-            [0b01, 0b01, 0b01, 0b01, 0b10, 0b10, 0b10, 0b10],
-        );
+    fn test_main_screen_priorities() {
+        use crate::ppu::registers::{BGMODE, BG1SC, BG3SC, BG12NBA, BG34NBA, TM, INIDISP};
+        let mut ppu = PPU::new();
+        ppu.registers.write(BGMODE, 0x01);
+        ppu.registers.write(BG1SC, 0x04);
+        ppu.registers.write(BG3SC, 0x08);
+        ppu.registers.write(BG12NBA, 0x01);
+        ppu.registers.write(BG34NBA, 0x02);
+        // BG1 (4bpp): character 1 is color 1. BG3 (2bpp): character 1 is color 2, high priority.
+        for row in 0..8 {
+            ppu.registers.vram_mut()[0x1010 + row] = 0x00FF;
+            ppu.registers.vram_mut()[0x2008 + row] = 0xFF00;
+        }
+        ppu.registers.vram_mut()[0x0400] = 0x0001;
+        ppu.registers.vram_mut()[0x0800] = 0x2001;
+        // Nothing is on the main screen: backdrop
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 0), 0);
+        ppu.registers.write(TM, 0b0101);
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 0), 1);
+        // BG3 high priority tiles go in front of everything
+        ppu.registers.write(BGMODE, 0x09);
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 0), 2);
+        ppu.registers.write(TM, 0b0100);
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 0), 2);
+
+        ppu.registers.cgram_mut()[2] = 0x7FFF;
+        ppu.registers.v_count = 1;
+        ppu.registers.h_count = 22;
+        assert_eq!(ppu.compute_pixel(), (0x00, 0x00, 0x00));
+        ppu.registers.write(INIDISP, 0x0F);
+        assert_eq!(ppu.compute_pixel(), (0xFF, 0xFF, 0xFF));
+        ppu.registers.write(INIDISP, 0x8F);
+        assert_eq!(ppu.compute_pixel(), (0x00, 0x00, 0x00));
+    }
+
+    #[test]
+    fn test_obj_priorities() {
+        use crate::ppu::registers::{BGMODE, BG1SC, BG12NBA, TM, OAMADDL, OAMDATA};
+        let mut ppu = PPU::new();
+        ppu.registers.write(BGMODE, 0x01);
+        ppu.registers.write(BG1SC, 0x04);
+        ppu.registers.write(BG12NBA, 0x01);
+        // BG1 character 1 and OBJ character 1 are both color 1
+        for row in 0..8 {
+            ppu.registers.vram_mut()[0x1010 + row] = 0x00FF;
+            ppu.registers.vram_mut()[0x0010 + row] = 0x00FF;
+        }
+        ppu.registers.vram_mut()[0x0400] = 0x2001;
+        ppu.registers.write(TM, 0b10001);
+        // Sprite 0 at (0, 0), priority 3, every other sprite below the screen
+        for (i, byte) in [0x00, 0x00, 0x01, 0x30].into_iter().enumerate() {
+            ppu.registers.oam_mut()[i] = byte;
+        }
+        for i in 1..128 {
+            ppu.registers.oam_mut()[i * 4 + 1] = 0xF0;
+        }
+        ppu.registers.v_count = 1;
+        ppu.evaluate_obj_line();
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 1), 128 + 1);
+        // Priority 2 sprites are behind high priority BG1 tiles
+        ppu.registers.oam_mut()[3] = 0x20;
+        ppu.evaluate_obj_line();
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 1), 1);
+        ppu.registers.vram_mut()[0x0400] = 0x0001;
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 1), 128 + 1);
+        // Without OBJ on the main screen
+        ppu.registers.write(TM, 0b00001);
+        assert_eq!(ppu.compute_main_screen_cgram_index(0, 1), 1);
+
+        // 33 sprites on the line set range over until the end of V-Blank
+        for i in 0..33 {
+            ppu.registers.oam_mut()[i * 4 + 1] = 0x00;
+        }
+        ppu.evaluate_obj_line();
+        assert!(ppu.registers.is_obj_range_over);
+        assert!(!ppu.registers.is_obj_time_over);
+        ppu.registers.write(OAMADDL, 0x10);
+        ppu.registers.write(OAMDATA, 0x00);
+        ppu.registers.v_count = 224;
+        ppu.registers.h_count = 339;
+        ppu.increment_hv_count();
+        // The OAM address is reloaded at the start of V-Blank
+        assert_eq!(ppu.registers.oam_address, 0x20);
+        ppu.registers.v_count = 261;
+        ppu.registers.h_count = 339;
+        ppu.increment_hv_count();
+        assert!(!ppu.registers.is_obj_range_over);
     }
 }
@@ -1,3 +1,5 @@
 pub mod interface;
 pub use interface::PPU;
 pub mod registers;
+pub mod background;
+pub mod sprites;
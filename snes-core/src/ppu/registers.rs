@@ -1,3 +1,5 @@
+use crate::common::memory_access::{MemoryAccess, MemorySpace, AccessKind};
+
 // PPU Control
 pub const INIDISP: u16      = 0x2100;  // Display Control 1 (W)
 
@@ -91,6 +93,11 @@ pub const MAX_BG_HEIGHT: usize = 16 * 64;
 pub const MAX_TV_WIDTH: usize  = 512;
 pub const MAX_TV_HEIGHT: usize = 448;
 
+pub const PPU1_VERSION: u8 = 1;
+pub const PPU2_VERSION: u8 = 3;
+
+pub const OAM_LOW_TABLE_SIZE: usize = 0x200;
+
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum TileSize {
@@ -154,14 +161,56 @@ pub enum CGRamDataReadFlipflop{
 }
 
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CounterLatchReadFlipflop{
+    FirstAccess, // Lower 8 bits
+    SecondAccess, // Upper 1 bit
+}
+
+impl CounterLatchReadFlipflop {
+    fn toggle(&mut self) {
+        *self = match self {
+            Self::FirstAccess => Self::SecondAccess,
+            Self::SecondAccess => Self::FirstAccess,
+        };
+    }
+}
+
+
 pub struct PPURegisters {
     data: [u8; 64],
     vram: [u16; 0x8000],
     cgram: [u16; 256],
+    /// 512 bytes low table and 32 bytes high table
+    oam: [u8; 544],
     pub vblank_nmi: bool,
     pub h_count: u16,
     pub v_count: u16,
-    cgram_data_read_flipflop: CGRamDataReadFlipflop
+    cgram_data_read_flipflop: CGRamDataReadFlipflop,
+    /// H/V counters latched either by SLHV or by the external latch pin
+    pub h_count_latch: u16,
+    pub v_count_latch: u16,
+    pub is_counter_latched: bool,
+    h_count_latch_flipflop: CounterLatchReadFlipflop,
+    v_count_latch_flipflop: CounterLatchReadFlipflop,
+    /// Wired to WRIO bit 7
+    external_latch_pin: bool,
+    /// Open bus latches of each PPU chip, updated by reads of their registers
+    pub ppu1_mdr: u8,
+    pub ppu2_mdr: u8,
+    /// When set, VRAM and CGRAM accesses through the ports are recorded here (used by the debugger)
+    pub access_log: Option<Vec<MemoryAccess>>,
+    /// BG1-BG4 (H, V) scroll, 10 bits each
+    bg_scroll: [(u16, u16); 4],
+    /// Last byte written to any BGnHOFS/BGnVOFS, shared by all of them
+    bg_scroll_latch: u8,
+    /// Internal OAM byte address (10 bits), reloaded from OAMADDL/H
+    pub oam_address: u16,
+    /// Even low table bytes wait here until the odd byte is written
+    oam_write_latch: u8,
+    /// STAT77 flags: more than 32 sprites or 34 sprite tiles on a line
+    pub is_obj_range_over: bool,
+    pub is_obj_time_over: bool,
 }
 
 impl PPURegisters {
@@ -170,10 +219,26 @@ impl PPURegisters {
             data: [0x00; 64],
             vram: [0; 0x8000],
             cgram: [0; 256],
+            oam: [0; 544],
             vblank_nmi: false,
             h_count: 0,
             v_count: 0,
             cgram_data_read_flipflop: CGRamDataReadFlipflop::FirstAccess,
+            h_count_latch: 0,
+            v_count_latch: 0,
+            is_counter_latched: false,
+            h_count_latch_flipflop: CounterLatchReadFlipflop::FirstAccess,
+            v_count_latch_flipflop: CounterLatchReadFlipflop::FirstAccess,
+            external_latch_pin: true,
+            ppu1_mdr: 0x00,
+            ppu2_mdr: 0x00,
+            access_log: None,
+            bg_scroll: [(0, 0); 4],
+            bg_scroll_latch: 0x00,
+            oam_address: 0,
+            oam_write_latch: 0x00,
+            is_obj_range_over: false,
+            is_obj_time_over: false,
         }
     }
 
@@ -189,6 +254,23 @@ impl PPURegisters {
         &self.cgram
     }
 
+    pub fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
+    /// Direct access for tools, bypasses the ports and their address registers
+    pub fn vram_mut(&mut self) -> &mut [u16] {
+        &mut self.vram
+    }
+
+    pub fn cgram_mut(&mut self) -> &mut [u16] {
+        &mut self.cgram
+    }
+
+    pub fn oam_mut(&mut self) -> &mut [u8] {
+        &mut self.oam
+    }
+
     fn _read(&self, address: u16) -> u8 {
         match address {
             0x2100..=0x213F => self.data[(address as usize) - 0x2100],
@@ -202,22 +284,84 @@ impl PPURegisters {
         }
     }
 
-    pub fn read_external(&self, address: u16) -> u8 {
-        self._read(address)
+    /// Write-only registers that reads return the PPU1 open bus from,
+    /// the rest of them return the CPU open bus instead
+    fn is_ppu1_open_bus_register(address: u16) -> bool {
+        matches!(
+            address,
+            OAMDATA | BGMODE | MOSAIC |
+            BG2SC | BG3SC | BG4SC |
+            BG4VOFS | VMAIN | VMADDL |
+            VMDATAL | VMDATAH | M7SEL |
+            W34SEL | WOBJSEL | WH0 |
+            WH2 | WH3 | WBGLOG
+        )
     }
 
-    pub fn read(&mut self, address: u16) -> u8 {
+    pub fn read_external(&self, address: u16, cpu_mdr: u8) -> u8 {
+        match address {
+            MPYL | MPYM | MPYH | RDOAM | RDVRAML | RDVRAMH => self._read(address),
+            STAT77 => self.read_stat77(),
+            RDCGRAM => self._read(address),
+            OPHCT => self.read_counter_latch(self.h_count_latch, self.h_count_latch_flipflop),
+            OPVCT => self.read_counter_latch(self.v_count_latch, self.v_count_latch_flipflop),
+            STAT78 => self.read_stat78(),
+            _ if PPURegisters::is_ppu1_open_bus_register(address) => self.ppu1_mdr,
+            _ => cpu_mdr,
+        }
+    }
+
+    pub fn read(&mut self, address: u16, cpu_mdr: u8) -> u8 {
         let result = self._read(address);
+        match address {
+            RDVRAML | RDVRAMH => self.record_access(
+                MemorySpace::VRAM,
+                AccessKind::Read,
+                self.vram_byte_address(address == RDVRAMH),
+                result,
+            ),
+            _ => {},
+        };
         match address {
             VMDATAH | RDVRAMH => self.handle_vram_addr_auto_increment(Some(result), None),
             VMDATAL | RDVRAML => self.handle_vram_addr_auto_increment(None, Some(result)),
             RDCGRAM => {
-                let value = self.get_rdcgram();
+                let is_high_byte = self.cgram_data_read_flipflop == CGRamDataReadFlipflop::SecondAccess;
+                let mut value = self.get_rdcgram();
+                if is_high_byte {
+                    // Colors are 15 bits wide, so bit 7 of the high byte is open bus
+                    value = (value & 0x7F) | (self.ppu2_mdr & 0x80);
+                }
                 self._write(RDCGRAM, value);
             },
+            RDOAM => {
+                let value = self.oam[self.oam_table_index()];
+                self._write(RDOAM, value);
+                self.increment_oam_address();
+            },
+            // Software latching only works while the external latch pin is high
+            SLHV if self.external_latch_pin => self.latch_counters(),
+            _ => {},
+        };
+        let result = self.read_external(address, cpu_mdr);
+        match address {
+            OPHCT => self.h_count_latch_flipflop.toggle(),
+            OPVCT => self.v_count_latch_flipflop.toggle(),
+            STAT78 => {
+                self.h_count_latch_flipflop = CounterLatchReadFlipflop::FirstAccess;
+                self.v_count_latch_flipflop = CounterLatchReadFlipflop::FirstAccess;
+                if self.external_latch_pin {
+                    self.is_counter_latched = false;
+                }
+            },
+            _ => {},
+        };
+        match address {
+            MPYL | MPYM | MPYH | RDOAM | RDVRAML | RDVRAMH | STAT77 => self.ppu1_mdr = result,
+            RDCGRAM | OPHCT | OPVCT | STAT78 => self.ppu2_mdr = result,
             _ => {},
         };
-        self._read(address)
+        result
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
@@ -234,16 +378,217 @@ impl PPURegisters {
                 self._write(address, value);
             },
             RDVRAML | RDVRAMH => {},
+            OAMADDL | OAMADDH => {
+                self._write(address, value);
+                self.reload_oam_address();
+            },
+            OAMDATA => {
+                self._write(address, value);
+                self.write_oam(value);
+            },
             CGADD => {
                 self._write(address, value);
                 self.cgram_data_read_flipflop = CGRamDataReadFlipflop::FirstAccess;
             },
             CGDATA => self.write_cgram(value),
+            BG1HOFS..=BG4VOFS => {
+                self._write(address, value);
+                self.write_bg_scroll(address, value);
+            },
             _ => self._write(address, value),
         };
     }
 
+    /// The scroll registers are written twice, low byte first. Writes share one latch,
+    /// and horizontal scrolls also keep bits 0-2 of the previous value's high byte.
+    fn write_bg_scroll(&mut self, address: u16, value: u8) {
+        let index = ((address - BG1HOFS) / 2) as usize;
+        let latch = self.bg_scroll_latch as u16;
+        let (h_scroll, v_scroll) = &mut self.bg_scroll[index];
+        // The full 16 bits are kept so the next horizontal write still sees the previous byte
+        if (address - BG1HOFS).is_multiple_of(2) {
+            *h_scroll = ((value as u16) << 8) | (latch & !7) | ((*h_scroll >> 8) & 7);
+        } else {
+            *v_scroll = ((value as u16) << 8) | latch;
+        }
+        self.bg_scroll_latch = value;
+    }
+
+    /// (H, V) scroll of a background
+    pub fn get_bg_scroll(&self, background: Background) -> (u16, u16) {
+        let (h_scroll, v_scroll) = self.bg_scroll[background as usize];
+        (h_scroll & 0x3FF, v_scroll & 0x3FF)
+    }
+
+    /// BG Screen Mode (0..7)
+    pub fn get_bg_mode(&self) -> u8 {
+        self._read(BGMODE) & 0b111
+    }
+
+    /// Mode 1 BG3 priority bit, puts BG3 high priority tiles in front of everything
+    pub fn is_bg3_priority_enabled(&self) -> bool {
+        self._read(BGMODE) & 0b1000 != 0
+    }
+
+    pub fn is_bg_on_main_screen(&self, background: Background) -> bool {
+        self._read(TM) & (1 << background as u8) != 0
+    }
+
+    pub fn is_obj_on_main_screen(&self) -> bool {
+        self._read(TM) & 0x10 != 0
+    }
+
+    pub fn is_forced_blank(&self) -> bool {
+        self._read(INIDISP) & 0x80 != 0
+    }
+
+    /// Master brightness, 0 (black) to 15
+    pub fn get_brightness(&self) -> u8 {
+        self._read(INIDISP) & 0x0F
+    }
+
+    pub fn latch_counters(&mut self) {
+        self.h_count_latch = self.h_count;
+        self.v_count_latch = self.v_count;
+        self.is_counter_latched = true;
+    }
+
+    /// WRIO bit 7 is wired to the external latch pin, a 1 to 0 transition latches the H/V counters
+    pub fn set_external_latch_pin(&mut self, value: bool) {
+        if self.external_latch_pin && !value {
+            self.latch_counters();
+        }
+        self.external_latch_pin = value;
+    }
+
+    /// OAMADDL/H hold a word address, bit 7 of OAMADDH enables priority rotation
+    fn get_oam_reload_address(&self) -> u16 {
+        (((self._read(OAMADDH) as u16) & 1) << 8) | (self._read(OAMADDL) as u16)
+    }
+
+    /// Also happens at the start of V-Blank, unless in forced blank
+    pub fn reload_oam_address(&mut self) {
+        self.oam_address = self.get_oam_reload_address() << 1;
+    }
+
+    fn increment_oam_address(&mut self) {
+        self.oam_address = (self.oam_address + 1) & 0x3FF;
+    }
+
+    /// Addresses past the low table mirror the 32 bytes of the high table
+    fn oam_table_index(&self) -> usize {
+        match self.oam_address as usize {
+            address if address < OAM_LOW_TABLE_SIZE => address,
+            address => OAM_LOW_TABLE_SIZE + (address & 0x1F),
+        }
+    }
+
+    /// Low table writes are buffered: the even byte is latched and both bytes get written
+    /// together with the odd one. High table writes go straight through.
+    fn write_oam(&mut self, value: u8) {
+        let index = self.oam_table_index();
+        if index >= OAM_LOW_TABLE_SIZE {
+            self.oam[index] = value;
+        } else if index & 1 == 0 {
+            self.oam_write_latch = value;
+        } else {
+            self.oam[index - 1] = self.oam_write_latch;
+            self.oam[index] = value;
+        }
+        self.increment_oam_address();
+    }
+
+    /// First sprite of the priority order: sprite 0, or with priority rotation the one
+    /// OAMADD points at
+    pub fn get_first_obj(&self) -> usize {
+        if self._read(OAMADDH) & 0x80 == 0 {
+            return 0;
+        }
+        ((self.get_oam_reload_address() >> 1) & 0x7F) as usize
+    }
+
+    ///  7-5  OBJ Size Selection  (0-5, see below) (6-7=Reserved)
+    ///  4-3  Gap between OBJ 0FFh and 100h (0=None) (4K-word steps) (8K-byte steps)
+    ///  2-0  Base Address for OBJ Tiles 000h..0FFh  (8K-word steps) (16K-byte steps)
+    /// Returns the (width, height) of small and large sprites
+    pub fn get_obj_sizes(&self) -> ((usize, usize), (usize, usize)) {
+        match self._read(OBSEL) >> 5 {
+            0 => ((8, 8), (16, 16)),
+            1 => ((8, 8), (32, 32)),
+            2 => ((8, 8), (64, 64)),
+            3 => ((16, 16), (32, 32)),
+            4 => ((16, 16), (64, 64)),
+            5 => ((32, 32), (64, 64)),
+            6 => ((16, 32), (32, 64)),
+            7 => ((16, 32), (32, 32)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// VRAM word address of OBJ tiles 000h..0FFh
+    pub fn get_obj_name_base_address(&self) -> u16 {
+        ((self._read(OBSEL) as u16) & 0b111) << 13
+    }
+
+    /// VRAM word address of OBJ tiles 100h..1FFh
+    pub fn get_obj_name_select_address(&self) -> u16 {
+        let gap = ((((self._read(OBSEL) >> 3) & 0b11) as u16) + 1) << 12;
+        self.get_obj_name_base_address().wrapping_add(gap) & 0x7FFF
+    }
+
+    fn read_counter_latch(&self, counter: u16, flipflop: CounterLatchReadFlipflop) -> u8 {
+        match flipflop {
+            CounterLatchReadFlipflop::FirstAccess => counter as u8,
+            // Bits 1-7 are open bus
+            CounterLatchReadFlipflop::SecondAccess => (self.ppu2_mdr & 0xFE) | ((counter >> 8) as u8 & 1),
+        }
+    }
+
+    ///  7    Time Over  (0=Okay, 1=More than 8x34 OBJ pixels per scanline)
+    ///  6    Range Over (0=Okay, 1=More than 32 OBJs per scanline)
+    ///  5    Master/Slave Mode (PPU1.Pin25) (0=Normal=Master)
+    ///  4    Not used (PPU1 open bus)
+    ///  3-0  5C77 Version Number
+    fn read_stat77(&self) -> u8 {
+        ((self.is_obj_time_over as u8) << 7) |
+        ((self.is_obj_range_over as u8) << 6) |
+        (self.ppu1_mdr & 0x10) |
+        PPU1_VERSION
+    }
+
+    ///  7    Current Interlace-Frame (0=1st, 1=2nd Frame)
+    ///  6    H/V-Counter/Lightgun/Joypad2 Latch Flag (0=No, 1=New Data Latched)
+    ///  5    Not used (PPU2 open bus)
+    ///  4    Frame Rate (PPU2.Pin30)  (0=NTSC/60Hz, 1=PAL/50Hz)
+    ///  3-0  5C78 Version Number
+    fn read_stat78(&self) -> u8 {
+        // The flag always reads as set while the external latch pin is low
+        let latch_flag = self.is_counter_latched || !self.external_latch_pin;
+        ((latch_flag as u8) << 6) | (self.ppu2_mdr & 0x20) | PPU2_VERSION
+    }
+
+    fn record_access(&mut self, space: MemorySpace, kind: AccessKind, address: u32, value: u8) {
+        if let Some(access_log) = &mut self.access_log {
+            access_log.push(MemoryAccess {space, kind, address, value});
+        }
+    }
+
+    fn vram_byte_address(&self, is_high_byte: bool) -> u32 {
+        (((self.get_current_vram_address() & 0x7FFF) as u32) << 1) | (is_high_byte as u32)
+    }
+
+    fn cgram_byte_address(&self) -> u32 {
+        let is_high_byte = self.cgram_data_read_flipflop == CGRamDataReadFlipflop::SecondAccess;
+        ((self.get_cgram_index() as u32) << 1) | (is_high_byte as u32)
+    }
+
     fn handle_write_vram(&mut self, byte_hi: Option<u8>, byte_lo: Option<u8>) {
+        if let Some(byte) = byte_hi {
+            self.record_access(MemorySpace::VRAM, AccessKind::Write, self.vram_byte_address(true), byte);
+        }
+        if let Some(byte) = byte_lo {
+            self.record_access(MemorySpace::VRAM, AccessKind::Write, self.vram_byte_address(false), byte);
+        }
         let address = (self.get_current_vram_address() & 0x7FFF) as usize;
         let current_word = self.vram[address];
         if let Some(byte) = byte_hi {
@@ -457,6 +802,7 @@ impl PPURegisters {
             CGRamDataReadFlipflop::FirstAccess => self.cgram[cgram_index] as u8,
             CGRamDataReadFlipflop::SecondAccess => (self.cgram[cgram_index] >> 8) as u8,
         };
+        self.record_access(MemorySpace::CGRAM, AccessKind::Read, self.cgram_byte_address(), value);
         self.handle_cgram_flipflop();
         value
     }
@@ -466,6 +812,7 @@ impl PPURegisters {
     }
 
     fn write_cgram(&mut self, data: u8) {
+        self.record_access(MemorySpace::CGRAM, AccessKind::Write, self.cgram_byte_address(), data);
         let cgram_index = self.get_cgram_index() as usize;
         match self.cgram_data_read_flipflop {
             CGRamDataReadFlipflop::FirstAccess => {
@@ -501,6 +848,82 @@ mod ppu_registers_test {
         assert_eq!(registers.get_bg_tile_size(Background::Bg1), TileSize::P16x16);
     }
 
+    #[test]
+    fn test_oam_ports() {
+        let mut registers = PPURegisters::new();
+        registers.write(OAMADDL, 0x01);
+        registers.write(OAMADDH, 0x00);
+        assert_eq!(registers.oam_address, 0x002);
+        // The even byte waits for the odd one
+        registers.write(OAMDATA, 0xAA);
+        assert_eq!(registers.oam()[2], 0x00);
+        registers.write(OAMDATA, 0xBB);
+        assert_eq!(&registers.oam()[2..4], &[0xAA, 0xBB]);
+        // A lone odd byte writes the latched even byte too
+        registers.write(OAMDATA, 0xCC);
+        registers.write(OAMADDL, 0x02);
+        registers.oam_address += 1;
+        registers.write(OAMDATA, 0xDD);
+        assert_eq!(&registers.oam()[4..6], &[0xCC, 0xDD]);
+        // The high table is written right away and mirrored up to $3FF
+        registers.write(OAMADDL, 0x00);
+        registers.write(OAMADDH, 0x01);
+        registers.write(OAMDATA, 0x11);
+        assert_eq!(registers.oam()[0x200], 0x11);
+        registers.oam_address = 0x3E1;
+        registers.write(OAMDATA, 0x22);
+        assert_eq!(registers.oam()[0x201], 0x22);
+        assert_eq!(registers.oam_address, 0x3E2);
+        // Reads increment the address
+        registers.write(OAMADDL, 0x01);
+        registers.write(OAMADDH, 0x00);
+        assert_eq!(registers.read(RDOAM, 0x00), 0xAA);
+        assert_eq!(registers.read(RDOAM, 0x00), 0xBB);
+        assert_eq!(registers.oam_address, 0x004);
+        registers.oam_address = 0x3E0;
+        assert_eq!(registers.read(RDOAM, 0x00), 0x11);
+    }
+
+    #[test]
+    fn test_priority_rotation() {
+        let mut registers = PPURegisters::new();
+        registers.write(OAMADDL, 0x0A);
+        assert_eq!(registers.get_first_obj(), 0);
+        // Bit 8 of the address doesn't matter: word $10A is sprite 5
+        registers.write(OAMADDH, 0x81);
+        assert_eq!(registers.get_first_obj(), 0x05);
+    }
+
+    #[test]
+    fn test_stat77() {
+        let mut registers = PPURegisters::new();
+        registers.ppu1_mdr = 0xFF;
+        assert_eq!(registers.read(STAT77, 0x00), 0x10 | PPU1_VERSION);
+        registers.is_obj_range_over = true;
+        assert_eq!(registers.read(STAT77, 0x00), 0x50 | PPU1_VERSION);
+        registers.is_obj_time_over = true;
+        assert_eq!(registers.read(STAT77, 0x00), 0xD0 | PPU1_VERSION);
+    }
+
+    #[test]
+    fn test_bg_scroll_latch() {
+        let mut registers = PPURegisters::new();
+        registers.write(BG1HOFS, 0x34);
+        registers.write(BG1HOFS, 0x01);
+        registers.write(BG1VOFS, 0xFF);
+        registers.write(BG1VOFS, 0x03);
+        assert_eq!(registers.get_bg_scroll(Background::Bg1), (0x0134, 0x03FF));
+        // The low 3 bits of the horizontal scroll come from its previous high byte
+        registers.write(BG2HOFS, 0xFF);
+        registers.write(BG2HOFS, 0x02);
+        registers.write(BG2HOFS, 0x00);
+        assert_eq!(registers.get_bg_scroll(Background::Bg2), (0x0002, 0x0000));
+        // The latch is shared by every register
+        registers.write(BG3HOFS, 0x12);
+        registers.write(BG4VOFS, 0x00);
+        assert_eq!(registers.get_bg_scroll(Background::Bg4), (0x0000, 0x0012));
+    }
+
     #[test]
     fn test_get_bg_size() {
         let mut registers = PPURegisters::new();
@@ -593,16 +1016,16 @@ mod ppu_registers_test {
         registers.write(VMDATAH, 0xAB);
         registers.write(VMDATAL, 0xCD);
         assert_eq!(registers.vram[0x1234], 0xABCD);
-        assert_eq!(registers.read(RDVRAMH), 0xAB);
-        assert_eq!(registers.read(RDVRAML), 0xCD);
+        assert_eq!(registers.read(RDVRAMH, 0x00), 0xAB);
+        assert_eq!(registers.read(RDVRAML, 0x00), 0xCD);
 
         registers.write(VMADDH, 0x7F);
         registers.write(VMADDL, 0xFF);
         registers.write(VMDATAH, 0xAB);
         registers.write(VMDATAL, 0xCD);
         assert_eq!(registers.vram[0x7FFF], 0xABCD);
-        assert_eq!(registers.read(RDVRAMH), 0xAB);
-        assert_eq!(registers.read(RDVRAML), 0xCD);
+        assert_eq!(registers.read(RDVRAMH, 0x00), 0xAB);
+        assert_eq!(registers.read(RDVRAML, 0x00), 0xCD);
     }
 
     #[test]
@@ -635,16 +1058,16 @@ mod ppu_registers_test {
         // Increment when low bit is read from
         registers.write(VMAIN, 0x00);
 
-        registers.read(VMDATAH);
+        registers.read(VMDATAH, 0x00);
         assert_eq!(registers.get_current_vram_address(), 0x0000);
-        registers.read(VMDATAL);
+        registers.read(VMDATAL, 0x00);
         assert_eq!(registers.get_current_vram_address(), 0x0001);
 
         // Increment when hi bit is read from
         registers.write(VMAIN, 0b1000_0000);
-        registers.read(VMDATAH);
+        registers.read(VMDATAH, 0x00);
         assert_eq!(registers.get_current_vram_address(), 0x0002);
-        registers.read(VMDATAL);
+        registers.read(VMDATAL, 0x00);
         assert_eq!(registers.get_current_vram_address(), 0x0002);
 
         // Increment amounts
@@ -808,14 +1231,14 @@ mod ppu_registers_test {
         registers.cgram_data_read_flipflop = CGRamDataReadFlipflop::FirstAccess;
         registers.cgram[0x10] = 0x1234;
         registers._write(CGADD, 0x10);
-        let first_access_value = registers.read(RDCGRAM);
+        let first_access_value = registers.read(RDCGRAM, 0x00);
         assert_eq!(first_access_value, 0x34);
         assert_eq!(
             registers.cgram_data_read_flipflop,
             CGRamDataReadFlipflop::SecondAccess,
         );
 
-        let second_access_value = registers.read(RDCGRAM);
+        let second_access_value = registers.read(RDCGRAM, 0x00);
         assert_eq!(registers._read(CGADD), 0x11);
         assert_eq!(second_access_value, 0x12);
         assert_eq!(
@@ -845,4 +1268,81 @@ mod ppu_registers_test {
             CGRamDataReadFlipflop::FirstAccess,
         );
     }
+
+    #[test]
+    fn test_open_bus() {
+        let mut registers = PPURegisters::new();
+        registers.write(VMADDL, 0x00);
+        registers.write(VMADDH, 0x00);
+        registers.write(VMDATAL, 0xCD);
+        // Write-only registers return either the CPU or the PPU1 open bus
+        assert_eq!(registers.read(INIDISP, 0xAA), 0xAA);
+        assert_eq!(registers.read(BGMODE, 0xAA), 0x00);
+        assert_eq!(registers.read(RDVRAML, 0xAA), 0xCD);
+        assert_eq!(registers.ppu1_mdr, 0xCD);
+        assert_eq!(registers.read(BGMODE, 0xAA), 0xCD);
+        assert_eq!(registers.read_external(VMDATAL, 0xAA), 0xCD);
+        assert_eq!(registers.read(SLHV, 0xAA), 0xAA);
+
+        // Bit 7 of the CGRAM high byte comes from the PPU2 open bus
+        registers.cgram[0x00] = 0x7FFF;
+        registers.ppu2_mdr = 0x80;
+        registers._write(CGADD, 0x00);
+        assert_eq!(registers.read(RDCGRAM, 0x00), 0xFF);
+        assert_eq!(registers.read(RDCGRAM, 0x00), 0xFF);
+        registers._write(CGADD, 0x00);
+        registers.read(RDCGRAM, 0x00);
+        registers.ppu2_mdr = 0x00;
+        assert_eq!(registers.read(RDCGRAM, 0x00), 0x7F);
+        assert_eq!(registers.ppu2_mdr, 0x7F);
+    }
+
+    #[test]
+    fn test_software_counter_latch() {
+        let mut registers = PPURegisters::new();
+        registers.h_count = 0x123;
+        registers.v_count = 0x0AB;
+        assert_eq!(registers.read(SLHV, 0xAA), 0xAA);
+        registers.h_count = 0;
+        registers.v_count = 0;
+        assert_eq!(registers.read(STAT78, 0x00) & 0x40, 0x40);
+        // The flag is cleared after reading STAT78
+        assert_eq!(registers.read(STAT78, 0x00) & 0x40, 0x00);
+
+        assert_eq!(registers.read(OPHCT, 0x00), 0x23);
+        // Bits 1-7 of the second access come from PPU2 open bus
+        registers.ppu2_mdr = 0xFE;
+        assert_eq!(registers.read(OPHCT, 0x00), 0xFF);
+        assert_eq!(registers.read(OPHCT, 0x00), 0x23);
+        assert_eq!(registers.read(OPVCT, 0x00), 0xAB);
+        // STAT78 resets the flip-flops
+        registers.read(STAT78, 0x00);
+        assert_eq!(registers.read(OPVCT, 0x00), 0xAB);
+        assert_eq!(registers.read(OPVCT, 0x00), 0xAA);
+    }
+
+    #[test]
+    fn test_external_counter_latch() {
+        let mut registers = PPURegisters::new();
+        registers.h_count = 100;
+        registers.v_count = 200;
+        registers.set_external_latch_pin(true);
+        assert!(!registers.is_counter_latched);
+        registers.set_external_latch_pin(false);
+        assert!(registers.is_counter_latched);
+        assert_eq!(registers.h_count_latch, 100);
+        assert_eq!(registers.v_count_latch, 200);
+
+        // SLHV doesn't latch while the pin is low
+        registers.h_count = 10;
+        registers.read(SLHV, 0x00);
+        assert_eq!(registers.h_count_latch, 100);
+        // And the latch flag stays set
+        registers.read(STAT78, 0x00);
+        assert_eq!(registers.read(STAT78, 0x00) & 0x40, 0x40);
+
+        registers.set_external_latch_pin(true);
+        registers.read(STAT78, 0x00);
+        assert_eq!(registers.read(STAT78, 0x00) & 0x40, 0x00);
+    }
 }
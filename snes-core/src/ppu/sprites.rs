@@ -0,0 +1,303 @@
+// Sprite (OBJ) evaluation: each line, the range pass picks up to 32 sprites in priority
+// order, then the time pass fetches up to 34 8-pixel tiles of them into a line buffer.
+use super::background::char_pixel;
+use super::registers::{PPURegisters, OAM_LOW_TABLE_SIZE};
+
+pub const OBJ_COUNT: usize = 128;
+pub const MAX_OBJS_PER_LINE: usize = 32;
+pub const MAX_OBJ_TILES_PER_LINE: usize = 34;
+pub const OBJ_LINE_WIDTH: usize = 256;
+/// A sprite and its (width, height)
+type SizedObj = (ObjAttributes, (usize, usize));
+/// Sprites use the second half of CGRAM
+const OBJ_CGRAM_BASE: usize = 128;
+
+/// One sprite, from its 4 low table bytes and 2 high table bits
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjAttributes {
+    /// 9 bits signed, -256..255
+    pub x: i16,
+    pub y: u8,
+    /// 9 bits, bit 8 selects the second name table
+    pub tile: u16,
+    pub palette: u8,
+    pub priority: u8,
+    pub h_flip: bool,
+    pub v_flip: bool,
+    pub is_large: bool,
+}
+
+impl ObjAttributes {
+    pub fn from_oam(oam: &[u8], index: usize) -> Self {
+        let low = &oam[index * 4..index * 4 + 4];
+        let high = (oam[OAM_LOW_TABLE_SIZE + index / 4] >> ((index % 4) * 2)) & 0b11;
+        // Sign extend the 9 bit X coordinate
+        let x = (((high as u16 & 1) << 8) | low[0] as u16) as i16;
+        Self {
+            x: if x & 0x100 != 0 { x - 0x200 } else { x },
+            y: low[1],
+            tile: ((low[3] as u16 & 1) << 8) | low[2] as u16,
+            palette: (low[3] >> 1) & 0b111,
+            priority: (low[3] >> 4) & 0b11,
+            h_flip: low[3] & 0x40 != 0,
+            v_flip: low[3] & 0x80 != 0,
+            is_large: high & 0b10 != 0,
+        }
+    }
+
+    /// Row of the sprite shown on `line`, if any. Y wraps around, so sprites near
+    /// the bottom also show at the top.
+    fn row_on_line(&self, line: usize, height: usize) -> Option<usize> {
+        let row = (line as u8).wrapping_sub(self.y) as usize;
+        (row < height).then_some(row)
+    }
+
+    fn is_in_range(&self, line: usize, (width, height): (usize, usize)) -> bool {
+        // X = -256 counts as in range even though it's never visible
+        self.row_on_line(line, height).is_some() && (self.x == -256 || self.x > -(width as i16))
+    }
+}
+
+/// A non-transparent sprite pixel
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjPixel {
+    pub cgram_index: u8,
+    pub priority: u8,
+}
+
+pub struct ObjLine {
+    pub pixels: [Option<ObjPixel>; OBJ_LINE_WIDTH],
+    pub is_range_over: bool,
+    pub is_time_over: bool,
+}
+
+/// VRAM word address of 8x8 character (`column`, `row`) of a sprite. Characters of a
+/// sprite are laid out on a 16x16 grid, wrapping within its name table.
+fn obj_char_address(registers: &PPURegisters, tile: u16, column: usize, row: usize) -> usize {
+    let char_x = ((tile & 0x0F) as usize + column) & 0x0F;
+    let char_y = (((tile >> 4) & 0x0F) as usize + row) & 0x0F;
+    let base_address = match tile & 0x100 {
+        0 => registers.get_obj_name_base_address(),
+        _ => registers.get_obj_name_select_address(),
+    };
+    (base_address as usize + (char_y * 16 + char_x) * 16) & 0x7FFF
+}
+
+/// Sprites in range of `line` in priority order, starting from the first sprite and
+/// stopping at 32
+fn evaluate_range(registers: &PPURegisters, line: usize) -> (Vec<SizedObj>, bool) {
+    let (small_size, large_size) = registers.get_obj_sizes();
+    let first_obj = registers.get_first_obj();
+    let mut objs = Vec::with_capacity(MAX_OBJS_PER_LINE);
+    for i in 0..OBJ_COUNT {
+        let obj = ObjAttributes::from_oam(registers.oam(), (first_obj + i) % OBJ_COUNT);
+        let size = if obj.is_large { large_size } else { small_size };
+        if !obj.is_in_range(line, size) {
+            continue;
+        }
+        if objs.len() == MAX_OBJS_PER_LINE {
+            return (objs, true);
+        }
+        objs.push((obj, size));
+    }
+    (objs, false)
+}
+
+/// Sprite pixels of `line` (0 being the first visible line). Tiles are fetched from the
+/// last sprite in range to the first, so past 34 tiles the highest priority sprites are
+/// the ones that lose theirs.
+pub fn evaluate_line(registers: &PPURegisters, line: usize) -> ObjLine {
+    let (objs, is_range_over) = evaluate_range(registers, line);
+    let mut obj_line = ObjLine {
+        pixels: [None; OBJ_LINE_WIDTH],
+        is_range_over,
+        is_time_over: false,
+    };
+    let mut tile_count = 0;
+    for (obj, (width, height)) in objs.iter().rev() {
+        let Some(mut row) = obj.row_on_line(line, *height) else {
+            continue
+        };
+        if obj.v_flip {
+            row = height - 1 - row;
+        }
+        for tile_column in 0..(width / 8) {
+            let tile_x = obj.x + (tile_column * 8) as i16;
+            if !(-7..OBJ_LINE_WIDTH as i16).contains(&tile_x) {
+                continue;
+            }
+            if tile_count == MAX_OBJ_TILES_PER_LINE {
+                obj_line.is_time_over = true;
+                return obj_line;
+            }
+            tile_count += 1;
+            for i in 0..8 {
+                let screen_x = tile_x + i as i16;
+                if !(0..OBJ_LINE_WIDTH as i16).contains(&screen_x) {
+                    continue;
+                }
+                let mut obj_x = tile_column * 8 + i;
+                if obj.h_flip {
+                    obj_x = width - 1 - obj_x;
+                }
+                let char_address = obj_char_address(registers, obj.tile, obj_x / 8, row / 8);
+                let color = char_pixel(registers.vram(), char_address, 4, obj_x % 8, row % 8);
+                if color == 0 {
+                    continue;
+                }
+                // Later writes come from earlier sprites, which are in front
+                obj_line.pixels[screen_x as usize] = Some(ObjPixel {
+                    cgram_index: (OBJ_CGRAM_BASE + (obj.palette as usize) * 16 + color as usize) as u8,
+                    priority: obj.priority,
+                });
+            }
+        }
+    }
+    obj_line
+}
+
+
+#[cfg(test)]
+mod ppu_sprites_tests {
+    use super::*;
+    use crate::ppu::registers::{OBSEL, OAMADDL, OAMADDH, OAMDATA};
+
+    /// Writes sprite `index` through the OAM port, `high` being its 2 high table bits
+    fn write_obj(registers: &mut PPURegisters, index: usize, bytes: [u8; 4], high: u8) {
+        registers.write(OAMADDL, (index * 2) as u8);
+        registers.write(OAMADDH, 0x00);
+        for byte in bytes {
+            registers.write(OAMDATA, byte);
+        }
+        let high_index = OAM_LOW_TABLE_SIZE + index / 4;
+        let shift = (index % 4) * 2;
+        registers.oam_mut()[high_index] = (registers.oam()[high_index] & !(0b11 << shift)) | (high << shift);
+    }
+
+    /// Character `tile` of the first name table is solid `color` (4bpp)
+    fn write_solid_char(registers: &mut PPURegisters, tile: usize, color: u8) {
+        let address = registers.get_obj_name_base_address() as usize + tile * 16;
+        for pair in 0..2 {
+            let low = if color >> (pair * 2) & 1 == 1 { 0x00FF } else { 0 };
+            let high = if color >> (pair * 2 + 1) & 1 == 1 { 0xFF00 } else { 0 };
+            for row in 0..8 {
+                registers.vram_mut()[address + pair * 8 + row] = low | high;
+            }
+        }
+    }
+
+    /// Moves every sprite below the visible lines
+    fn clear_oam(registers: &mut PPURegisters) {
+        for i in 0..OBJ_COUNT {
+            registers.oam_mut()[i * 4 + 1] = 0xF0;
+        }
+    }
+
+    #[test]
+    fn test_obj_attributes() {
+        let mut registers = PPURegisters::new();
+        write_obj(&mut registers, 5, [0xF0, 0x20, 0x34, 0b1101_0111], 0b11);
+        assert_eq!(ObjAttributes::from_oam(registers.oam(), 5), ObjAttributes {
+            x: -16,
+            y: 0x20,
+            tile: 0x134,
+            palette: 3,
+            priority: 1,
+            h_flip: true,
+            v_flip: true,
+            is_large: true,
+        });
+    }
+
+    #[test]
+    fn test_obj_sizes_and_names() {
+        let mut registers = PPURegisters::new();
+        registers.write(OBSEL, 0b1101_0011);
+        assert_eq!(registers.get_obj_sizes(), ((16, 32), (32, 64)));
+        assert_eq!(registers.get_obj_name_base_address(), 0x6000);
+        // A gap of 3 * 4K words wraps around VRAM
+        assert_eq!(registers.get_obj_name_select_address(), 0x1000);
+        registers.write(OBSEL, 0b0000_0001);
+        assert_eq!(registers.get_obj_name_select_address(), 0x3000);
+        // Characters wrap within a row of the 16x16 grid
+        assert_eq!(obj_char_address(&registers, 0x0FF, 1, 0), 0x2000 + 0xF0 * 16);
+        assert_eq!(obj_char_address(&registers, 0x100, 2, 1), 0x3000 + 0x12 * 16);
+    }
+
+    #[test]
+    fn test_evaluate_line() {
+        let mut registers = PPURegisters::new();
+        clear_oam(&mut registers);
+        write_solid_char(&mut registers, 0x01, 5);
+        write_solid_char(&mut registers, 0x12, 7);
+        // Sprite 0: 16x16 at (-4, 10), palette 2, priority 3
+        write_obj(&mut registers, 0, [0xFC, 10, 0x01, 0b0011_0100], 0b11);
+        // Sprite 1: 8x8 at (0, 10), behind sprite 0
+        write_obj(&mut registers, 1, [0x00, 10, 0x01, 0b0000_0000], 0b00);
+        let line = evaluate_line(&registers, 10);
+        assert_eq!(line.pixels[0], Some(ObjPixel {cgram_index: 128 + 32 + 5, priority: 3}));
+        // The second character of sprite 0 is empty, so sprite 1 shows through
+        assert_eq!(line.pixels[4], Some(ObjPixel {cgram_index: 128 + 5, priority: 0}));
+        assert_eq!(line.pixels[8], None);
+        assert!(!line.is_range_over && !line.is_time_over);
+        // The bottom right character of sprite 0 is tile 0x12
+        let line = evaluate_line(&registers, 18);
+        assert_eq!(line.pixels[3], None);
+        assert_eq!(line.pixels[4].unwrap().cgram_index, 128 + 32 + 7);
+        // Flipped, it's the top left one
+        write_obj(&mut registers, 0, [0xFC, 10, 0x01, 0b1111_0100], 0b11);
+        let line = evaluate_line(&registers, 10);
+        assert_eq!(line.pixels[0].unwrap().cgram_index, 128 + 32 + 7);
+        assert_eq!(evaluate_line(&registers, 9).pixels[0], None);
+    }
+
+    #[test]
+    fn test_range_over_and_priority_rotation() {
+        let mut registers = PPURegisters::new();
+        clear_oam(&mut registers);
+        write_solid_char(&mut registers, 0x01, 1);
+        write_solid_char(&mut registers, 0x02, 2);
+        // 33 sprites at X = 0, sprite 32 being the only one with character 2
+        for i in 0..33 {
+            write_obj(&mut registers, i, [0x00, 0x00, if i == 32 { 0x02 } else { 0x01 }, 0x00], 0b00);
+        }
+        let line = evaluate_line(&registers, 0);
+        assert!(line.is_range_over);
+        assert_eq!(line.pixels[0].unwrap().cgram_index, 128 + 1);
+        // With priority rotation from sprite 32 it's in front, and sprite 31 is dropped instead
+        registers.write(OAMADDL, 64);
+        registers.write(OAMADDH, 0x80);
+        assert_eq!(registers.get_first_obj(), 32);
+        let line = evaluate_line(&registers, 0);
+        assert!(line.is_range_over);
+        assert_eq!(line.pixels[0].unwrap().cgram_index, 128 + 2);
+    }
+
+    #[test]
+    fn test_time_over() {
+        let mut registers = PPURegisters::new();
+        clear_oam(&mut registers);
+        for tile in 0..8 {
+            write_solid_char(&mut registers, tile, 1);
+        }
+        // 64x64 sprites are 8 tiles wide: 5 of them are 40 tiles
+        registers.write(OBSEL, 0b0100_0000);
+        for (i, x) in [0x00, 0x40, 0x60, 0x80, 0xA0].into_iter().enumerate() {
+            write_obj(&mut registers, i, [x, 0x00, 0x00, 0x00], 0b10);
+        }
+        let line = evaluate_line(&registers, 0);
+        assert!(line.is_time_over);
+        assert!(!line.is_range_over);
+        // Sprites 1-4 got their 32 tiles, sprite 0 only its first 2
+        assert!(line.pixels[..16].iter().all(Option::is_some));
+        assert!(line.pixels[16..64].iter().all(Option::is_none));
+        assert!(line.pixels[64..224].iter().all(Option::is_some));
+        // Tiles off screen don't count
+        for i in 0..5 {
+            write_obj(&mut registers, i, [0xE0, 0x00, 0x00, 0x00], 0b10);
+        }
+        let line = evaluate_line(&registers, 0);
+        assert!(!line.is_time_over);
+        assert!(line.pixels[224..].iter().all(Option::is_some));
+    }
+}
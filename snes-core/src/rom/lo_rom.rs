@@ -24,15 +24,25 @@ impl ROM for LoROM {
         load_rom(filename, &mut self.data)
     }
 
-    fn read(&self, address: u32) -> u8 {
+    fn read(&self, address: u32) -> Option<u8> {
         let address = LoROM::adjust_address(address);
-        match self.data.get(address as usize) {
-            Some(byte) => *byte,
-            None => 0x00,
-        }
+        self.data.get(address as usize).copied()
     }
 
     fn write(&mut self, _address: u32, _value: u8) {}
+
+    fn rom_offset(&self, address: u32) -> Option<usize> {
+        let offset = LoROM::adjust_address(address) as usize;
+        (offset < self.data.len()).then_some(offset)
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
 }
 
 impl Default for LoROM {
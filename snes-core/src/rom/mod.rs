@@ -1,5 +1,5 @@
 pub mod lo_rom;
-pub mod special_ram_cart;
+pub mod test_memory;
 
 use std::fs::File;
 use std::io::Read;
@@ -13,6 +13,21 @@ pub fn load_rom(filename: &str, target: &mut Vec<u8>) -> std::io::Result<bool> {
 
 pub trait ROM {
     fn load(&mut self, filename: &str) -> std::io::Result<bool>;
-    fn read(&self, address: u32) -> u8;
+    /// Returns `None` when the cartridge doesn't drive the data bus at that address
+    fn read(&self, address: u32) -> Option<u8>;
     fn write(&mut self, address: u32, value: u8);
+    /// Offset in `data` a CPU address reads from, `None` outside the ROM
+    fn rom_offset(&self, address: u32) -> Option<usize>;
+    /// Raw ROM image, for tools that address it by file offset
+    fn data(&self) -> &[u8];
+    fn data_mut(&mut self) -> &mut [u8];
+    /// Battery-backed save RAM, empty when the cartridge has none or the mapper doesn't map it yet
+    fn sram(&self) -> &[u8] {
+        &[]
+    }
+    fn sram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    /// Brings writable memory back to its power-on contents, save RAM is kept
+    fn reset(&mut self) {}
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! Flat 24-bit memory for CPU test suites, used by `Bus::new_flat`
+//! so every address reads back what was written to it.
+//!
+//! Pages are only allocated once something non-zero is written to them, and
+//! `reset` clears just the bytes written since the last reset.
+use std::collections::HashMap;
+
+use super::ROM;
+
+pub const PAGE_SIZE: usize = 0x1000;
+const ADDRESS_MASK: u32 = 0xFFFFFF;
+
+pub struct TestMemory {
+    pages: HashMap<u32, Box<[u8; PAGE_SIZE]>>,
+    written_addresses: Vec<u32>,
+}
+
+impl TestMemory {
+    pub fn new() -> Self {
+        Self {
+            pages: HashMap::new(),
+            written_addresses: vec![],
+        }
+    }
+
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl ROM for TestMemory {
+    fn load(&mut self, _filename: &str) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    fn read(&self, address: u32) -> Option<u8> {
+        let address = address & ADDRESS_MASK;
+        let value = self.pages.get(&(address / PAGE_SIZE as u32))
+            .map_or(0, |page| page[address as usize % PAGE_SIZE]);
+        Some(value)
+    }
+
+    fn write(&mut self, address: u32, value: u8) {
+        let address = address & ADDRESS_MASK;
+        let page_index = address / PAGE_SIZE as u32;
+        if value == 0 && !self.pages.contains_key(&page_index) {
+            return;
+        }
+        let page = self.pages.entry(page_index).or_insert_with(|| Box::new([0; PAGE_SIZE]));
+        page[address as usize % PAGE_SIZE] = value;
+        self.written_addresses.push(address);
+    }
+
+    fn rom_offset(&self, address: u32) -> Option<usize> {
+        Some((address & ADDRESS_MASK) as usize)
+    }
+
+    /// There's no contiguous image behind the pages
+    fn data(&self) -> &[u8] {
+        &[]
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Zeroes what was written since the last reset, pages stay allocated for the next test
+    fn reset(&mut self) {
+        for address in self.written_addresses.drain(..) {
+            if let Some(page) = self.pages.get_mut(&(address / PAGE_SIZE as u32)) {
+                page[address as usize % PAGE_SIZE] = 0;
+            }
+        }
+    }
+}
+
+impl Default for TestMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod rom_test_memory_tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_pages() {
+        let mut memory = TestMemory::new();
+        assert_eq!(memory.read(0xFFFFFF), Some(0));
+        memory.write(0x123456, 0x00);
+        assert_eq!(memory.allocated_pages(), 0);
+        memory.write(0x123456, 0xAB);
+        memory.write(0x123457, 0xCD);
+        memory.write(0xFF0000, 0x01);
+        assert_eq!(memory.allocated_pages(), 2);
+        assert_eq!(memory.read(0x123456), Some(0xAB));
+        // Addresses wrap at 24 bits
+        assert_eq!(memory.read(0x01123457), Some(0xCD));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut memory = TestMemory::new();
+        memory.write(0x001000, 0x11);
+        memory.write(0x7E0000, 0x22);
+        memory.reset();
+        assert_eq!(memory.read(0x001000), Some(0));
+        assert_eq!(memory.read(0x7E0000), Some(0));
+        assert_eq!(memory.allocated_pages(), 2);
+        memory.write(0x001001, 0x33);
+        memory.reset();
+        assert_eq!(memory.read(0x001001), Some(0));
+    }
+}
@@ -0,0 +1,164 @@
+//! ca65/ld65 debug info files, written by `ld65 --dbgfile`.
+//!
+//! Each line is a record type, a tab, and comma separated `key=value` pairs like
+//! `line id=3,file=0,line=12,span=4+5`. Line records point to spans, spans point
+//! to segments, and a span's address is its segment's start plus its offset.
+use std::collections::HashMap;
+
+use super::SymbolTable;
+
+/// Line records of this type come from macro expansions
+const LINE_TYPE_MACRO: u32 = 2;
+
+fn split_fields(text: &str) -> HashMap<&str, &str> {
+    let mut fields = HashMap::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some((key, value_start)) = rest.split_once('=') else {
+            break
+        };
+        let (value, next) = match value_start.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted[end..].trim_start_matches('"'))
+            },
+            None => {
+                let end = value_start.find(',').unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            },
+        };
+        fields.insert(key.trim(), value);
+        rest = next.trim_start_matches(',');
+    }
+    fields
+}
+
+fn parse_number(text: &str) -> Result<u32, String> {
+    let result = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    result.map_err(|_| format!("Invalid number '{}'", text))
+}
+
+fn field(fields: &HashMap<&str, &str>, key: &str) -> Result<u32, String> {
+    match fields.get(key) {
+        Some(value) => parse_number(value),
+        None => Err(format!("Missing field '{}'", key)),
+    }
+}
+
+/// Records needed to resolve addresses, keyed by id
+#[derive(Default)]
+struct Records {
+    files: HashMap<u32, String>,
+    segments: HashMap<u32, u32>,
+    spans: HashMap<u32, (u32, u32)>,
+    lines: Vec<(u32, u32, String)>,
+    labels: Vec<(String, u32)>,
+}
+
+impl Records {
+    fn add(&mut self, kind: &str, fields: &HashMap<&str, &str>) -> Result<(), String> {
+        let name = || fields.get("name").ok_or_else(|| String::from("Missing field 'name'"));
+        match kind {
+            "file" => {
+                self.files.insert(field(fields, "id")?, name()?.to_string());
+            },
+            "seg" => {
+                self.segments.insert(field(fields, "id")?, field(fields, "start")?);
+            },
+            "span" => {
+                let location = (field(fields, "seg")?, field(fields, "start")?);
+                self.spans.insert(field(fields, "id")?, location);
+            },
+            "line" => {
+                let line_type = fields.get("type").map(|t| parse_number(t)).transpose()?;
+                if line_type != Some(LINE_TYPE_MACRO) {
+                    if let Some(span_ids) = fields.get("span") {
+                        self.lines.push((field(fields, "file")?, field(fields, "line")?, span_ids.to_string()));
+                    }
+                }
+            },
+            // Skip constants (type=equ) and imports, which have no value
+            "sym" if fields.get("type") == Some(&"lab") && fields.contains_key("val") => {
+                self.labels.push((name()?.to_string(), field(fields, "val")?));
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn span_address(&self, span_id: u32) -> Option<u32> {
+        let (segment, offset) = self.spans.get(&span_id)?;
+        Some(self.segments.get(segment)? + offset)
+    }
+}
+
+pub fn parse_dbg(text: &str) -> Result<SymbolTable, String> {
+    let mut records = Records::default();
+    for (number, record) in text.lines().enumerate() {
+        if let Some((kind, rest)) = record.split_once(char::is_whitespace) {
+            records.add(kind, &split_fields(rest))
+                .map_err(|err| format!("Line {}: {}", number + 1, err))?;
+        }
+    }
+
+    let mut table = SymbolTable::new();
+    for (name, address) in &records.labels {
+        table.add_label(*address, name);
+    }
+    let mut file_ids: Vec<_> = records.files.keys().copied().collect();
+    file_ids.sort();
+    let file_indices: HashMap<u32, usize> = file_ids.iter()
+        .map(|id| (*id, table.add_file(&records.files[id])))
+        .collect();
+    for (file, line, span_ids) in &records.lines {
+        let Some(file_index) = file_indices.get(file) else {
+            continue
+        };
+        for span_id in span_ids.split('+') {
+            if let Some(address) = records.span_address(parse_number(span_id)?) {
+                table.add_line(address, *file_index, *line);
+            }
+        }
+    }
+    Ok(table)
+}
+
+
+#[cfg(test)]
+mod symbols_ca65_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dbg() {
+        let text = "\
+version\tmajor=2,minor=0
+file\tid=0,name=\"src/main.s\",size=120,mtime=0x65A1B2C3,mod=0
+file\tid=1,name=\"src/macros, old.inc\",size=20,mtime=0x65A1B2C3,mod=0
+line\tid=0,file=0,line=4,span=0
+line\tid=1,file=0,line=5,span=1+2
+line\tid=2,file=1,line=2,type=2,span=3
+seg\tid=0,name=\"CODE\",start=0x008000,size=0x0010,addrsize=absolute,type=ro,oname=\"game.sfc\",ooffs=0
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=3
+span\tid=2,seg=0,start=8,size=1
+span\tid=3,seg=0,start=5,size=1
+sym\tid=0,name=\"main\",addrsize=absolute,scope=0,def=0,ref=1,val=0x8000,seg=0,type=lab
+sym\tid=1,name=\"SCREEN_WIDTH\",addrsize=zeropage,scope=0,def=1,val=0x100,type=equ
+sym\tid=2,name=\"external\",addrsize=absolute,scope=0,def=2,type=imp
+";
+        let table = parse_dbg(text).unwrap();
+        assert_eq!(table.files, vec!["src/main.s", "src/macros, old.inc"]);
+        assert_eq!(table.address_of("main"), Some(0x008000));
+        assert_eq!(table.address_of("SCREEN_WIDTH"), None);
+        assert_eq!(table.address_of("external"), None);
+        assert_eq!(table.addresses_at_line("/project/src/main.s", 4), vec![0x008000]);
+        assert_eq!(table.addresses_at_line("/project/src/main.s", 5), vec![0x008002, 0x008008]);
+        // Macro expansion lines are skipped
+        assert_eq!(table.line_at(0x008005), None);
+
+        assert!(parse_dbg("seg\tid=0,start=0xZZ").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use super::{ca65, mlb, vice, wla};
+
+/// Extensions tried next to a ROM, in order of preference
+pub const EXTENSIONS: [&str; 4] = ["dbg", "sym", "mlb", "lbl"];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SourceLine {
+    /// Index into `SymbolTable::files`
+    pub file: usize,
+    pub line: u32,
+}
+
+/// Labels and source line information loaded from an assembler's symbol file.
+/// Addresses are 24-bit CPU addresses.
+pub struct SymbolTable {
+    /// Source file paths, as written in the symbol file
+    pub files: Vec<String>,
+    /// First label defined at each address, in the shape the disassembler expects
+    pub labels: HashMap<u32, String>,
+    addresses: HashMap<String, u32>,
+    lines: BTreeMap<u32, SourceLine>,
+}
+
+/// Whether `requested` (usually an absolute path from an editor) names the
+/// symbol file path `path`, which is often relative to the project directory
+fn is_same_file(path: &str, requested: &str) -> bool {
+    let path = path.replace('\\', "/");
+    let requested = requested.replace('\\', "/");
+    let path = path.trim_start_matches("./");
+    requested == path || requested.ends_with(&format!("/{}", path))
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            files: vec![],
+            labels: HashMap::new(),
+            addresses: HashMap::new(),
+            lines: BTreeMap::new(),
+        }
+    }
+
+    /// Loads a ca65 debug info file (`.dbg`), VICE labels (`ld65 -Ln`), a Mesen label file (`.mlb`),
+    /// or a WLA-DX style symbol file (`.sym`, also written by bass and asar)
+    pub fn load(filename: &str) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(filename)?;
+        let extension = Path::new(filename).extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let result = match extension.as_deref() {
+            Some("dbg") => ca65::parse_dbg(&text),
+            Some("mlb") => mlb::parse_mlb(&text),
+            _ if text.trim_start().starts_with("al ") => vice::parse_labels(&text),
+            _ => wla::parse_sym(&text),
+        };
+        result.map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    /// Symbol file next to `rom_path` with the same name, if there is one
+    pub fn find_for_rom(rom_path: &str) -> Option<PathBuf> {
+        EXTENSIONS.iter()
+            .map(|extension| Path::new(rom_path).with_extension(extension))
+            .find(|path| path.exists())
+    }
+
+    pub fn add_label(&mut self, address: u32, name: &str) {
+        self.labels.entry(address).or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    /// Returns the index of `path` in `files`, adding it if needed
+    pub fn add_file(&mut self, path: &str) -> usize {
+        match self.files.iter().position(|f| f == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_string());
+                self.files.len() - 1
+            },
+        }
+    }
+
+    pub fn add_line(&mut self, address: u32, file: usize, line: u32) {
+        self.lines.entry(address).or_insert(SourceLine {file, line});
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u32> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Label at `address`, banks $80-$FD fall back to their $00-$7D mirror
+    pub fn label_at(&self, address: u32) -> Option<&str> {
+        self.labels.get(&address)
+            .or_else(|| match address >> 16 {
+                0x80..=0xFD => self.labels.get(&(address & 0x7FFFFF)),
+                _ => None,
+            })
+            .map(String::as_str)
+    }
+
+    /// Resolves a label name or a hex address (`$808000`, `0x808000`, `808000`)
+    pub fn resolve(&self, text: &str) -> Result<u32, String> {
+        let text = text.trim();
+        if let Some(address) = self.address_of(text) {
+            return Ok(address);
+        }
+        let hex = text.strip_prefix('$')
+            .or_else(|| text.strip_prefix("0x"))
+            .unwrap_or(text);
+        u32::from_str_radix(hex, 16).map_err(|_| format!("Unknown label or address '{}'", text))
+    }
+
+    /// Closest label at or before `address` in the same bank, and the offset from it
+    pub fn nearest_label(&self, address: u32) -> Option<(&str, u32)> {
+        self.labels.iter()
+            .filter(|(a, _)| **a <= address && (**a >> 16) == (address >> 16))
+            .max_by_key(|(a, _)| **a)
+            .map(|(a, name)| (name.as_str(), address - a))
+    }
+
+    pub fn line_at(&self, address: u32) -> Option<SourceLine> {
+        self.lines.get(&address).copied()
+    }
+
+    /// Addresses of the code generated for `line` of the source file at `path`
+    pub fn addresses_at_line(&self, path: &str, line: u32) -> Vec<u32> {
+        self.lines.iter()
+            .filter(|(_, l)| l.line == line && is_same_file(&self.files[l.file], path))
+            .map(|(address, _)| *address)
+            .collect()
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod symbols_interface_tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups() {
+        let mut symbols = SymbolTable::new();
+        symbols.add_label(0x008000, "reset");
+        symbols.add_label(0x008000, "main");
+        symbols.add_label(0x008010, "loop");
+        let file = symbols.add_file("src/main.s");
+        assert_eq!(symbols.add_file("src/main.s"), file);
+        symbols.add_line(0x008000, file, 10);
+        symbols.add_line(0x008010, file, 14);
+        symbols.add_line(0x808010, file, 14);
+
+        assert_eq!(symbols.label_at(0x008000), Some("reset"));
+        assert_eq!(symbols.address_of("main"), Some(0x008000));
+        assert_eq!(symbols.label_at(0x808010), Some("loop"));
+        assert_eq!(symbols.label_at(0xFE8010), None);
+        assert_eq!(symbols.resolve("loop"), Ok(0x008010));
+        assert_eq!(symbols.resolve("$808000"), Ok(0x808000));
+        assert!(symbols.resolve("nowhere").is_err());
+        assert_eq!(symbols.nearest_label(0x008012), Some(("loop", 2)));
+        assert_eq!(symbols.nearest_label(0x018000), None);
+        assert_eq!(symbols.line_at(0x008010), Some(SourceLine {file, line: 14}));
+        assert_eq!(symbols.addresses_at_line("/home/dev/game/src/main.s", 14), vec![0x008010, 0x808010]);
+        assert_eq!(symbols.addresses_at_line("C:\\game\\src\\main.s", 10), vec![0x008000]);
+        assert!(symbols.addresses_at_line("/home/dev/game/src/other.s", 10).is_empty());
+    }
+}
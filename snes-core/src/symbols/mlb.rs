@@ -0,0 +1,65 @@
+//! Mesen label files (`.mlb`).
+//!
+//! Lines are `type:offset[-end]:label[:comment]`, where the offset is relative to
+//! the memory type. ROM and SRAM offsets are mapped to LoROM CPU addresses.
+use super::SymbolTable;
+
+fn to_cpu_address(memory_type: &str, offset: u32) -> Option<u32> {
+    match memory_type {
+        "SnesPrgRom" | "PRG" => Some(((offset >> 15) << 16) | 0x8000 | (offset & 0x7FFF)),
+        "SnesWorkRam" | "WORK" => Some(0x7E0000 + (offset & 0x1FFFF)),
+        "SnesSaveRam" | "SAVE" => Some((0x70 + (offset >> 15)) << 16 | (offset & 0x7FFF)),
+        "SnesRegister" | "REG" => Some(offset & 0xFFFF),
+        // Other CPUs' memory (SPC, Super FX...)
+        _ => None,
+    }
+}
+
+pub fn parse_mlb(text: &str) -> Result<SymbolTable, String> {
+    let mut table = SymbolTable::new();
+    for (number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.trim().splitn(4, ':').collect();
+        let [memory_type, offset, name, ..] = fields.as_slice() else {
+            continue
+        };
+        // Comment-only entries have no name
+        if name.is_empty() {
+            continue
+        }
+        let start = offset.split('-').next().unwrap_or_default();
+        let offset = u32::from_str_radix(start, 16)
+            .map_err(|_| format!("Line {}: Invalid offset '{}'", number + 1, start))?;
+        if let Some(address) = to_cpu_address(memory_type, offset) {
+            table.add_label(address, name);
+        }
+    }
+    Ok(table)
+}
+
+
+#[cfg(test)]
+mod symbols_mlb_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mlb() {
+        let text = "\
+SnesPrgRom:0000:Reset
+SnesPrgRom:9A3C:UpdatePlayer:Moves the player
+SnesWorkRam:0010-0011:player_x
+SnesSaveRam:0000:save_slot
+SnesRegister:2100:INIDISP
+SnesPrgRom:1234::Comment only
+SpcRam:0200:spc_entry
+";
+        let table = parse_mlb(text).unwrap();
+        assert_eq!(table.address_of("Reset"), Some(0x008000));
+        assert_eq!(table.address_of("UpdatePlayer"), Some(0x019A3C));
+        assert_eq!(table.address_of("player_x"), Some(0x7E0010));
+        assert_eq!(table.address_of("save_slot"), Some(0x700000));
+        assert_eq!(table.address_of("INIDISP"), Some(0x002100));
+        assert_eq!(table.address_of("spc_entry"), None);
+        assert_eq!(table.labels.len(), 5);
+        assert!(parse_mlb("SnesPrgRom:zz:Broken\n").is_err());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod interface;
+pub use interface::SymbolTable;
+pub mod ca65;
+pub mod wla;
+pub mod vice;
+pub mod mlb;
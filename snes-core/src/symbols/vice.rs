@@ -0,0 +1,43 @@
+//! VICE label files, written by `ld65 -Ln`.
+//!
+//! Each line is `al address .name`, the address is a full 24-bit address in hex,
+//! optionally prefixed with VICE's `C:` memory space.
+use super::SymbolTable;
+
+pub fn parse_labels(text: &str) -> Result<SymbolTable, String> {
+    let mut table = SymbolTable::new();
+    for (number, line) in text.lines().enumerate() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let [command, address, name, ..] = tokens.as_slice() else {
+            continue
+        };
+        if !command.eq_ignore_ascii_case("al") {
+            continue
+        }
+        let address = address.strip_prefix("C:").unwrap_or(address);
+        let address = u32::from_str_radix(address, 16)
+            .map_err(|_| format!("Line {}: Invalid address '{}'", number + 1, address))?;
+        table.add_label(address & 0xFFFFFF, name.strip_prefix('.').unwrap_or(name));
+    }
+    Ok(table)
+}
+
+
+#[cfg(test)]
+mod symbols_vice_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        let text = "\
+al 008000 .Reset
+al 00809A3C .UpdatePlayer
+al C:7E0010 .player_x
+";
+        let table = parse_labels(text).unwrap();
+        assert_eq!(table.address_of("Reset"), Some(0x008000));
+        assert_eq!(table.label_at(0x809A3C), Some("UpdatePlayer"));
+        assert_eq!(table.address_of("player_x"), Some(0x7E0010));
+        assert!(parse_labels("al zz .Broken\n").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! WLA-DX symbol files, written by `wlalink -S`.
+//!
+//! Sections start with a `[name]` line. Labels are `bank:address name`, and with
+//! `-A` the linker also writes `[source files]` and `[addr-to-line mapping]`
+//! sections, where each address maps to a hexadecimal `file:line` pair.
+//!
+//! bass (`-sym`) and asar (`--symbols=wla`) write the same format, and asar's
+//! `--symbols=nocash` files are plain `bbaaaaaa name` lines, read as labels too.
+use std::collections::HashMap;
+
+use super::SymbolTable;
+
+fn parse_hex(text: &str) -> Result<u32, String> {
+    let hex = text.strip_prefix("0x").unwrap_or(text);
+    u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid number '{}'", text))
+}
+
+/// Parses a `bank:address` pair, or a full address in hex, into a 24-bit address
+fn parse_address(text: &str) -> Result<u32, String> {
+    match text.split_once(':') {
+        Some((bank, address)) => Ok((parse_hex(bank)? << 16) | parse_hex(address)?),
+        None => {
+            let hex = text.strip_prefix('$').unwrap_or(text);
+            Ok(parse_hex(hex)? & 0xFFFFFF)
+        },
+    }
+}
+
+pub fn parse_sym(text: &str) -> Result<SymbolTable, String> {
+    let mut table = SymbolTable::new();
+    let mut file_indices = HashMap::new();
+    let mut line_mappings = vec![];
+    // Old symbol files only have labels and no section headers
+    let mut section = String::from("labels");
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.to_lowercase();
+            continue
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let result: Result<(), String> = match (section.as_str(), tokens.as_slice()) {
+            ("labels", [address, name, ..]) => parse_address(address).map(|address| {
+                table.add_label(address, name);
+            }),
+            // `id path`, or `id crc32 path` in newer versions
+            ("source files", [id, rest @ ..]) if !rest.is_empty() => parse_hex(id).map(|id| {
+                let path = match rest {
+                    [checksum, path @ ..] if checksum.starts_with("0x") && !path.is_empty() => path.join(" "),
+                    _ => rest.join(" "),
+                };
+                file_indices.insert(id, table.add_file(&path));
+            }),
+            ("addr-to-line mapping", [address, location]) => {
+                location.split_once(':')
+                    .ok_or_else(|| format!("Invalid source location '{}'", location))
+                    .and_then(|(file, line)| {
+                        line_mappings.push((parse_address(address)?, parse_hex(file)?, parse_hex(line)?));
+                        Ok(())
+                    })
+            },
+            _ => Ok(()),
+        };
+        result.map_err(|err| format!("Line {}: {}", number + 1, err))?;
+    }
+
+    for (address, file, line) in line_mappings {
+        if let Some(file_index) = file_indices.get(&file) {
+            table.add_line(address, *file_index, line);
+        }
+    }
+    Ok(table)
+}
+
+
+#[cfg(test)]
+mod symbols_wla_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sym() {
+        let text = "\
+; wla symbolic information file
+; generated by wlalink
+
+[labels]
+00:8000 Main
+00:8004 _loop
+01:9000 Other
+
+[source files]
+0000 0x3a4f1c22 src/main.asm
+0001 src/lib.asm
+
+[addr-to-line mapping]
+00:8000 0000:0000000a
+00:8004 0000:0000000c
+01:9000 0001:00000003
+";
+        let table = parse_sym(text).unwrap();
+        assert_eq!(table.address_of("Main"), Some(0x008000));
+        assert_eq!(table.label_at(0x019000), Some("Other"));
+        assert_eq!(table.files, vec!["src/main.asm", "src/lib.asm"]);
+        assert_eq!(table.addresses_at_line("/home/dev/src/main.asm", 12), vec![0x008004]);
+        assert_eq!(table.addresses_at_line("/home/dev/src/lib.asm", 3), vec![0x019000]);
+
+        // Section-less files only hold labels
+        let table = parse_sym("00:8000 Start\n").unwrap();
+        assert_eq!(table.address_of("Start"), Some(0x008000));
+        assert!(parse_sym("[labels]\nzz:8000 Start\n").is_err());
+
+        // asar's no$sns format
+        let table = parse_sym("00808000 Start\n00809A3C UpdatePlayer\n").unwrap();
+        assert_eq!(table.address_of("UpdatePlayer"), Some(0x809A3C));
+    }
+}
@@ -0,0 +1,229 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::cycles::CycleCheck;
+use crate::suite::{self, TestSuiteList};
+
+/// Failures kept per file, the rest are only counted
+pub const MAX_RECORDED_FAILURES: usize = 100;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    Emulation,
+    Native,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Emulation => "emulation",
+            Mode::Native => "native",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestFile {
+    pub path: PathBuf,
+    pub opcode: Option<u8>,
+    pub mode: Option<Mode>,
+}
+
+impl TestFile {
+    /// Test files are named after their opcode and mode, like `a9.e.json` or `a9.n.json`
+    pub fn from_path(path: &Path) -> Self {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let mut parts = file_name.split('.');
+        let opcode = parts.next().and_then(|p| u8::from_str_radix(p, 16).ok());
+        let mode = match parts.next() {
+            Some("e") => Some(Mode::Emulation),
+            Some("n") => Some(Mode::Native),
+            _ => None,
+        };
+        Self {path: path.to_path_buf(), opcode, mode}
+    }
+
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    }
+}
+
+pub struct Filters {
+    pub opcodes: Vec<u8>,
+    pub mode: Option<Mode>,
+}
+
+impl Filters {
+    fn matches(&self, file: &TestFile) -> bool {
+        let opcode_matches = self.opcodes.is_empty() || file.opcode.is_some_and(|o| self.opcodes.contains(&o));
+        let mode_matches = self.mode.is_none() || file.mode == self.mode;
+        opcode_matches && mode_matches
+    }
+}
+
+pub struct RunOptions {
+    /// Set by `--cycles`
+    pub check_cycles: Option<CycleCheck>,
+    pub stop_on_fail: bool,
+    pub verbose: bool,
+}
+
+pub struct Failure {
+    pub name: String,
+    pub reason: String,
+}
+
+pub struct FileResult {
+    pub file: TestFile,
+    pub passed: usize,
+    pub failed: usize,
+    /// Up to `MAX_RECORDED_FAILURES`
+    pub failures: Vec<Failure>,
+    /// Set when the file couldn't be loaded
+    pub error: Option<String>,
+}
+
+impl FileResult {
+    pub fn is_success(&self) -> bool {
+        self.failed == 0 && self.error.is_none()
+    }
+}
+
+/// JSON test files in `directory` that pass `filters`, sorted by name
+pub fn collect_files(directory: &Path, filters: &Filters) -> std::io::Result<Vec<TestFile>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            let file = TestFile::from_path(&path);
+            if filters.matches(&file) {
+                files.push(file);
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Runs every test in `file`. Stops early once `stop` is set, and sets it on
+/// the first failure when stopping on failures.
+pub fn run_file(file: TestFile, options: &RunOptions, stop: &AtomicBool) -> FileResult {
+    let mut result = FileResult {file, passed: 0, failed: 0, failures: vec![], error: None};
+    let tests: TestSuiteList = match suite::load_tests(&result.file.path.to_string_lossy()) {
+        Ok(tests) => tests,
+        Err(error) => {
+            result.error = Some(error);
+            if options.stop_on_fail {
+                stop.store(true, Ordering::Relaxed);
+            }
+            return result;
+        },
+    };
+    let mut emulator = suite::new_emulator(options.check_cycles);
+    for test in &tests.0 {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if options.verbose {
+            println!("running test case {}", test.name);
+        }
+        match suite::run_test(&mut emulator, test, options.check_cycles) {
+            None => result.passed += 1,
+            Some(reason) => {
+                result.failed += 1;
+                if result.failures.len() < MAX_RECORDED_FAILURES {
+                    result.failures.push(Failure {name: test.name.clone(), reason});
+                }
+                if options.stop_on_fail {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            },
+        }
+    }
+    result
+}
+
+/// Runs the files on `threads` threads, results come back in the same order as `files`
+pub fn run_files(files: Vec<TestFile>, options: &RunOptions, threads: usize) -> Vec<FileResult> {
+    let stop = AtomicBool::new(false);
+    let next_file = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<FileResult>>> = Mutex::new(files.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next_file.fetch_add(1, Ordering::Relaxed);
+                if index >= files.len() || stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let result = run_file(files[index].clone(), options, &stop);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Prints a 16x16 opcode grid, each cell being `<emulation><native>`:
+/// `.` passed, `F` failed, `!` couldn't be loaded, `-` not run
+pub fn print_matrix(results: &[FileResult]) {
+    let status = |opcode: u8, mode: Mode| {
+        let result = results.iter().find(|r| r.file.opcode == Some(opcode) && r.file.mode == Some(mode));
+        match result {
+            Some(result) if result.error.is_some() => '!',
+            Some(result) if result.failed > 0 => 'F',
+            Some(_) => '.',
+            None => '-',
+        }
+    };
+    print!("   ");
+    for low in 0..16 {
+        print!(" x{:X}", low);
+    }
+    println!();
+    for high in 0..16u8 {
+        print!("{:X}x ", high);
+        for low in 0..16u8 {
+            let opcode = (high << 4) | low;
+            print!(" {}{}", status(opcode, Mode::Emulation), status(opcode, Mode::Native));
+        }
+        println!();
+    }
+    println!("(emulation, native) . passed  F failed  ! load error  - not run");
+}
+
+
+#[cfg(test)]
+mod batch_tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_parsing() {
+        let file = TestFile::from_path(Path::new("tests/v1/a9.e.json"));
+        assert_eq!(file.opcode, Some(0xA9));
+        assert_eq!(file.mode, Some(Mode::Emulation));
+        assert_eq!(file.name(), "a9.e.json");
+        let file = TestFile::from_path(Path::new("FF.n.json"));
+        assert_eq!(file.opcode, Some(0xFF));
+        assert_eq!(file.mode, Some(Mode::Native));
+        let file = TestFile::from_path(Path::new("results.json"));
+        assert_eq!(file.opcode, None);
+        assert_eq!(file.mode, None);
+        let file = TestFile::from_path(Path::new("a9.x.json"));
+        assert_eq!(file.opcode, Some(0xA9));
+        assert_eq!(file.mode, None);
+    }
+
+    #[test]
+    fn test_filters() {
+        let files = ["a9.e.json", "a9.n.json", "ad.e.json", "results.json"]
+            .map(|name| TestFile::from_path(Path::new(name)));
+        let matching = |filters: &Filters| -> Vec<String> {
+            files.iter().filter(|f| filters.matches(f)).map(|f| f.name()).collect()
+        };
+        assert_eq!(matching(&Filters {opcodes: vec![], mode: None}).len(), 4);
+        assert_eq!(matching(&Filters {opcodes: vec![0xA9], mode: None}), ["a9.e.json", "a9.n.json"]);
+        assert_eq!(matching(&Filters {opcodes: vec![], mode: Some(Mode::Emulation)}), ["a9.e.json", "ad.e.json"]);
+        assert_eq!(matching(&Filters {opcodes: vec![0xA9, 0xAD], mode: Some(Mode::Native)}), ["a9.n.json"]);
+    }
+}
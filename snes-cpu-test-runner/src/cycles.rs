@@ -0,0 +1,173 @@
+// Compares the bus accesses the core made with a test's `cycles` list.
+// Each expected cycle is `[address, value, flags]`, where the value is null on
+// idle cycles and the flags are a string like "dp-r----": d = VDA, p = VPA,
+// v = VPB, r/w = read/write. Cycles with neither VDA nor VPA don't reach the
+// bus, and the core doesn't model them, so they only count towards the total.
+// The core only guesses VDA/VPA (see `snes_core::common::bus_cycle`), so they are
+// only compared in strict mode.
+use snes_core::common::bus_cycle::BusCycle;
+use snes_core::common::memory_access::AccessKind;
+
+pub type ExpectedCycle = (usize, Option<usize>, String);
+
+/// What `compare_cycles` checks on each bus access
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CycleCheck {
+    /// Address, value, read/write and VPB (`--cycles`)
+    Accesses,
+    /// VDA and VPA too (`--cycles=strict`)
+    Strict,
+}
+
+fn format_flags(vda: bool, vpa: bool, vpb: bool, is_write: bool) -> String {
+    format!(
+        "{}{}{}{}",
+        if vda {'d'} else {'-'},
+        if vpa {'p'} else {'-'},
+        if vpb {'v'} else {'-'},
+        if is_write {'w'} else {'r'},
+    )
+}
+
+fn format_expected(cycle: &ExpectedCycle) -> String {
+    let (address, value, flags) = cycle;
+    let value = match value {
+        Some(value) => format!("${:02X}", value),
+        None => String::from("--"),
+    };
+    let flags = format_flags(flags.contains('d'), flags.contains('p'), flags.contains('v'), flags.contains('w'));
+    format!("{} ${:06X} = {}", flags, address, value)
+}
+
+fn format_actual(cycle: &BusCycle) -> String {
+    let flags = format_flags(cycle.vda, cycle.vpa, cycle.vpb, cycle.kind == AccessKind::Write);
+    format!("{} ${:06X} = ${:02X}", flags, cycle.address, cycle.value)
+}
+
+fn matches(expected: &ExpectedCycle, actual: &BusCycle, check: CycleCheck) -> bool {
+    let (address, value, flags) = expected;
+    let signals_match = check != CycleCheck::Strict ||
+        (flags.contains('d') == actual.vda && flags.contains('p') == actual.vpa);
+    *address == actual.address as usize &&
+    value.is_none_or(|value| value == actual.value as usize) &&
+    flags.contains('w') == (actual.kind == AccessKind::Write) &&
+    flags.contains('v') == actual.vpb &&
+    signals_match
+}
+
+/// Describes the first cycle that differs, or `None` when they all match
+pub fn compare_cycles(expected: &[ExpectedCycle], actual: &[BusCycle], cpu_cycles: u64, check: CycleCheck) -> Option<String> {
+    let mut bus_cycles = expected.iter().enumerate()
+        .filter(|(_, (_, _, flags))| flags.contains('d') || flags.contains('p'));
+    let mut actual_cycles = actual.iter();
+    loop {
+        match (bus_cycles.next(), actual_cycles.next()) {
+            (Some((index, expected)), Some(actual)) if !matches(expected, actual, check) => return Some(format!(
+                "cycle {}: expected {}, got {}", index, format_expected(expected), format_actual(actual),
+            )),
+            (Some(_), Some(_)) => {},
+            (Some((index, expected)), None) => return Some(format!(
+                "cycle {}: expected {}, got no access", index, format_expected(expected),
+            )),
+            (None, Some(actual)) => return Some(format!(
+                "cycle {}: expected no more accesses, got {}", expected.len(), format_actual(actual),
+            )),
+            (None, None) => break,
+        }
+    }
+    if cpu_cycles as usize != expected.len() {
+        return Some(format!("expected {} cycles, took {}", expected.len(), cpu_cycles));
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod cycles_tests {
+    use super::*;
+
+    fn expected(address: usize, value: Option<usize>, flags: &str) -> ExpectedCycle {
+        (address, value, flags.to_string())
+    }
+
+    fn read(address: u32, value: u8, vda: bool, vpa: bool) -> BusCycle {
+        BusCycle {address, value, kind: AccessKind::Read, vda, vpa, vpb: false}
+    }
+
+    #[test]
+    fn test_matching_cycles() {
+        // LDA $12 with an idle cycle in between
+        let expected_cycles = vec![
+            expected(0x000100, Some(0xA5), "dp-r----"),
+            expected(0x000101, Some(0x12), "-p-r----"),
+            expected(0x000101, None, "---r----"),
+            expected(0x000012, Some(0x34), "d--r----"),
+        ];
+        let actual = vec![
+            read(0x000100, 0xA5, true, true),
+            read(0x000101, 0x12, false, true),
+            read(0x000012, 0x34, true, false),
+        ];
+        assert_eq!(compare_cycles(&expected_cycles, &actual, 4, CycleCheck::Accesses), None);
+        assert_eq!(compare_cycles(&expected_cycles, &actual, 4, CycleCheck::Strict), None);
+        // The inferred VDA/VPA only count in strict mode
+        let actual = vec![
+            read(0x000100, 0xA5, true, true),
+            read(0x000101, 0x12, true, false),
+            read(0x000012, 0x34, false, true),
+        ];
+        assert_eq!(compare_cycles(&expected_cycles, &actual, 4, CycleCheck::Accesses), None);
+        assert_eq!(
+            compare_cycles(&expected_cycles, &actual, 4, CycleCheck::Strict),
+            Some(String::from("cycle 1: expected -p-r $000101 = $12, got d--r $000101 = $12")),
+        );
+    }
+
+    #[test]
+    fn test_first_diverging_cycle() {
+        let expected_cycles = vec![
+            expected(0x000100, Some(0xA5), "dp-r----"),
+            expected(0x000101, None, "---r----"),
+            expected(0x000012, Some(0x34), "d--r----"),
+            expected(0x000013, Some(0x56), "d--r----"),
+        ];
+        let actual = vec![
+            read(0x000100, 0xA5, true, true),
+            read(0x000012, 0x35, true, false),
+            read(0x000014, 0x56, true, false),
+        ];
+        // Indices count the idle cycles too
+        assert_eq!(
+            compare_cycles(&expected_cycles, &actual, 4, CycleCheck::Accesses),
+            Some(String::from("cycle 2: expected d--r $000012 = $34, got d--r $000012 = $35")),
+        );
+        let mut written = actual.clone();
+        written[1] = BusCycle {kind: AccessKind::Write, value: 0x34, ..actual[1]};
+        assert_eq!(
+            compare_cycles(&expected_cycles, &written, 4, CycleCheck::Accesses),
+            Some(String::from("cycle 2: expected d--r $000012 = $34, got d--w $000012 = $34")),
+        );
+    }
+
+    #[test]
+    fn test_length_mismatch() {
+        let expected_cycles = vec![
+            expected(0x000100, Some(0xEA), "dp-r----"),
+            expected(0x000101, None, "---r----"),
+        ];
+        let nop = read(0x000100, 0xEA, true, true);
+        assert_eq!(compare_cycles(&expected_cycles, &[nop], 2, CycleCheck::Accesses), None);
+        assert_eq!(
+            compare_cycles(&expected_cycles, &[], 2, CycleCheck::Accesses),
+            Some(String::from("cycle 0: expected dp-r $000100 = $EA, got no access")),
+        );
+        assert_eq!(
+            compare_cycles(&expected_cycles, &[nop, nop], 2, CycleCheck::Accesses),
+            Some(String::from("cycle 2: expected no more accesses, got dp-r $000100 = $EA")),
+        );
+        assert_eq!(
+            compare_cycles(&expected_cycles, &[nop], 3, CycleCheck::Accesses),
+            Some(String::from("expected 2 cycles, took 3")),
+        );
+    }
+}
@@ -1,166 +1,181 @@
-use snes_core::cpu::bus::Bus;
-use snes_core::cpu::registers::Registers;
-use snes_core::cpu::CPU;
-/// https://github.com/TomHarte/ProcessorTests/tree/main/65816
-
-use snes_core::emulator::Emulator;
-use serde::{Deserialize, Serialize};
-use serde_json::Result;
-use snes_core::rom::special_ram_cart::SpecialRAMCart;
-
-use std::fs::File;
-use std::io::Read;
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TestState {
-    pc: usize,
-    s: usize,
-    p: usize,
-    a: usize,
-    x: usize,
-    y: usize,
-    dbr: usize,
-    d: usize,
-    pbr: usize,
-    e: usize,
-    ram: Vec<(usize, usize)>,
+// https://github.com/TomHarte/ProcessorTests/tree/main/65816
+//
+// Usage: snes-cpu-test-runner <test file or directory> [options]
+//   --cycles              also compare every bus access against the test's cycle list: address,
+//                         value, read/write and VPB. Idle cycles only count towards the total.
+//   --cycles=strict       like --cycles, and VDA/VPA too. The core infers those from the
+//                         instruction bytes (see snes_core::common::bus_cycle).
+//   --opcode a9[,ad]      only run these opcodes (directory mode)
+//   --emulation-only      only run the `.e.json` files (directory mode)
+//   --native-only         only run the `.n.json` files (directory mode)
+//   --stop-on-fail        stop at the first failing test
+//   --junit <file>        write a JUnit XML report
+//   --json <file>         write a JSON report
+//   --threads <n>         number of files run in parallel (directory mode)
+//   --verbose             print every test name
+//   --case "<name>"       replay a single test with its trace and disassembly
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+mod batch;
+mod cycles;
+mod replay;
+mod report;
+mod suite;
+
+use batch::{FileResult, Filters, Mode, RunOptions, TestFile};
+use cycles::CycleCheck;
+
+struct Arguments {
+    path: String,
+    filters: Filters,
+    run_options: RunOptions,
+    junit_path: Option<String>,
+    json_path: Option<String>,
+    threads: usize,
+    case: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct TestSuite {
-    name: String,
-    initial: TestState,
-    r#final: TestState,
-    cycles: Vec<(usize, Option<usize>, String)>,
+fn parse_arguments(args: &[String]) -> Result<Arguments, String> {
+    let mut path = None;
+    let mut filters = Filters {opcodes: vec![], mode: None};
+    let mut run_options = RunOptions {check_cycles: None, stop_on_fail: false, verbose: false};
+    let mut junit_path = None;
+    let mut json_path = None;
+    let mut case = None;
+    let mut threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--cycles" => run_options.check_cycles = Some(CycleCheck::Accesses),
+            "--cycles=strict" => run_options.check_cycles = Some(CycleCheck::Strict),
+            "--stop-on-fail" => run_options.stop_on_fail = true,
+            "--verbose" => run_options.verbose = true,
+            "--emulation-only" => filters.mode = Some(Mode::Emulation),
+            "--native-only" => filters.mode = Some(Mode::Native),
+            "--opcode" => {
+                for opcode in value(arg)?.split(',') {
+                    let opcode = opcode.trim().trim_start_matches("0x").trim_start_matches('$');
+                    filters.opcodes.push(u8::from_str_radix(opcode, 16).map_err(|_| format!("Invalid opcode '{}'", opcode))?);
+                }
+            },
+            "--junit" => junit_path = Some(value(arg)?),
+            "--json" => json_path = Some(value(arg)?),
+            "--case" => case = Some(value(arg)?),
+            "--threads" => threads = value(arg)?.parse().map_err(|_| String::from("Invalid thread count"))?,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => path = Some(arg.clone()),
+        }
+    }
+    let path = path.filter(|p| !p.is_empty()).ok_or("A test file or directory must be provided")?;
+    Ok(Arguments {path, filters, run_options, junit_path, json_path, threads, case})
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct TestSuiteList(Vec<TestSuite>);
-
-fn print_failed(initial_state: &TestState, expected_state: &TestState, bus: &Bus, cpu_registers: &Registers) {
-    eprintln!("FAILED!");
-    eprintln!("----------");
-    eprintln!("Initial:");
-    eprintln!("{:?}", initial_state);
-    eprintln!("Expected:");
-    eprintln!("{:?}", expected_state);
-    eprintln!("Result:");
-    let mut result_ram = vec![];
-    for (address, _) in &expected_state.ram {
-        result_ram.push((*address, bus.read_external(*address as u32) as usize))
+fn write_report(path: &Option<String>, contents: String) {
+    if let Some(path) = path {
+        if let Err(err) = std::fs::write(path, contents) {
+            eprintln!("Could not write {}: {}", path, err);
+        }
     }
-    let result_state = TestState {
-        pc: cpu_registers.pc as usize,
-        s: cpu_registers.sp as usize,
-        p: cpu_registers.p as usize,
-        a: cpu_registers.a as usize,
-        x: cpu_registers.x as usize,
-        y: cpu_registers.y as usize,
-        dbr: cpu_registers.dbr as usize,
-        d: cpu_registers.d as usize,
-        pbr: cpu_registers.pbr as usize,
-        e: if cpu_registers.emulation_mode {1} else {0},
-        ram: result_ram,
-    };
-    eprintln!("{:?}", result_state);
-    eprintln!("----------");
-    // std::process::exit(1);
 }
 
-fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("A test file must be provided");
-        std::process::exit(1);
-    }
-    let filename = args.get(1).unwrap();
-    if filename.is_empty() {
-        eprintln!("A test file must be provided");
-        std::process::exit(1);
-    }
-    let mut file = File::open(filename).unwrap();
-    let mut buff = String::new();
-    file.read_to_string(&mut buff).unwrap();
-
-    let tests: TestSuiteList = serde_json::from_str(&buff)?;
-
-    let mut emulator = Emulator::new();
-    emulator.bus.force_cart_lookup = true;
-    emulator.bus.rom = Box::new(SpecialRAMCart::new());
-
-    let mut total_failed = 0;
-    let mut total_passed = 0;
-
-    for test in &tests.0 {
-        emulator.cpu = CPU::new();
-        let mut did_test_fail = false;
-        println!("running test case {}", test.name);
-
-        emulator.cpu.registers.pc = test.initial.pc as u16;
-        emulator.cpu.registers.sp = test.initial.s as u16;
-        emulator.cpu.registers.p = test.initial.p as u8;
-        emulator.cpu.registers.a = test.initial.a as u16;
-        emulator.cpu.registers.x = test.initial.x as u16;
-        emulator.cpu.registers.y = test.initial.y as u16;
-        emulator.cpu.registers.dbr = test.initial.dbr as u8;
-        emulator.cpu.registers.d = test.initial.d as u16;
-        emulator.cpu.registers.pbr = test.initial.pbr as u8;
-        emulator.cpu.registers.emulation_mode = test.initial.e == 1;
-
-        for (address, value) in &test.initial.ram {
-            emulator.bus.write(*address as u32, *value as u8);
-        }
+/// 0 when every file loaded and passed, 1 otherwise
+fn exit_code(results: &[FileResult]) -> i32 {
+    if results.iter().all(|r| r.is_success()) { 0 } else { 1 }
+}
 
-        emulator.tick();
-        while emulator.cpu.registers.is_moving {
-            emulator.tick();
-        }
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arguments = match parse_arguments(&args) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        },
+    };
 
-        let is_emu_mode = emulator.cpu.registers.emulation_mode || (test.initial.e == 1);
-        let is_16index = emulator.cpu.registers.is_16bit_index();
-
-        let emu_mode_sp = (emulator.cpu.registers.sp as usize & 0xFF) | 0x100;
-
-        // compare the results
-        if
-            emulator.cpu.registers.pc as usize != test.r#final.pc ||
-            (if is_emu_mode {emu_mode_sp} else {emulator.cpu.registers.sp as usize}) != test.r#final.s ||
-            emulator.cpu.registers.p as usize != test.r#final.p ||
-            emulator.cpu.registers.a as usize != test.r#final.a ||
-            if is_16index {emulator.cpu.registers.x as usize} else {emulator.cpu.registers.x as u8 as usize} != test.r#final.x ||
-            if is_16index {emulator.cpu.registers.y as usize} else {emulator.cpu.registers.y as u8 as usize} != test.r#final.y ||
-            emulator.cpu.registers.dbr as usize != test.r#final.dbr ||
-            emulator.cpu.registers.d as usize != test.r#final.d ||
-            emulator.cpu.registers.pbr as usize != test.r#final.pbr ||
-            emulator.cpu.registers.emulation_mode != (test.r#final.e == 1)
-        {
-            print_failed(&test.initial, &test.r#final, &emulator.bus, &emulator.cpu.registers);
-            did_test_fail = true;
-        }
-        for (address, value) in &test.r#final.ram {
-            if *value != emulator.bus.read_external(*address as u32) as usize {
-                print_failed(&test.initial, &test.r#final, &emulator.bus, &emulator.cpu.registers);
-                did_test_fail = true;
-            }
+    let path = Path::new(&arguments.path);
+    let is_directory = path.is_dir();
+    let files = if is_directory {
+        match batch::collect_files(path, &arguments.filters) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("Could not read {}: {}", arguments.path, err);
+                std::process::exit(1);
+            },
         }
+    } else {
+        vec![TestFile::from_path(path)]
+    };
 
-        if did_test_fail {
-            total_failed += 1;
-        } else {
-            total_passed += 1;
+    if let Some(case) = &arguments.case {
+        match replay::replay_case(&files, case, arguments.run_options.check_cycles) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            },
         }
+    }
 
-        // Cleanup RAM
-        for (address, _) in &test.initial.ram {
-            emulator.bus.write(*address as u32, 0x00);
+    let results = if is_directory {
+        batch::run_files(files, &arguments.run_options, arguments.threads)
+    } else {
+        let stop = AtomicBool::new(false);
+        files.into_iter().map(|file| batch::run_file(file, &arguments.run_options, &stop)).collect()
+    };
+
+    for result in &results {
+        if let Some(error) = &result.error {
+            eprintln!("{}", error);
+        }
+        for failure in &result.failures {
+            eprintln!("FAILED! {} ({})", failure.name, result.file.name());
+            eprintln!("----------");
+            eprintln!("{}", failure.reason);
+            eprintln!("----------");
         }
-        for (address, _) in &test.r#final.ram {
-            emulator.bus.write(*address as u32, 0x00);
+        if result.failed > result.failures.len() {
+            eprintln!("...and {} more failures in {}", result.failed - result.failures.len(), result.file.name());
         }
     }
+    if is_directory {
+        batch::print_matrix(&results);
+    }
+    write_report(&arguments.junit_path, report::to_junit(&results));
+    write_report(&arguments.json_path, report::to_json(&results));
 
+    let total_passed: usize = results.iter().map(|r| r.passed).sum();
+    let total_failed: usize = results.iter().map(|r| r.failed).sum();
     println!("----------");
     println!("TOTAL PASSED: {}", total_passed);
     println!("TOTAL FAILED: {}", total_failed);
-    Ok(())
+    std::process::exit(exit_code(&results));
+}
+
+
+#[cfg(test)]
+mod main_tests {
+    use super::*;
+
+    fn result(name: &str, failed: usize, error: Option<&str>) -> FileResult {
+        FileResult {
+            file: TestFile::from_path(Path::new(name)),
+            passed: 10,
+            failed,
+            failures: vec![],
+            error: error.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&[]), 0);
+        assert_eq!(exit_code(&[result("a9.e.json", 0, None), result("a9.n.json", 0, None)]), 0);
+        assert_eq!(exit_code(&[result("a9.e.json", 0, None), result("a9.n.json", 1, None)]), 1);
+        assert_eq!(exit_code(&[result("a9.e.json", 0, Some("Invalid JSON"))]), 1);
+    }
 }
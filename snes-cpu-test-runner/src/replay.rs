@@ -0,0 +1,84 @@
+use snes_core::cpu::disasm::{self, WidthState};
+use snes_core::cpu::tracer::{TraceFormat, Tracer};
+
+use crate::batch::TestFile;
+use crate::cycles::CycleCheck;
+use crate::suite;
+
+const TRACE_LINES: usize = 256;
+
+/// Finds the test named `name` in `files` and runs it alone, printing the
+/// instruction, its trace and bus accesses. Returns whether it passed.
+pub fn replay_case(files: &[TestFile], name: &str, check_cycles: Option<CycleCheck>) -> Result<bool, String> {
+    for file in files {
+        let tests = suite::load_tests(&file.path.to_string_lossy())?;
+        let Some(test) = tests.0.iter().find(|test| test.name == name) else {
+            continue
+        };
+        println!("Replaying '{}' from {}", test.name, file.name());
+
+        let mut emulator = suite::new_emulator(check_cycles);
+        suite::load_initial_state(&mut emulator, test);
+        let registers = &emulator.cpu.registers;
+        let mut state = WidthState::from_registers(registers);
+        let instruction = disasm::decode(|address| emulator.bus.read_external(address), registers.get_pc_address(), &mut state);
+        let bytes: Vec<String> = instruction.bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        println!("Instruction: ${:06X}  {:<12} {}", instruction.address, bytes.join(" "), instruction);
+
+        emulator.cpu.tracer = Some(Tracer::to_ring_buffer(TRACE_LINES, TraceFormat::Mesen));
+        emulator.bus.access_log = Some(vec![]);
+        suite::run_instruction(&mut emulator);
+
+        println!("Trace:");
+        if let Some(tracer) = &emulator.cpu.tracer {
+            for line in tracer.lines() {
+                println!("  {}", line);
+            }
+        }
+        println!("Bus accesses:");
+        for access in emulator.bus.access_log.iter().flatten() {
+            println!("  {:<5} ${:06X} = ${:02X}", format!("{:?}", access.kind), access.address, access.value);
+        }
+        return match suite::check_results(&emulator, test, check_cycles) {
+            Some(failure) => {
+                println!("FAILED!\n{}", failure);
+                Ok(false)
+            },
+            None => {
+                println!("PASSED");
+                Ok(true)
+            },
+        };
+    }
+    Err(format!("No test case named '{}'", name))
+}
+
+
+#[cfg(test)]
+mod replay_tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_replay_case() {
+        // LDA #$12 in emulation mode, the second case expects the wrong value
+        let state = |pc: usize, a: usize| format!(
+            r#"{{"pc": {}, "s": 511, "p": 52, "a": {}, "x": 0, "y": 0, "dbr": 0, "d": 0, "pbr": 0, "e": 1,
+                "ram": [[4096, 169], [4097, 18]]}}"#,
+            pc, a,
+        );
+        let case = |name: &str, a: usize| format!(
+            r#"{{"name": "{}", "initial": {}, "final": {}, "cycles": [[4096, 169, "dp-remx-"], [4097, 18, "-p-remx-"]]}}"#,
+            name, state(4096, 0), state(4098, a),
+        );
+        let path = std::env::temp_dir().join(format!("snes-replay-{}.a9.e.json", std::process::id()));
+        std::fs::write(&path, format!("[{}, {}]", case("a9 e 1", 0x12), case("a9 e 2", 0x13))).unwrap();
+        let files = [TestFile::from_path(Path::new(&path))];
+
+        assert_eq!(replay_case(&files, "a9 e 1", Some(CycleCheck::Accesses)), Ok(true));
+        assert_eq!(replay_case(&files, "a9 e 2", Some(CycleCheck::Accesses)), Ok(false));
+        assert_eq!(replay_case(&files, "a9 e 3", None), Err(String::from("No test case named 'a9 e 3'")));
+        std::fs::remove_file(path).unwrap();
+    }
+}
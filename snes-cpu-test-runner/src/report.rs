@@ -0,0 +1,113 @@
+// Machine-readable reports for CI. JUnit has one test case per file, so
+// an opcode/mode shows up as a single entry however many cases it holds.
+use serde_json::json;
+
+use crate::batch::FileResult;
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn to_junit(results: &[FileResult]) -> String {
+    let failures = results.iter().filter(|r| r.failed > 0).count();
+    let errors = results.iter().filter(|r| r.error.is_some()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"65816\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+        results.len(), failures, errors,
+    ));
+    for result in results {
+        xml.push_str(&format!("  <testcase classname=\"65816\" name=\"{}\"", escape_xml(&result.file.name())));
+        if let Some(error) = &result.error {
+            xml.push_str(&format!(">\n    <error message=\"{}\"/>\n  </testcase>\n", escape_xml(error)));
+        } else if result.failed > 0 {
+            let message = format!("{} of {} cases failed", result.failed, result.failed + result.passed);
+            let details: Vec<String> = result.failures.iter()
+                .map(|failure| format!("{}\n{}", failure.name, failure.reason))
+                .collect();
+            xml.push_str(&format!(
+                ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                escape_xml(&message),
+                escape_xml(&details.join("\n\n")),
+            ));
+        } else {
+            xml.push_str("/>\n");
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+pub fn to_json(results: &[FileResult]) -> String {
+    let files: Vec<_> = results.iter().map(|result| json!({
+        "file": result.file.name(),
+        "opcode": result.file.opcode,
+        "mode": result.file.mode.map(|mode| mode.name()),
+        "passed": result.passed,
+        "failed": result.failed,
+        "error": result.error,
+        "failures": result.failures.iter()
+            .map(|failure| json!({"name": failure.name, "reason": failure.reason}))
+            .collect::<Vec<_>>(),
+    })).collect();
+    let report = json!({
+        "passed": results.iter().map(|r| r.passed).sum::<usize>(),
+        "failed": results.iter().map(|r| r.failed).sum::<usize>(),
+        "files": files,
+    });
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod report_tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::batch::{Failure, TestFile};
+
+    fn results() -> Vec<FileResult> {
+        vec![
+            FileResult {
+                file: TestFile::from_path(Path::new("a9.e.json")),
+                passed: 10, failed: 0, failures: vec![], error: None,
+            },
+            FileResult {
+                file: TestFile::from_path(Path::new("ad.n.json")),
+                passed: 8,
+                failed: 2,
+                failures: vec![Failure {name: String::from("ad n 1"), reason: String::from("A: <1 & \"2\">")}],
+                error: None,
+            },
+            FileResult {
+                file: TestFile::from_path(Path::new("ff.e.json")),
+                passed: 0, failed: 0, failures: vec![], error: Some(String::from("Invalid <JSON>")),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = to_junit(&results());
+        assert!(xml.contains("<testsuite name=\"65816\" tests=\"3\" failures=\"1\" errors=\"1\">"));
+        assert!(xml.contains("<testcase classname=\"65816\" name=\"a9.e.json\"/>"));
+        assert!(xml.contains("<failure message=\"2 of 10 cases failed\">ad n 1\nA: &lt;1 &amp; &quot;2&quot;&gt;</failure>"));
+        assert!(xml.contains("<error message=\"Invalid &lt;JSON&gt;\"/>"));
+        assert!(xml.ends_with("</testsuite>\n"));
+    }
+
+    #[test]
+    fn test_json() {
+        let report: serde_json::Value = serde_json::from_str(&to_json(&results())).unwrap();
+        assert_eq!(report["passed"], 18);
+        assert_eq!(report["failed"], 2);
+        assert_eq!(report["files"][0]["opcode"], 0xA9);
+        assert_eq!(report["files"][0]["mode"], "emulation");
+        assert_eq!(report["files"][1]["failures"][0]["reason"], "A: <1 & \"2\">");
+        assert_eq!(report["files"][2]["error"], "Invalid <JSON>");
+        assert!(report["files"][2]["failures"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+use snes_core::cpu::CPU;
+use snes_core::cpu::bus::Bus;
+use snes_core::common::bus_cycle::BusCycleLog;
+use snes_core::emulator::Emulator;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::Read;
+
+use crate::cycles::{self, CycleCheck};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TestState {
+    pc: usize,
+    s: usize,
+    p: usize,
+    a: usize,
+    x: usize,
+    y: usize,
+    dbr: usize,
+    d: usize,
+    pbr: usize,
+    e: usize,
+    ram: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestSuite {
+    pub name: String,
+    initial: TestState,
+    r#final: TestState,
+    cycles: Vec<cycles::ExpectedCycle>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestSuiteList(pub Vec<TestSuite>);
+
+pub fn load_tests(filename: &str) -> Result<TestSuiteList, String> {
+    let mut file = File::open(filename).map_err(|err| format!("Could not open {}: {}", filename, err))?;
+    let mut buff = String::new();
+    file.read_to_string(&mut buff).map_err(|err| format!("Could not read {}: {}", filename, err))?;
+    serde_json::from_str(&buff).map_err(|err| format!("Invalid test file {}: {}", filename, err))
+}
+
+pub fn new_emulator(cycle_check: Option<CycleCheck>) -> Emulator {
+    let mut emulator = Emulator::new();
+    emulator.bus = Bus::new_flat();
+    if cycle_check.is_some() {
+        emulator.bus.cycle_log = Some(BusCycleLog::new());
+    }
+    emulator
+}
+
+const FLAG_NAMES: &str = "NVMXDIZC";
+
+/// Formats P like `$34 nvMXdIzc`, set flags in upper case
+pub fn format_p(p: usize) -> String {
+    let flags: String = FLAG_NAMES.chars().enumerate()
+        .map(|(i, flag)| if (p >> (7 - i)) & 1 == 1 { flag } else { flag.to_ascii_lowercase() })
+        .collect();
+    format!("${:02X} {}", p, flags)
+}
+
+fn format_field(name: &str, value: usize) -> String {
+    match name {
+        "P" => format_p(value),
+        "E" => value.to_string(),
+        "DBR" | "PBR" => format!("${:02X}", value),
+        _ => format!("${:04X}", value),
+    }
+}
+
+/// Register values as (name, initial, expected, actual)
+fn register_rows(test: &TestSuite, emulator: &Emulator) -> Vec<(&'static str, usize, usize, usize)> {
+    let registers = &emulator.cpu.registers;
+    let (initial, expected) = (&test.initial, &test.r#final);
+    let is_emu_mode = registers.emulation_mode || (initial.e == 1);
+    let sp = if is_emu_mode { (registers.sp as usize & 0xFF) | 0x100 } else { registers.sp as usize };
+    let index = |value: u16| if registers.is_16bit_index() { value as usize } else { value as u8 as usize };
+    vec![
+        ("PC", initial.pc, expected.pc, registers.pc as usize),
+        ("S", initial.s, expected.s, sp),
+        ("P", initial.p, expected.p, registers.p as usize),
+        ("A", initial.a, expected.a, registers.a as usize),
+        ("X", initial.x, expected.x, index(registers.x)),
+        ("Y", initial.y, expected.y, index(registers.y)),
+        ("DBR", initial.dbr, expected.dbr, registers.dbr as usize),
+        ("D", initial.d, expected.d, registers.d as usize),
+        ("PBR", initial.pbr, expected.pbr, registers.pbr as usize),
+        ("E", initial.e, expected.e, registers.emulation_mode as usize),
+    ]
+}
+
+/// Table of the registers that differ, followed by the RAM mismatches by address.
+/// `None` when the final state matches.
+fn describe_state_mismatch(test: &TestSuite, emulator: &Emulator) -> Option<String> {
+    let mut lines = vec![];
+    let rows: Vec<_> = register_rows(test, emulator).into_iter()
+        .filter(|(_, _, expected, actual)| expected != actual)
+        .collect();
+    if !rows.is_empty() {
+        lines.push(format!("{:<6}{:<16}{:<16}{}", "Field", "Initial", "Expected", "Actual"));
+        for (name, initial, expected, actual) in rows {
+            lines.push(format!(
+                "{:<6}{:<16}{:<16}{}",
+                name, format_field(name, initial), format_field(name, expected), format_field(name, actual),
+            ));
+        }
+    }
+    let initial_ram = |address: usize| test.initial.ram.iter()
+        .find(|(a, _)| *a == address)
+        .map_or(String::from("--"), |(_, value)| format!("${:02X}", value));
+    let ram_mismatches: Vec<String> = test.r#final.ram.iter()
+        .map(|(address, expected)| (*address, *expected, emulator.bus.read_external(*address as u32) as usize))
+        .filter(|(_, expected, actual)| expected != actual)
+        .map(|(address, expected, actual)| format!(
+            "${:06X}  initial {}  expected ${:02X}  actual ${:02X}",
+            address, initial_ram(address), expected, actual,
+        ))
+        .collect();
+    if !ram_mismatches.is_empty() {
+        lines.push(String::from("RAM mismatches:"));
+        lines.extend(ram_mismatches);
+    }
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
+}
+
+/// Sets up the registers and RAM a test starts with
+pub fn load_initial_state(emulator: &mut Emulator, test: &TestSuite) {
+    let tracer = emulator.cpu.tracer.take();
+    emulator.cpu = CPU::new();
+    emulator.cpu.tracer = tracer;
+
+    emulator.cpu.registers.pc = test.initial.pc as u16;
+    emulator.cpu.registers.sp = test.initial.s as u16;
+    emulator.cpu.registers.p = test.initial.p as u8;
+    emulator.cpu.registers.a = test.initial.a as u16;
+    emulator.cpu.registers.x = test.initial.x as u16;
+    emulator.cpu.registers.y = test.initial.y as u16;
+    emulator.cpu.registers.dbr = test.initial.dbr as u8;
+    emulator.cpu.registers.d = test.initial.d as u16;
+    emulator.cpu.registers.pbr = test.initial.pbr as u8;
+    emulator.cpu.registers.emulation_mode = test.initial.e == 1;
+
+    for (address, value) in &test.initial.ram {
+        emulator.bus.write(*address as u32, *value as u8);
+    }
+    if let Some(cycle_log) = &mut emulator.bus.cycle_log {
+        cycle_log.clear();
+    }
+}
+
+/// Runs the test's instruction, block moves until they finish
+pub fn run_instruction(emulator: &mut Emulator) {
+    emulator.tick();
+    while emulator.cpu.registers.is_moving {
+        emulator.tick();
+    }
+}
+
+/// Compares the state after `run_instruction` with the expected one, and the bus
+/// accesses too when the emulator was created with a `cycle_check`
+pub fn check_results(emulator: &Emulator, test: &TestSuite, cycle_check: Option<CycleCheck>) -> Option<String> {
+    let mut failures = vec![];
+    if let Some(mismatch) = describe_state_mismatch(test, emulator) {
+        failures.push(mismatch);
+    }
+    if let (Some(cycle_log), Some(cycle_check)) = (&emulator.bus.cycle_log, cycle_check) {
+        if let Some(mismatch) = cycles::compare_cycles(&test.cycles, &cycle_log.cycles, emulator.cpu.cycle_count, cycle_check) {
+            failures.push(format!("Cycle mismatch, {}", mismatch));
+        }
+    }
+    if failures.is_empty() { None } else { Some(failures.join("\n")) }
+}
+
+/// Runs a single test case, returning why it failed if it did
+pub fn run_test(emulator: &mut Emulator, test: &TestSuite, cycle_check: Option<CycleCheck>) -> Option<String> {
+    load_initial_state(emulator, test);
+    run_instruction(emulator);
+    let failure = check_results(emulator, test, cycle_check);
+    // Clears every byte the test touched, even outside its RAM lists
+    emulator.bus.rom.reset();
+    failure
+}
+
+
+#[cfg(test)]
+mod suite_tests {
+    use super::*;
+
+    /// LDA #$12 in emulation mode
+    fn lda_test(expected_a: usize, expected_ram: usize) -> TestSuite {
+        let json = format!(r#"{{
+            "name": "a9 e 1",
+            "initial": {{"pc": 4096, "s": 511, "p": 52, "a": 0, "x": 0, "y": 0, "dbr": 0, "d": 0, "pbr": 0, "e": 1,
+                "ram": [[4096, 169], [4097, 18], [32, 5]]}},
+            "final": {{"pc": 4098, "s": 511, "p": 52, "a": {}, "x": 0, "y": 0, "dbr": 0, "d": 0, "pbr": 0, "e": 1,
+                "ram": [[4096, 169], [4097, 18], [32, {}]]}},
+            "cycles": [[4096, 169, "dp-remx-"], [4097, 18, "-p-remx-"]]
+        }}"#, expected_a, expected_ram);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_format_p() {
+        assert_eq!(format_p(0x00), "$00 nvmxdizc");
+        assert_eq!(format_p(0xFF), "$FF NVMXDIZC");
+        assert_eq!(format_p(0x34), "$34 nvMXdIzc");
+        assert_eq!(format_p(0x81), "$81 NvmxdizC");
+    }
+
+    #[test]
+    fn test_run_test() {
+        let mut emulator = new_emulator(Some(CycleCheck::Strict));
+        assert_eq!(run_test(&mut emulator, &lda_test(0x12, 5), Some(CycleCheck::Strict)), None);
+    }
+
+    #[test]
+    fn test_describe_state_mismatch() {
+        let mut emulator = new_emulator(None);
+        let test = lda_test(0x34, 6);
+        load_initial_state(&mut emulator, &test);
+        run_instruction(&mut emulator);
+        assert_eq!(
+            describe_state_mismatch(&test, &emulator).unwrap(),
+            [
+                "Field Initial         Expected        Actual",
+                "A     $0000           $0034           $0012",
+                "RAM mismatches:",
+                "$000020  initial $05  expected $06  actual $05",
+            ].join("\n"),
+        );
+        assert_eq!(describe_state_mismatch(&lda_test(0x12, 5), &emulator), None);
+    }
+}
@@ -0,0 +1,44 @@
+// Debug Adapter Protocol server, so editors like VS Code can debug ROMs running in the emulator.
+// Point the editor's debug adapter at the port and launch with
+// { "program": "game.sfc", "symbols": "game.dbg", "stopOnEntry": true }
+mod protocol;
+mod session;
+
+use std::net::TcpListener;
+
+const DEFAULT_PORT: u16 = 4711;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let port = match args.get(1) {
+        Some(port) => match port.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                eprintln!("Invalid port '{}'", port);
+                std::process::exit(1);
+            },
+        },
+        None => DEFAULT_PORT,
+    };
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Could not listen on port {}: {}", port, err);
+            std::process::exit(1);
+        },
+    };
+    println!("Listening for debug adapter clients on 127.0.0.1:{}", port);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                println!("Client connected");
+                if let Err(err) = session::serve(stream) {
+                    eprintln!("Session error: {}", err);
+                }
+                println!("Client disconnected");
+            },
+            Err(err) => eprintln!("Connection failed: {}", err),
+        }
+    }
+}
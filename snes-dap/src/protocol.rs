@@ -0,0 +1,105 @@
+//! Debug Adapter Protocol framing: each message is a JSON body preceded by a
+//! `Content-Length` header and an empty line.
+//! https://microsoft.github.io/debug-adapter-protocol/overview
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+use serde_json::Value;
+
+/// Largest message body accepted, well above any request a client sends
+pub const MAX_MESSAGE_LENGTH: usize = 0x100_0000;
+
+/// Reads the next message, returns `None` when the other end closed the connection
+pub fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(content_length) = content_length else {
+        return Err(Error::new(ErrorKind::InvalidData, "Missing Content-Length header"));
+    };
+    if content_length > MAX_MESSAGE_LENGTH {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Message of {} bytes is over the {} bytes limit", content_length, MAX_MESSAGE_LENGTH),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// `readMemory` responses carry the data as base64
+pub fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[((group >> (18 - i * 6)) & 0x3F) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod dap_protocol_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_round_trip() {
+        let mut buffer = vec![];
+        write_message(&mut buffer, &json!({"seq": 1, "type": "request", "command": "threads"})).unwrap();
+        write_message(&mut buffer, &json!({"seq": 2, "type": "request", "command": "pause"})).unwrap();
+        assert!(buffer.starts_with(b"Content-Length: "));
+
+        let mut reader = &buffer[..];
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["command"], "threads");
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["seq"], 2);
+        assert!(read_message(&mut reader).unwrap().is_none());
+        assert!(read_message(&mut &b"Accept: json\r\n\r\n{}"[..]).is_err());
+    }
+
+    #[test]
+    fn test_message_length_limit() {
+        let header = format!("Content-Length: {}\r\n\r\n{{}}", MAX_MESSAGE_LENGTH + 1);
+        let err = read_message(&mut header.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let header = format!("Content-Length: {}\r\n\r\n{{}}", usize::MAX);
+        assert_eq!(read_message(&mut header.as_bytes()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!(encode_base64(&[]), "");
+        assert_eq!(encode_base64(&[0x18, 0xFB, 0xA9, 0x12]), "GPupEg==");
+        assert_eq!(encode_base64(b"Man"), "TWFu");
+        assert_eq!(encode_base64(b"Ma"), "TWE=");
+    }
+}
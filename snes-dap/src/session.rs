@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use serde_json::{json, Value};
+use snes_core::debugger::expression::{EvalContext, Expression};
+use snes_core::debugger::interface::{parse_address_range, StopReason};
+use snes_core::debugger::Debugger;
+use snes_core::emulator::Emulator;
+use snes_core::symbols::SymbolTable;
+
+use crate::protocol::{encode_base64, read_message, write_message};
+
+/// The CPU is the only thread the client sees
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const FLAGS_REFERENCE: u64 = 2;
+/// Emulator ticks run between checks for new requests while running
+const TICKS_PER_SLICE: usize = 10_000;
+const MAX_READ_MEMORY_LENGTH: u64 = 0x10000;
+
+/// One debugging session with a single client
+pub struct Session<W: Write> {
+    emulator: Emulator,
+    debugger: Debugger,
+    symbols: SymbolTable,
+    /// Relative source paths in the symbol file are resolved from here
+    source_root: PathBuf,
+    writer: W,
+    sequence: u64,
+    is_running: bool,
+    stop_on_entry: bool,
+    /// Debugger breakpoint ids set for each source path
+    source_breakpoints: HashMap<String, Vec<usize>>,
+    function_breakpoints: Vec<usize>,
+}
+
+/// Runs a session until the client disconnects.
+/// Requests are read on a separate thread so they can interrupt a running emulator.
+pub fn serve(stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    Session::new(stream).run(receiver)
+}
+
+fn format_stop_reason(reason: &StopReason) -> &'static str {
+    match reason {
+        StopReason::Breakpoint(_) => "breakpoint",
+        StopReason::Watchpoint(_, _) => "data breakpoint",
+        StopReason::Step => "step",
+    }
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            emulator: Emulator::new(),
+            debugger: Debugger::new(),
+            symbols: SymbolTable::new(),
+            source_root: PathBuf::new(),
+            writer,
+            sequence: 1,
+            is_running: false,
+            stop_on_entry: false,
+            source_breakpoints: HashMap::new(),
+            function_breakpoints: vec![],
+        }
+    }
+
+    pub fn run(mut self, requests: Receiver<Value>) -> std::io::Result<()> {
+        loop {
+            let request = if self.is_running {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(()),
+                }
+            };
+            match request {
+                Some(request) => {
+                    if !self.handle_request(&request)? {
+                        return Ok(());
+                    }
+                },
+                None => self.run_slice()?,
+            }
+        }
+    }
+
+    fn send(&mut self, mut message: Value) -> std::io::Result<()> {
+        message["seq"] = json!(self.sequence);
+        self.sequence += 1;
+        write_message(&mut self.writer, &message)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> std::io::Result<()> {
+        self.send(json!({"type": "event", "event": event, "body": body}))
+    }
+
+    fn send_stopped(&mut self, reason: &str, hit_breakpoint: Option<usize>) -> std::io::Result<()> {
+        self.is_running = false;
+        let mut body = json!({"reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true});
+        if let Some(id) = hit_breakpoint {
+            body["hitBreakpointIds"] = json!([id]);
+        }
+        self.send_event("stopped", body)
+    }
+
+    /// Returns false once the client asked to disconnect
+    fn handle_request(&mut self, request: &Value) -> std::io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsEvaluateForHovers": true,
+            })),
+            "launch" => self.launch(arguments).map(|_| json!({})),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setFunctionBreakpoints" => Ok(self.set_function_breakpoints(arguments)),
+            "setExceptionBreakpoints" => Ok(json!({"breakpoints": []})),
+            "configurationDone" => Ok(json!({})),
+            "threads" => Ok(json!({"threads": [{"id": THREAD_ID, "name": "CPU"}]})),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(json!({"scopes": [
+                {"name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false},
+                {"name": "Flags", "variablesReference": FLAGS_REFERENCE, "expensive": false},
+            ]})),
+            "variables" => Ok(self.variables(arguments["variablesReference"].as_u64().unwrap_or_default())),
+            "readMemory" => self.read_memory(arguments),
+            "evaluate" => self.evaluate(arguments["expression"].as_str().unwrap_or_default()),
+            "continue" => {
+                self.debugger.resume();
+                self.is_running = true;
+                Ok(json!({"allThreadsContinued": true}))
+            },
+            "next" | "stepIn" | "stepOut" => {
+                match command {
+                    "next" => self.debugger.step_over(&self.emulator),
+                    "stepIn" => self.debugger.step_into(),
+                    _ => self.debugger.step_out(&self.emulator),
+                }
+                self.is_running = true;
+                Ok(json!({}))
+            },
+            "pause" | "disconnect" | "terminate" => Ok(json!({})),
+            _ => Err(format!("Unsupported command '{}'", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match &result {
+            Ok(body) => response["body"] = body.clone(),
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)?;
+
+        match command {
+            "launch" if result.is_ok() => self.send_event("initialized", json!({}))?,
+            "configurationDone" if self.stop_on_entry => self.send_stopped("entry", None)?,
+            "configurationDone" => self.is_running = true,
+            "pause" => self.send_stopped("pause", None)?,
+            "disconnect" | "terminate" => {
+                self.send_event("terminated", json!({}))?;
+                return Ok(false);
+            },
+            _ => {},
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<(), String> {
+        let program = arguments["program"].as_str().ok_or("Missing 'program' launch argument")?;
+        self.emulator.bus.rom.load(program).map_err(|err| format!("Error loading the ROM: {}", err))?;
+        self.emulator.hard_reset();
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+
+        // Symbol files default to the ROM path with a symbol file extension
+        let symbols_path = match arguments["symbols"].as_str() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => SymbolTable::find_for_rom(program),
+        };
+        if let Some(path) = symbols_path {
+            self.symbols = SymbolTable::load(&path.to_string_lossy())
+                .map_err(|err| format!("Error loading symbols from {}: {}", path.display(), err))?;
+            self.source_root = match arguments["sourceRoot"].as_str() {
+                Some(root) => PathBuf::from(root),
+                None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            };
+        }
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["source"]["path"].as_str().ok_or("Missing source path")?;
+        for id in self.source_breakpoints.remove(path).unwrap_or_default() {
+            self.debugger.remove(id);
+        }
+        let mut ids = vec![];
+        let mut breakpoints = vec![];
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or_default() as u32;
+            let condition = breakpoint["condition"].as_str();
+            let addresses = self.symbols.addresses_at_line(path, line);
+            if addresses.is_empty() {
+                breakpoints.push(json!({"verified": false, "line": line, "message": "No code at this line"}));
+                continue;
+            }
+            let result: Result<Vec<usize>, String> = addresses.iter()
+                .map(|address| self.debugger.add_breakpoint(*address, *address, condition))
+                .collect();
+            match result {
+                Ok(new_ids) => {
+                    breakpoints.push(json!({"id": new_ids[0], "verified": true, "line": line}));
+                    ids.extend(new_ids);
+                },
+                Err(message) => breakpoints.push(json!({"verified": false, "line": line, "message": message})),
+            }
+        }
+        self.source_breakpoints.insert(path.to_string(), ids);
+        Ok(json!({"breakpoints": breakpoints}))
+    }
+
+    /// Function breakpoints take a label name or an address like `$808000`
+    fn set_function_breakpoints(&mut self, arguments: &Value) -> Value {
+        for id in std::mem::take(&mut self.function_breakpoints) {
+            self.debugger.remove(id);
+        }
+        let mut breakpoints = vec![];
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let name = breakpoint["name"].as_str().unwrap_or_default();
+            let result = self.symbols.address_of(name)
+                .map(|address| (address, address))
+                .ok_or_else(|| format!("Unknown label '{}'", name))
+                .or_else(|_| parse_address_range(name))
+                .and_then(|(start, end)| self.debugger.add_breakpoint(start, end, breakpoint["condition"].as_str()));
+            match result {
+                Ok(id) => {
+                    self.function_breakpoints.push(id);
+                    breakpoints.push(json!({"id": id, "verified": true}));
+                },
+                Err(message) => breakpoints.push(json!({"verified": false, "message": message})),
+            }
+        }
+        json!({"breakpoints": breakpoints})
+    }
+
+    fn run_slice(&mut self) -> std::io::Result<()> {
+        for _ in 0..TICKS_PER_SLICE {
+            if let Some(reason) = self.debugger.tick(&mut self.emulator) {
+                let hit_breakpoint = match reason {
+                    StopReason::Breakpoint(id) | StopReason::Watchpoint(id, _) => Some(id),
+                    StopReason::Step => None,
+                };
+                return self.send_stopped(format_stop_reason(&reason), hit_breakpoint);
+            }
+        }
+        Ok(())
+    }
+
+    fn source_path(&self, file: usize) -> PathBuf {
+        let path = Path::new(&self.symbols.files[file]);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.source_root.join(path)
+        }
+    }
+
+    fn stack_frame(&self, id: usize, address: u32, routine: Option<u32>) -> Value {
+        let name = match self.symbols.nearest_label(address) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+${:X}", label, offset),
+            None => match routine {
+                Some(target) => format!("${:06X}+${:X}", target, address.wrapping_sub(target) & 0xFFFF),
+                None => format!("${:06X}", address),
+            },
+        };
+        let mut frame = json!({
+            "id": id,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("0x{:06X}", address),
+        });
+        if let Some(source_line) = self.symbols.line_at(address) {
+            let path = self.source_path(source_line.file);
+            let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            frame["line"] = json!(source_line.line);
+            frame["column"] = json!(1);
+            frame["source"] = json!({"name": file_name, "path": path.to_string_lossy()});
+        }
+        frame
+    }
+
+    fn stack_trace(&self) -> Value {
+        let mut frames = vec![];
+        let mut address = self.emulator.cpu.registers.get_pc_address();
+        for call in self.emulator.cpu.call_stack.frames.iter().rev() {
+            frames.push(self.stack_frame(frames.len(), address, Some(call.target)));
+            address = call.caller;
+        }
+        frames.push(self.stack_frame(frames.len(), address, None));
+        json!({"stackFrames": frames, "totalFrames": frames.len()})
+    }
+
+    fn variables(&self, reference: u64) -> Value {
+        let registers = &self.emulator.cpu.registers;
+        let variables: Vec<(&str, String)> = match reference {
+            REGISTERS_REFERENCE => vec![
+                ("A", format!("${:04X}", registers.a)),
+                ("X", format!("${:04X}", registers.x)),
+                ("Y", format!("${:04X}", registers.y)),
+                ("S", format!("${:04X}", registers.sp)),
+                ("D", format!("${:04X}", registers.d)),
+                ("DB", format!("${:02X}", registers.dbr)),
+                ("PB", format!("${:02X}", registers.pbr)),
+                ("PC", format!("${:04X}", registers.pc)),
+                ("P", format!("${:02X}", registers.p)),
+                ("E", (registers.emulation_mode as u8).to_string()),
+            ],
+            FLAGS_REFERENCE => vec![
+                ("N", registers.get_negative_flag().to_string()),
+                ("V", registers.get_overflow_flag().to_string()),
+                ("M", registers.get_memory_select_flag().to_string()),
+                ("X", registers.get_index_register_select_flag().to_string()),
+                ("D", registers.get_decimal_mode_flag().to_string()),
+                ("I", registers.get_irq_disable_flag().to_string()),
+                ("Z", registers.get_zero_flag().to_string()),
+                ("C", registers.get_carry_flag().to_string()),
+            ],
+            _ => vec![],
+        };
+        let variables: Vec<Value> = variables.into_iter()
+            .map(|(name, value)| json!({"name": name, "value": value, "variablesReference": 0}))
+            .collect();
+        json!({"variables": variables})
+    }
+
+    /// Memory references are label names or addresses like `0x7E0000` or `$7E0000`
+    fn resolve_address(&self, reference: &str) -> Result<u32, String> {
+        match self.symbols.address_of(reference) {
+            Some(address) => Ok(address),
+            None => parse_address_range(reference).map(|(address, _)| address),
+        }
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let reference = arguments["memoryReference"].as_str().ok_or("Missing memory reference")?;
+        let offset = arguments["offset"].as_i64().unwrap_or_default();
+        let count = arguments["count"].as_u64().unwrap_or_default().min(MAX_READ_MEMORY_LENGTH);
+        let start = (self.resolve_address(reference)? as i64).wrapping_add(offset) as u32 & 0xFFFFFF;
+        let data: Vec<u8> = (0..count as u32)
+            .map(|i| self.emulator.bus.read_external(start.wrapping_add(i) & 0xFFFFFF))
+            .collect();
+        Ok(json!({"address": format!("0x{:06X}", start), "data": encode_base64(&data)}))
+    }
+
+    fn evaluate(&self, expression: &str) -> Result<Value, String> {
+        if let Some(address) = self.symbols.address_of(expression.trim()) {
+            return Ok(json!({
+                "result": format!("${:06X}", address),
+                "memoryReference": format!("0x{:06X}", address),
+                "variablesReference": 0,
+            }));
+        }
+        let context = EvalContext {
+            registers: &self.emulator.cpu.registers,
+            bus: &self.emulator.bus,
+            access: None,
+        };
+        let value = Expression::parse(expression)?.evaluate(&context);
+        Ok(json!({"result": format!("${:X} ({})", value, value), "variablesReference": 0}))
+    }
+}
+
+
+#[cfg(test)]
+mod dap_session_tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Scripted DAP client, keeps the events it reads while waiting for responses
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        sequence: u64,
+        events: Vec<Value>,
+    }
+
+    impl Client {
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            let seq = self.sequence;
+            self.sequence += 1;
+            let request = json!({"seq": seq, "type": "request", "command": command, "arguments": arguments});
+            write_message(&mut self.writer, &request).unwrap();
+            loop {
+                let message = read_message(&mut self.reader).unwrap().unwrap();
+                if message["type"] == "response" && message["request_seq"] == seq {
+                    return message;
+                }
+                self.events.push(message);
+            }
+        }
+
+        fn wait_event(&mut self, event: &str) -> Value {
+            if let Some(index) = self.events.iter().position(|e| e["event"] == event) {
+                return self.events.remove(index);
+            }
+            loop {
+                let message = read_message(&mut self.reader).unwrap().unwrap();
+                if message["event"] == event {
+                    return message;
+                }
+                self.events.push(message);
+            }
+        }
+    }
+
+    /// Writes a LoROM image and its ca65 debug info file, returns the ROM path
+    fn write_test_files(directory: &Path) -> PathBuf {
+        let program: &[u8] = &[
+            0x18,               // $8000 main: CLC
+            0xFB,               // $8001 XCE
+            0xA9, 0x12,         // $8002 LDA #$12
+            0x20, 0x0A, 0x80,   // $8004 JSR sub
+            0x4C, 0x07, 0x80,   // $8007 forever: JMP forever
+            0xA9, 0x34,         // $800A sub: LDA #$34
+            0x8D, 0x00, 0x00,   // $800C STA $0000
+            0x60,               // $800F RTS
+        ];
+        let mut rom = vec![0; 0x8000];
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x7FFC] = 0x00;
+        rom[0x7FFD] = 0x80;
+        std::fs::create_dir_all(directory).unwrap();
+        let rom_path = directory.join("game.sfc");
+        std::fs::write(&rom_path, rom).unwrap();
+
+        let mut dbg = String::from("version\tmajor=2,minor=0\n");
+        dbg.push_str("file\tid=0,name=\"src/main.s\",size=200,mtime=0x65A1B2C3,mod=0\n");
+        dbg.push_str("seg\tid=0,name=\"CODE\",start=0x008000,size=0x0010,addrsize=absolute,type=ro,oname=\"game.sfc\",ooffs=0\n");
+        // Source line of each instruction
+        let lines = [(2, 0x0), (3, 0x1), (4, 0x2), (5, 0x4), (6, 0x7), (9, 0xA), (10, 0xC), (11, 0xF)];
+        for (id, (line, offset)) in lines.iter().enumerate() {
+            dbg.push_str(&format!("line\tid={},file=0,line={},span={}\n", id, line, id));
+            dbg.push_str(&format!("span\tid={},seg=0,start={},size=1\n", id, offset));
+        }
+        dbg.push_str("sym\tid=0,name=\"main\",addrsize=absolute,scope=0,def=0,val=0x8000,seg=0,type=lab\n");
+        dbg.push_str("sym\tid=1,name=\"sub\",addrsize=absolute,scope=0,def=1,val=0x800A,seg=0,type=lab\n");
+        std::fs::write(directory.join("game.dbg"), dbg).unwrap();
+        rom_path
+    }
+
+    #[test]
+    fn test_debug_session() {
+        let directory = std::env::temp_dir().join(format!("snes-dap-test-{}", std::process::id()));
+        let rom_path = write_test_files(&directory);
+        let source_path = directory.join("src/main.s").to_string_lossy().to_string();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream).unwrap();
+        });
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            sequence: 1,
+            events: vec![],
+        };
+
+        let response = client.request("initialize", json!({"adapterID": "snes"}));
+        assert_eq!(response["success"], true);
+        assert_eq!(response["body"]["supportsReadMemoryRequest"], true);
+        let response = client.request("launch", json!({"program": rom_path, "stopOnEntry": true}));
+        assert_eq!(response["success"], true);
+        client.wait_event("initialized");
+
+        let response = client.request("setBreakpoints", json!({
+            "source": {"path": source_path},
+            "breakpoints": [{"line": 10}, {"line": 7}],
+        }));
+        let breakpoints = &response["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[1]["verified"], false);
+        client.request("configurationDone", json!({}));
+        assert_eq!(client.wait_event("stopped")["body"]["reason"], "entry");
+
+        let response = client.request("stackTrace", json!({"threadId": THREAD_ID}));
+        let frames = &response["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "main");
+        assert_eq!(frames[0]["line"], 2);
+        assert_eq!(frames[0]["source"]["path"], source_path);
+
+        client.request("continue", json!({"threadId": THREAD_ID}));
+        let stopped = client.wait_event("stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        assert_eq!(stopped["body"]["hitBreakpointIds"][0], breakpoints[0]["id"]);
+
+        let response = client.request("stackTrace", json!({"threadId": THREAD_ID}));
+        let frames = response["body"]["stackFrames"].as_array().unwrap().clone();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0]["name"].as_str(), frames[0]["line"].as_u64()), (Some("sub+$2"), Some(10)));
+        assert_eq!((frames[1]["name"].as_str(), frames[1]["line"].as_u64()), (Some("main+$4"), Some(5)));
+
+        let response = client.request("scopes", json!({"frameId": 0}));
+        let reference = response["body"]["scopes"][0]["variablesReference"].clone();
+        let response = client.request("variables", json!({"variablesReference": reference}));
+        let variables = response["body"]["variables"].as_array().unwrap();
+        let a = variables.iter().find(|v| v["name"] == "A").unwrap();
+        assert_eq!(a["value"], "$0034");
+
+        let response = client.request("readMemory", json!({"memoryReference": "0x008000", "count": 4}));
+        assert_eq!(response["body"]["data"], "GPupEg==");
+        let response = client.request("readMemory", json!({"memoryReference": "sub", "offset": 2, "count": 1}));
+        assert_eq!(response["body"]["address"], "0x00800C");
+        let response = client.request("evaluate", json!({"expression": "A + 1"}));
+        assert_eq!(response["body"]["result"], "$35 (53)");
+        assert_eq!(client.request("evaluate", json!({"expression": "A +"}))["success"], false);
+
+        client.request("stepOut", json!({"threadId": THREAD_ID}));
+        assert_eq!(client.wait_event("stopped")["body"]["reason"], "step");
+        let response = client.request("stackTrace", json!({"threadId": THREAD_ID}));
+        assert_eq!(response["body"]["stackFrames"][0]["line"], 6);
+        assert_eq!(response["body"]["totalFrames"], 1);
+
+        client.request("continue", json!({"threadId": THREAD_ID}));
+        client.request("pause", json!({"threadId": THREAD_ID}));
+        assert_eq!(client.wait_event("stopped")["body"]["reason"], "pause");
+
+        assert_eq!(client.request("disconnect", json!({}))["success"], true);
+        client.wait_event("terminated");
+        server.join().unwrap();
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 use eframe::epaint::TextureHandle;
+use snes_core::common::memory_access::MemorySpace;
+use snes_core::debugger::profiler::ProfilerColumn;
 use snes_core::ppu::registers::{
     Background as PPUBg,
     MAX_BG_WIDTH,
@@ -10,6 +12,9 @@ pub struct DebugOptions {
     pub show_debug_options_window: bool,
     pub memory_map_conrtrol_options: MemoryMapControlOptions,
     pub cpu_debug_control_options: CPUDebugControlOptions,
+    pub debugger_control_options: DebuggerControlOptions,
+    pub profiler_control_options: ProfilerControlOptions,
+    pub dma_control_options: DMAControlOptions,
     pub ppu_debug_control_options: PPUDebugControlOptions,
 }
 
@@ -20,6 +25,9 @@ impl DebugOptions {
             show_debug_options_window: false,
             memory_map_conrtrol_options: MemoryMapControlOptions::new(),
             cpu_debug_control_options: CPUDebugControlOptions::new(),
+            debugger_control_options: DebuggerControlOptions::new(),
+            profiler_control_options: ProfilerControlOptions::new(),
+            dma_control_options: DMAControlOptions::new(),
             ppu_debug_control_options: PPUDebugControlOptions::new(),
         }
     }
@@ -62,6 +70,7 @@ impl MemoryMapInputs {
 pub struct CPUDebugControlOptions {
     pub is_enabled: bool,
     pub show_registers: bool,
+    pub show_call_stack: bool,
     pub show_upcoming_instruction: bool,
 }
 
@@ -70,11 +79,77 @@ impl CPUDebugControlOptions {
         Self {
             is_enabled: true,
             show_registers: true,
+            show_call_stack: false,
             show_upcoming_instruction: true,
         }
     }
 }
 
+pub struct DebuggerControlOptions {
+    pub is_enabled: bool,
+    pub breakpoint_address: String,
+    pub breakpoint_condition: String,
+    pub watchpoint_space: MemorySpace,
+    pub watchpoint_address: String,
+    pub watchpoint_condition: String,
+    pub watch_read: bool,
+    pub watch_write: bool,
+    pub watch_execute: bool,
+    pub run_to_address: String,
+    pub error: Option<String>,
+}
+
+impl DebuggerControlOptions {
+    pub fn new() -> Self {
+        Self {
+            is_enabled: true,
+            breakpoint_address: String::new(),
+            breakpoint_condition: String::new(),
+            watchpoint_space: MemorySpace::Bus,
+            watchpoint_address: String::new(),
+            watchpoint_condition: String::new(),
+            watch_read: false,
+            watch_write: true,
+            watch_execute: false,
+            run_to_address: String::new(),
+            error: None,
+        }
+    }
+}
+
+pub struct ProfilerControlOptions {
+    pub is_enabled: bool,
+    pub sort_column: ProfilerColumn,
+    pub is_descending: bool,
+    pub message: Option<String>,
+}
+
+impl ProfilerControlOptions {
+    pub fn new() -> Self {
+        Self {
+            is_enabled: false,
+            sort_column: ProfilerColumn::Inclusive,
+            is_descending: true,
+            message: None,
+        }
+    }
+}
+
+pub struct DMAControlOptions {
+    pub is_enabled: bool,
+    /// Whether the transfer log records HDMA transfers
+    pub log_hdma_transfers: bool,
+}
+
+impl DMAControlOptions {
+    pub fn new() -> Self {
+        Self {
+            is_enabled: false,
+            log_hdma_transfers: false,
+        }
+    }
+}
+
 pub struct BgDebug {
     pub is_enabled: bool,
     pub background: PPUBg,
@@ -1,6 +1,18 @@
+use snes_core::debugger::Debugger;
+use snes_core::nwa::NwaServer;
+use snes_core::symbols::SymbolTable;
+
 pub struct EmulationState {
     pub is_paused: bool,
     pub one_tick_per_frame: bool,
+    pub rom_path: Option<String>,
+    pub debugger: Debugger,
+    /// Labels from the ROM's symbol file, empty if there is none
+    pub symbols: SymbolTable,
+    /// Serves NWA clients (trackers, QUsb2Snes bridges) when enabled from the Debug menu
+    pub nwa_server: Option<NwaServer>,
+    /// Why the NWA server could not be started, shown in the menu
+    pub nwa_error: Option<String>,
 }
 
 impl EmulationState {
@@ -8,6 +20,11 @@ impl EmulationState {
         Self {
             is_paused: true,
             one_tick_per_frame: false,
+            rom_path: None,
+            debugger: Debugger::new(),
+            symbols: SymbolTable::new(),
+            nwa_server: None,
+            nwa_error: None,
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use eframe::egui;
-use snes_core::{emulator::Emulator, cpu::instructions::mapper::map_opcode_to_instruction};
+use snes_core::{emulator::Emulator, cpu::{call_stack::{CallKind, ReturnKind}, instructions::mapper::map_opcode_to_instruction}};
+use snes_core::cpu::disasm::{self, Syntax, WidthState};
+use snes_core::symbols::SymbolTable;
 
 use crate::emu_state::{debug_options::CPUDebugControlOptions, emulation::EmulationState};
 
@@ -28,7 +32,7 @@ pub fn build_cpu_debug_controls(ctx: &egui::Context, cpu_debug_options: &mut CPU
                 }
                 let tick_button = ui.add_enabled(emulation_state.is_paused, egui::Button::new("Tick"));
                 if tick_button.clicked() {
-                    emulator.tick();
+                    emulation_state.debugger.tick(emulator);
                 }
             });
             ui.monospace("Vectors:");
@@ -45,6 +49,12 @@ pub fn build_cpu_debug_controls(ctx: &egui::Context, cpu_debug_options: &mut CPU
                 ).clicked() {
                     cpu_debug_options.show_registers = !cpu_debug_options.show_registers;
                 }
+                if ui.selectable_label(
+                    cpu_debug_options.show_call_stack,
+                    "Show call stack"
+                ).clicked() {
+                    cpu_debug_options.show_call_stack = !cpu_debug_options.show_call_stack;
+                }
                 if ui.selectable_label(
                     cpu_debug_options.show_upcoming_instruction,
                     "Show upcoming instruction"
@@ -55,7 +65,8 @@ pub fn build_cpu_debug_controls(ctx: &egui::Context, cpu_debug_options: &mut CPU
         });
 
     build_cpu_registers_window(ctx, cpu_debug_options, emulator);
-    build_upcoming_instruction_window(ctx, cpu_debug_options, emulator);
+    build_call_stack_window(ctx, cpu_debug_options, emulator);
+    build_upcoming_instruction_window(ctx, cpu_debug_options, &emulation_state.symbols, emulator);
 }
 
 fn build_cpu_registers_window(ctx: &egui::Context, cpu_debug_options: &mut CPUDebugControlOptions, emulator: &Emulator) {
@@ -83,13 +94,75 @@ fn build_cpu_registers_window(ctx: &egui::Context, cpu_debug_options: &mut CPUDe
         });
 }
 
-fn build_upcoming_instruction_window(ctx: &egui::Context, cpu_debug_options: &mut CPUDebugControlOptions, emulator: &Emulator) {
+fn format_call_kind(kind: CallKind) -> String {
+    match kind {
+        CallKind::JSR => String::from("JSR"),
+        CallKind::JSL => String::from("JSL"),
+        CallKind::Interrupt(vector) => format!("{:?}", vector).to_uppercase(),
+    }
+}
+
+fn build_call_stack_window(ctx: &egui::Context, cpu_debug_options: &mut CPUDebugControlOptions, emulator: &Emulator) {
+    egui::Window::new("Call Stack")
+        .auto_sized()
+        .open(&mut cpu_debug_options.show_call_stack)
+        .show(ctx, |ui| {
+            let call_stack = &emulator.cpu.call_stack;
+            ui.monospace("#   Type   Caller   Target   SP");
+            egui::ScrollArea::vertical().id_salt("call_stack_frames").max_height(300.0).show(ui, |ui| {
+                ui.monospace(format!("    PC            ${:06X}", emulator.cpu.registers.get_pc_address()));
+                for (depth, frame) in call_stack.frames.iter().enumerate().rev() {
+                    ui.monospace(format!(
+                        "{:<3} {:<6} ${:06X}  ${:06X}  ${:04X}",
+                        depth, format_call_kind(frame.kind), frame.caller, frame.target, frame.stack_pointer,
+                    ));
+                }
+            });
+            if call_stack.unbalanced_returns.is_empty() {
+                return;
+            }
+            ui.separator();
+            ui.colored_label(egui::Color32::YELLOW, "Unbalanced returns:");
+            egui::ScrollArea::vertical().id_salt("call_stack_unbalanced").max_height(150.0).show(ui, |ui| {
+                for unbalanced in call_stack.unbalanced_returns.iter().rev() {
+                    let kind = match unbalanced.kind {
+                        ReturnKind::RTS => "RTS",
+                        ReturnKind::RTL => "RTL",
+                        ReturnKind::RTI => "RTI",
+                    };
+                    let expected = match &unbalanced.expected {
+                        Some(frame) => format!("expected {} from ${:06X} SP:${:04X}", format_call_kind(frame.kind), frame.caller, frame.stack_pointer),
+                        None => String::from("empty stack"),
+                    };
+                    ui.monospace(format!(
+                        "{} ${:06X} -> ${:06X} SP:${:04X}, {}",
+                        kind, unbalanced.address, unbalanced.target, unbalanced.stack_pointer, expected,
+                    ));
+                }
+            });
+        });
+}
+
+fn build_upcoming_instruction_window(ctx: &egui::Context, cpu_debug_options: &mut CPUDebugControlOptions, symbols: &SymbolTable, emulator: &Emulator) {
     egui::Window::new("Upcoming CPU Instruction")
         .auto_sized()
         .min_width(150.0)
         .open(&mut cpu_debug_options.show_upcoming_instruction)
         .show(ctx, |ui| {
-            let opcode = emulator.bus.read_external(emulator.cpu.registers.get_pc_address());
+            let pc_address = emulator.cpu.registers.get_pc_address();
+            if let Some(label) = symbols.label_at(pc_address) {
+                ui.monospace(format!("{}:", label));
+            }
+            // Operands pointing at a label are printed by name
+            let mut state = WidthState::from_registers(&emulator.cpu.registers);
+            let decoded = disasm::decode(|address| emulator.bus.read_external(address), pc_address, &mut state);
+            let target_label = decoded.target.and_then(|target| Some((target, symbols.label_at(target)?)));
+            if let Some((target, label)) = target_label {
+                let labels = HashMap::from([(target, label.to_string())]);
+                ui.monospace(decoded.format(Syntax::Asar, &labels));
+                return;
+            }
+            let opcode = emulator.bus.read_external(pc_address);
             let instruction = map_opcode_to_instruction(opcode);
             ui.monospace(
                 instruction.mnemonic(&emulator.cpu.registers, &emulator.bus, opcode)
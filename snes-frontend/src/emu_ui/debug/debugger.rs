@@ -0,0 +1,198 @@
+use eframe::egui;
+use snes_core::common::memory_access::{AccessKind, MemorySpace};
+use snes_core::debugger::interface::{parse_symbolic_address_range, StopReason};
+use snes_core::emulator::Emulator;
+
+use crate::emu_state::{debug_options::DebuggerControlOptions, emulation::EmulationState};
+
+
+pub fn build_debugger_window(ctx: &egui::Context, debugger_options: &mut DebuggerControlOptions, emulation_state: &mut EmulationState, emulator: &Emulator) {
+    if !debugger_options.is_enabled {
+        return
+    }
+
+    let mut is_enabled = debugger_options.is_enabled;
+    egui::Window::new("Debugger")
+        .auto_sized()
+        .open(&mut is_enabled)
+        .show(ctx, |ui| {
+            build_step_controls(ui, debugger_options, emulation_state, emulator);
+            ui.separator();
+            build_breakpoint_controls(ui, debugger_options, emulation_state);
+            ui.separator();
+            build_watchpoint_controls(ui, debugger_options, emulation_state);
+            if let Some(error) = &debugger_options.error {
+                ui.separator();
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+    debugger_options.is_enabled = is_enabled;
+}
+
+fn format_stop_reason(reason: &StopReason) -> String {
+    match reason {
+        StopReason::Breakpoint(id) => format!("Breakpoint #{}", id),
+        StopReason::Watchpoint(id, access) => format!(
+            "Watchpoint #{}: {:?} {:?} ${:06X} = ${:02X}",
+            id, access.space, access.kind, access.address, access.value,
+        ),
+        StopReason::Step => String::from("Step"),
+    }
+}
+
+fn build_step_controls(ui: &mut egui::Ui, debugger_options: &mut DebuggerControlOptions, emulation_state: &mut EmulationState, emulator: &Emulator) {
+    let is_paused = emulation_state.is_paused;
+    ui.horizontal(|ui| {
+        if ui.add_enabled(is_paused, egui::Button::new("Step Into")).clicked() {
+            emulation_state.debugger.step_into();
+            emulation_state.is_paused = false;
+        }
+        if ui.add_enabled(is_paused, egui::Button::new("Step Over")).clicked() {
+            emulation_state.debugger.step_over(emulator);
+            emulation_state.is_paused = false;
+        }
+        if ui.add_enabled(is_paused, egui::Button::new("Step Out")).clicked() {
+            emulation_state.debugger.step_out(emulator);
+            emulation_state.is_paused = false;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Run to (address or label): ");
+        ui.text_edit_singleline(&mut debugger_options.run_to_address);
+        if ui.add_enabled(is_paused, egui::Button::new("Run")).clicked() {
+            match parse_symbolic_address_range(&debugger_options.run_to_address, &emulation_state.symbols) {
+                Ok((address, _)) => {
+                    emulation_state.debugger.run_to(address);
+                    emulation_state.is_paused = false;
+                    debugger_options.error = None;
+                },
+                Err(error) => debugger_options.error = Some(error),
+            }
+        }
+    });
+    if let Some(reason) = &emulation_state.debugger.last_stop_reason {
+        ui.monospace(format!("Last stop: {}", format_stop_reason(reason)));
+    }
+}
+
+fn build_breakpoint_controls(ui: &mut egui::Ui, debugger_options: &mut DebuggerControlOptions, emulation_state: &mut EmulationState) {
+    ui.monospace("Breakpoints:");
+    ui.horizontal(|ui| {
+        ui.label("Address or label: ");
+        ui.text_edit_singleline(&mut debugger_options.breakpoint_address);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Condition: ");
+        ui.text_edit_singleline(&mut debugger_options.breakpoint_condition);
+    });
+    if ui.button("Add breakpoint").clicked() {
+        let result = parse_symbolic_address_range(&debugger_options.breakpoint_address, &emulation_state.symbols)
+            .and_then(|(start, end)| emulation_state.debugger.add_breakpoint(
+                start,
+                end,
+                Some(&debugger_options.breakpoint_condition),
+            ));
+        debugger_options.error = result.err();
+    }
+
+    let mut removed_id = None;
+    let mut toggled = None;
+    for breakpoint in &emulation_state.debugger.breakpoints {
+        ui.horizontal(|ui| {
+            let mut is_enabled = breakpoint.is_enabled;
+            let mut text = format!("#{} ${:06X}-${:06X}", breakpoint.id, breakpoint.start, breakpoint.end);
+            if let Some(label) = emulation_state.symbols.label_at(breakpoint.start) {
+                text.push_str(&format!(" ({})", label));
+            }
+            if ui.checkbox(&mut is_enabled, text).changed() {
+                toggled = Some((breakpoint.id, is_enabled));
+            }
+            if breakpoint.condition.is_some() {
+                ui.monospace("(conditional)");
+            }
+            if ui.button("Remove").clicked() {
+                removed_id = Some(breakpoint.id);
+            }
+        });
+    }
+    apply_list_changes(emulation_state, removed_id, toggled);
+}
+
+fn build_watchpoint_controls(ui: &mut egui::Ui, debugger_options: &mut DebuggerControlOptions, emulation_state: &mut EmulationState) {
+    ui.monospace("Watchpoints:");
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Memory")
+            .selected_text(format!("{:?}", debugger_options.watchpoint_space))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut debugger_options.watchpoint_space, MemorySpace::Bus, "Bus");
+                ui.selectable_value(&mut debugger_options.watchpoint_space, MemorySpace::VRAM, "VRAM");
+                ui.selectable_value(&mut debugger_options.watchpoint_space, MemorySpace::CGRAM, "CGRAM");
+            });
+        ui.checkbox(&mut debugger_options.watch_read, "R");
+        ui.checkbox(&mut debugger_options.watch_write, "W");
+        ui.checkbox(&mut debugger_options.watch_execute, "X");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Address: ");
+        ui.text_edit_singleline(&mut debugger_options.watchpoint_address);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Condition: ");
+        ui.text_edit_singleline(&mut debugger_options.watchpoint_condition);
+    });
+    if ui.button("Add watchpoint").clicked() {
+        let mut kinds = vec![];
+        if debugger_options.watch_read {
+            kinds.push(AccessKind::Read);
+        }
+        if debugger_options.watch_write {
+            kinds.push(AccessKind::Write);
+        }
+        if debugger_options.watch_execute {
+            kinds.push(AccessKind::Execute);
+        }
+        let result = parse_symbolic_address_range(&debugger_options.watchpoint_address, &emulation_state.symbols)
+            .and_then(|(start, end)| emulation_state.debugger.add_watchpoint(
+                debugger_options.watchpoint_space,
+                start,
+                end,
+                &kinds,
+                Some(&debugger_options.watchpoint_condition),
+            ));
+        debugger_options.error = result.err();
+    }
+
+    let mut removed_id = None;
+    let mut toggled = None;
+    for watchpoint in &emulation_state.debugger.watchpoints {
+        ui.horizontal(|ui| {
+            let mut is_enabled = watchpoint.is_enabled;
+            let kinds = format!(
+                "{}{}{}",
+                if watchpoint.on_read { "R" } else { "-" },
+                if watchpoint.on_write { "W" } else { "-" },
+                if watchpoint.on_execute { "X" } else { "-" },
+            );
+            let text = format!("#{} {:?} ${:06X}-${:06X} {}", watchpoint.id, watchpoint.space, watchpoint.start, watchpoint.end, kinds);
+            if ui.checkbox(&mut is_enabled, text).changed() {
+                toggled = Some((watchpoint.id, is_enabled));
+            }
+            if watchpoint.condition.is_some() {
+                ui.monospace("(conditional)");
+            }
+            if ui.button("Remove").clicked() {
+                removed_id = Some(watchpoint.id);
+            }
+        });
+    }
+    apply_list_changes(emulation_state, removed_id, toggled);
+}
+
+fn apply_list_changes(emulation_state: &mut EmulationState, removed_id: Option<usize>, toggled: Option<(usize, bool)>) {
+    if let Some(id) = removed_id {
+        emulation_state.debugger.remove(id);
+    }
+    if let Some((id, is_enabled)) = toggled {
+        emulation_state.debugger.set_enabled(id, is_enabled);
+    }
+}
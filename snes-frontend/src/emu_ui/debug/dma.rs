@@ -0,0 +1,134 @@
+use eframe::egui;
+use snes_core::cpu::dma::{
+    AutoUpdateA, DMAAddressingMode, DMATransferLog, TransferDirection, TransferFormat, DMA,
+    DMAPX, BBADX, A1TXL, A1TXH, A1BX, DASXL, DASXH, DASBX, A2AXL, A2AXH, NTRLX,
+};
+use snes_core::emulator::Emulator;
+
+use crate::emu_state::debug_options::DMAControlOptions;
+
+const TRANSFER_LOG_CAPACITY: usize = 256;
+
+
+pub fn build_dma_window(ctx: &egui::Context, dma_options: &mut DMAControlOptions, emulator: &mut Emulator) {
+    if !dma_options.is_enabled {
+        return
+    }
+
+    let mut is_enabled = dma_options.is_enabled;
+    egui::Window::new("DMA/HDMA Channels")
+        .auto_sized()
+        .open(&mut is_enabled)
+        .show(ctx, |ui| {
+            build_channels_table(ui, &emulator.bus.dma);
+            ui.separator();
+            build_transfer_log(ui, dma_options, &mut emulator.bus.dma);
+        });
+    dma_options.is_enabled = is_enabled;
+}
+
+fn direction_name(direction: TransferDirection) -> &'static str {
+    match direction {
+        TransferDirection::AtoB => "A->B",
+        TransferDirection::BtoA => "B->A",
+    }
+}
+
+fn addressing_mode_name(addressing_mode: DMAAddressingMode) -> &'static str {
+    match addressing_mode {
+        DMAAddressingMode::Direct => "Direct",
+        DMAAddressingMode::Indirect => "Indirect",
+    }
+}
+
+fn auto_update_name(auto_update: AutoUpdateA) -> &'static str {
+    match auto_update {
+        AutoUpdateA::Increment => "Inc",
+        AutoUpdateA::Decrement => "Dec",
+        AutoUpdateA::Neither => "Fixed",
+    }
+}
+
+/// B-bus registers written for each unit, like `p p+1`
+fn transfer_format_name(format: TransferFormat) -> String {
+    let offsets: Vec<String> = format.b_bus_offsets().iter()
+        .map(|offset| if *offset == 0 { String::from("p") } else { format!("p+{}", offset) })
+        .collect();
+    offsets.join(" ")
+}
+
+fn build_channels_table(ui: &mut egui::Ui, dma: &DMA) {
+    egui::Grid::new("dma_channels_grid").striped(true).show(ui, |ui| {
+        for title in ["Ch", "DMAP", "Dir", "Mode", "A step", "Format", "A-bus", "B-bus", "Count", "HDMA", "Table", "Lines", "Indirect"] {
+            ui.monospace(title);
+        }
+        ui.end_row();
+
+        for channel in 0..8u8 {
+            let register = |register: u16| dma.read_channel_register(register, channel);
+            let params = register(DMAPX);
+            let count = dma.read_channel_word(DASXL, DASXH, channel);
+            ui.monospace(channel.to_string());
+            ui.monospace(format!("${:02X}", params));
+            ui.monospace(direction_name(TransferDirection::from_params(params)));
+            ui.monospace(addressing_mode_name(DMAAddressingMode::from_params(params)));
+            ui.monospace(auto_update_name(AutoUpdateA::from_params(params)));
+            ui.monospace(transfer_format_name(TransferFormat::from_params(params)));
+            ui.monospace(format!("${:02X}:{:04X}", register(A1BX), dma.read_channel_word(A1TXL, A1TXH, channel)));
+            ui.monospace(format!("$21{:02X}", register(BBADX)));
+            // A count of 0 transfers 64KB
+            ui.monospace(if count == 0 { String::from("65536") } else { count.to_string() });
+            let hdma_state = match (dma.is_hdma_channel_enabled(channel), dma.hdma_terminated[channel as usize]) {
+                (false, _) => "Off",
+                (true, true) => "Done",
+                (true, false) => "On",
+            };
+            ui.monospace(hdma_state);
+            ui.monospace(format!("${:02X}:{:04X}", register(A1BX), dma.read_channel_word(A2AXL, A2AXH, channel)));
+            let line_counter = register(NTRLX);
+            let repeat = if line_counter & 0x80 != 0 { " R" } else { "" };
+            ui.monospace(format!("{}{}", line_counter & 0x7F, repeat));
+            ui.monospace(format!("${:02X}:{:04X}", register(DASBX), count));
+            ui.end_row();
+        }
+    });
+}
+
+fn build_transfer_log(ui: &mut egui::Ui, dma_options: &mut DMAControlOptions, dma: &mut DMA) {
+    ui.horizontal(|ui| {
+        let mut is_logging = dma.transfer_log.is_some();
+        if ui.checkbox(&mut is_logging, "Log transfers").changed() {
+            dma.transfer_log = if is_logging { Some(DMATransferLog::new(TRANSFER_LOG_CAPACITY)) } else { None };
+        }
+        ui.checkbox(&mut dma_options.log_hdma_transfers, "Include HDMA");
+        if let Some(transfer_log) = &mut dma.transfer_log {
+            transfer_log.include_hdma = dma_options.log_hdma_transfers;
+            if ui.button("Clear").clicked() {
+                transfer_log.clear();
+            }
+        }
+    });
+    let Some(transfer_log) = &dma.transfer_log else {
+        return
+    };
+    egui::ScrollArea::vertical().id_salt("dma_transfer_log").max_height(300.0).show(ui, |ui| {
+        egui::Grid::new("dma_transfer_log_grid").striped(true).show(ui, |ui| {
+            for title in ["Frame", "Line", "Ch", "Kind", "Source", "Destination", "Length"] {
+                ui.monospace(title);
+            }
+            ui.end_row();
+
+            // Newest first
+            for entry in transfer_log.entries().rev() {
+                ui.monospace(entry.frame.to_string());
+                ui.monospace(entry.scanline.to_string());
+                ui.monospace(entry.channel_number.to_string());
+                ui.monospace(if entry.is_hdma { "HDMA" } else { "DMA" });
+                ui.monospace(format!("${:06X}", entry.source));
+                ui.monospace(format!("${:06X}", entry.destination));
+                ui.monospace(entry.length.to_string());
+                ui.end_row();
+            }
+        });
+    });
+}
@@ -6,6 +6,9 @@ use crate::emu_state::emulation::EmulationState;
 
 use super::memory_map::build_memory_map_window;
 use super::cpu::build_cpu_debug_controls;
+use super::debugger::build_debugger_window;
+use super::profiler::build_profiler_window;
+use super::dma::build_dma_window;
 use super::ppu::build_ppu_debug_controls;
 use super::ppu_graphics::build_bg_preview_windows;
 
@@ -19,6 +22,9 @@ pub fn build_all_debug_options(ctx: &egui::Context, debug_options: &mut DebugOpt
 
     build_memory_map_window(ctx, &mut debug_options.memory_map_conrtrol_options, emulator);
     build_cpu_debug_controls(ctx, &mut debug_options.cpu_debug_control_options, emulation_state, emulator);
+    build_debugger_window(ctx, &mut debug_options.debugger_control_options, emulation_state, emulator);
+    build_profiler_window(ctx, &mut debug_options.profiler_control_options, emulation_state, emulator);
+    build_dma_window(ctx, &mut debug_options.dma_control_options, emulator);
     build_ppu_debug_controls(ctx, &mut debug_options.ppu_debug_control_options, &emulator.bus.ppu.registers);
     build_bg_preview_windows(ctx, &mut debug_options.ppu_debug_control_options.backgrounds, &emulator.bus.ppu.registers);
 }
@@ -45,6 +51,24 @@ fn build_debug_options_window(ctx: &egui::Context, debug_options: &mut DebugOpti
             ).clicked() {
                 debug_options.cpu_debug_control_options.is_enabled = !debug_options.cpu_debug_control_options.is_enabled;
             }
+            if ui.selectable_label(
+                debug_options.debugger_control_options.is_enabled,
+                "Show Debugger"
+            ).clicked() {
+                debug_options.debugger_control_options.is_enabled = !debug_options.debugger_control_options.is_enabled;
+            }
+            if ui.selectable_label(
+                debug_options.profiler_control_options.is_enabled,
+                "Show Profiler"
+            ).clicked() {
+                debug_options.profiler_control_options.is_enabled = !debug_options.profiler_control_options.is_enabled;
+            }
+            if ui.selectable_label(
+                debug_options.dma_control_options.is_enabled,
+                "Show DMA/HDMA Channels"
+            ).clicked() {
+                debug_options.dma_control_options.is_enabled = !debug_options.dma_control_options.is_enabled;
+            }
             if ui.selectable_label(
                 debug_options.ppu_debug_control_options.is_enabled,
                 "Show PPU Debug Controls"
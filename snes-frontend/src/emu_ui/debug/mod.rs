@@ -1,6 +1,9 @@
 pub mod interface;
 pub mod memory_map;
 pub mod cpu;
+pub mod debugger;
+pub mod profiler;
+pub mod dma;
 pub mod ppu;
 pub mod ppu_graphics;
 pub mod common;
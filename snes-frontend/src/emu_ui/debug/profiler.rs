@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use snes_core::debugger::profiler::{Profiler, ProfilerColumn};
+use snes_core::emulator::Emulator;
+
+use crate::emu_state::{debug_options::ProfilerControlOptions, emulation::EmulationState};
+
+
+pub fn build_profiler_window(ctx: &egui::Context, profiler_options: &mut ProfilerControlOptions, emulation_state: &EmulationState, emulator: &mut Emulator) {
+    if !profiler_options.is_enabled {
+        return
+    }
+
+    let mut is_enabled = profiler_options.is_enabled;
+    egui::Window::new("Profiler")
+        .auto_sized()
+        .open(&mut is_enabled)
+        .show(ctx, |ui| {
+            build_profiler_controls(ui, profiler_options, emulation_state, emulator);
+            if let Some(profiler) = &emulator.profiler {
+                ui.separator();
+                build_profiler_table(ui, profiler_options, profiler);
+            }
+        });
+    profiler_options.is_enabled = is_enabled;
+}
+
+/// `game.sfc` exports to `game.profile.csv`
+fn csv_path(rom_path: Option<&String>) -> PathBuf {
+    match rom_path {
+        Some(rom_path) => Path::new(rom_path).with_extension("profile.csv"),
+        None => PathBuf::from("profile.csv"),
+    }
+}
+
+fn build_profiler_controls(ui: &mut egui::Ui, profiler_options: &mut ProfilerControlOptions, emulation_state: &EmulationState, emulator: &mut Emulator) {
+    ui.horizontal(|ui| {
+        let mut is_profiling = emulator.profiler.is_some();
+        if ui.checkbox(&mut is_profiling, "Profile").changed() {
+            emulator.profiler = if is_profiling { Some(Profiler::new()) } else { None };
+        }
+        let Some(profiler) = &mut emulator.profiler else {
+            return
+        };
+        if ui.button("Reset").clicked() {
+            profiler.reset();
+            profiler_options.message = None;
+        }
+        if ui.button("Export CSV").clicked() {
+            let path = csv_path(emulation_state.rom_path.as_ref());
+            profiler_options.message = Some(match profiler.save_csv(&path) {
+                Ok(_) => format!("Saved {}", path.display()),
+                Err(err) => format!("Could not save {}: {}", path.display(), err),
+            });
+        }
+        ui.monospace(format!("Frames: {}", profiler.frame_count));
+    });
+    if let Some(message) = &profiler_options.message {
+        ui.label(message);
+    }
+}
+
+fn sort_header(ui: &mut egui::Ui, profiler_options: &mut ProfilerControlOptions, title: &str, column: ProfilerColumn) {
+    let is_sorted = profiler_options.sort_column == column;
+    let text = match (is_sorted, profiler_options.is_descending) {
+        (true, true) => format!("{} v", title),
+        (true, false) => format!("{} ^", title),
+        (false, _) => title.to_string(),
+    };
+    if ui.selectable_label(is_sorted, egui::RichText::new(text).monospace()).clicked() {
+        if is_sorted {
+            profiler_options.is_descending = !profiler_options.is_descending;
+        } else {
+            profiler_options.sort_column = column;
+            profiler_options.is_descending = column != ProfilerColumn::Address;
+        }
+    }
+}
+
+fn build_profiler_table(ui: &mut egui::Ui, profiler_options: &mut ProfilerControlOptions, profiler: &Profiler) {
+    let total = profiler.total_cycles.max(1) as f64;
+    egui::ScrollArea::vertical().id_salt("profiler_table").max_height(400.0).show(ui, |ui| {
+        egui::Grid::new("profiler_grid").striped(true).show(ui, |ui| {
+            sort_header(ui, profiler_options, "Routine", ProfilerColumn::Address);
+            ui.monospace("Type");
+            sort_header(ui, profiler_options, "Calls", ProfilerColumn::Calls);
+            sort_header(ui, profiler_options, "Inclusive", ProfilerColumn::Inclusive);
+            ui.monospace("Incl/frame");
+            ui.monospace("Incl %");
+            sort_header(ui, profiler_options, "Exclusive", ProfilerColumn::Exclusive);
+            ui.monospace("Excl/frame");
+            ui.monospace("Excl %");
+            ui.end_row();
+
+            for stats in profiler.sorted_routines(profiler_options.sort_column, profiler_options.is_descending) {
+                ui.monospace(stats.name());
+                ui.monospace(stats.kind_name());
+                ui.monospace(stats.call_count.to_string());
+                ui.monospace(stats.inclusive_cycles.to_string());
+                ui.monospace(format!("{:.0}", profiler.per_frame(stats.inclusive_cycles)));
+                ui.monospace(format!("{:.2}", stats.inclusive_cycles as f64 * 100.0 / total));
+                ui.monospace(stats.exclusive_cycles.to_string());
+                ui.monospace(format!("{:.0}", profiler.per_frame(stats.exclusive_cycles)));
+                ui.monospace(format!("{:.2}", stats.exclusive_cycles as f64 * 100.0 / total));
+                ui.end_row();
+            }
+        });
+    });
+}
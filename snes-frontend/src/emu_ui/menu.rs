@@ -1,7 +1,10 @@
 use eframe::egui;
+use snes_core::debugger::cdl::{cdl_path, CodeDataLogger};
 use snes_core::emulator::Emulator;
+use snes_core::nwa::{server::DEFAULT_PORT, NwaServer};
+use snes_core::symbols::SymbolTable;
 
-use crate::emu_state::AppState;
+use crate::emu_state::{emulation::EmulationState, AppState};
 
 
 pub fn build_menu_bar(emulator: &mut Emulator, ui: &mut egui::Ui, state: &mut AppState) {
@@ -9,24 +12,123 @@ pub fn build_menu_bar(emulator: &mut Emulator, ui: &mut egui::Ui, state: &mut Ap
         ui.menu_button("Emulator", |ui| {
             if ui.button("Load ROM file").clicked() {
                 if let Some(path) = rfd::FileDialog::new().pick_file() {
-                    let picked_path = path.display().to_string();
-                    // TODO: replace this load function by an external function as each ROM may not always be LoROM
-                    match emulator.bus.rom.load(&picked_path) {
-                        Ok(_) => {
-                            emulator.hard_reset();
-                            state.emulation_state.is_paused = false;
-                            state.emulation_state.one_tick_per_frame = false;
-                            println!("Loaded ROM");
-                        },
-                        Err(err) => println!("Error loading the ROM: {}", err),
+                    match load_rom(emulator, &mut state.emulation_state, &path.display().to_string()) {
+                        Ok(_) => println!("Loaded ROM"),
+                        Err(err) => println!("{}", err),
                     };
                 }
             }
+            if ui.button("Load symbol file").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    load_symbols(&mut state.emulation_state, &path.display().to_string());
+                }
+            }
         });
         ui.menu_button("Debug", |ui| {
             if ui.button("Show Debug Menu").clicked() {
                 state.debug_options.show_debug_options_window = true;
             }
+            ui.separator();
+            let rom_path = state.emulation_state.rom_path.as_deref();
+            let mut is_logging = emulator.bus.cdl.is_some();
+            if ui.checkbox(&mut is_logging, "Code/Data Logger").changed() {
+                if is_logging {
+                    start_code_data_logger(emulator, rom_path);
+                } else {
+                    save_code_data_logger(emulator, rom_path);
+                    emulator.bus.cdl = None;
+                }
+            }
+            let can_save = emulator.bus.cdl.is_some() && rom_path.is_some();
+            if ui.add_enabled(can_save, egui::Button::new("Save .cdl file")).clicked() {
+                save_code_data_logger(emulator, rom_path);
+            }
+            ui.separator();
+            build_nwa_server_toggle(ui, &mut state.emulation_state);
         });
     });
 }
+
+/// Loads a ROM along with its symbols and .cdl file, used by the menu and NWA's LOAD_GAME
+pub fn load_rom(emulator: &mut Emulator, emulation_state: &mut EmulationState, path: &str) -> Result<(), String> {
+    // The running log goes with the current ROM data, so it's saved before that's replaced
+    save_code_data_logger(emulator, emulation_state.rom_path.as_deref());
+    // TODO: replace this load function by an external function as each ROM may not always be LoROM
+    if let Err(err) = emulator.bus.rom.load(path) {
+        // The old ROM data is gone too, its .cdl file mustn't be written again
+        emulation_state.rom_path = None;
+        return Err(format!("Error loading the ROM: {}", err));
+    }
+    emulator.hard_reset();
+    emulation_state.is_paused = false;
+    emulation_state.one_tick_per_frame = false;
+    emulation_state.symbols = SymbolTable::new();
+    if let Some(symbols_path) = SymbolTable::find_for_rom(path) {
+        load_symbols(emulation_state, &symbols_path.display().to_string());
+    }
+    emulation_state.rom_path = Some(path.to_string());
+    if emulator.bus.cdl.is_some() {
+        start_code_data_logger(emulator, emulation_state.rom_path.as_deref());
+    }
+    Ok(())
+}
+
+fn build_nwa_server_toggle(ui: &mut egui::Ui, emulation_state: &mut EmulationState) {
+    let mut is_serving = emulation_state.nwa_server.is_some();
+    if ui.checkbox(&mut is_serving, "NWA server").changed() {
+        emulation_state.nwa_error = None;
+        emulation_state.nwa_server = None;
+        if is_serving {
+            match NwaServer::start(DEFAULT_PORT) {
+                Ok(server) => emulation_state.nwa_server = Some(server),
+                Err(err) => emulation_state.nwa_error = Some(format!("Could not start the NWA server: {}", err)),
+            }
+        }
+    }
+    if let Some(server) = &emulation_state.nwa_server {
+        ui.label(format!("Listening on port {}", server.port));
+    }
+    if let Some(err) = &emulation_state.nwa_error {
+        ui.colored_label(egui::Color32::RED, err);
+    }
+}
+
+fn load_symbols(emulation_state: &mut EmulationState, path: &str) {
+    match SymbolTable::load(path) {
+        Ok(symbols) => {
+            println!("Loaded {} labels from {}", symbols.labels.len(), path);
+            emulation_state.symbols = symbols;
+        },
+        Err(err) => println!("Error loading symbols from {}: {}", path, err),
+    }
+}
+
+/// Resumes from the .cdl file next to the ROM if there is one
+fn start_code_data_logger(emulator: &mut Emulator, rom_path: Option<&str>) {
+    let rom = emulator.bus.rom.data();
+    let logger = match rom_path.map(cdl_path) {
+        Some(path) if path.exists() => match CodeDataLogger::load(&path, rom) {
+            Ok(logger) => {
+                println!("Loaded {}", path.display());
+                logger
+            },
+            Err(err) => {
+                println!("Error loading {}: {}", path.display(), err);
+                CodeDataLogger::new(rom.len())
+            },
+        },
+        _ => CodeDataLogger::new(rom.len()),
+    };
+    emulator.bus.cdl = Some(logger);
+}
+
+fn save_code_data_logger(emulator: &Emulator, rom_path: Option<&str>) {
+    let (Some(logger), Some(rom_path)) = (&emulator.bus.cdl, rom_path) else {
+        return
+    };
+    let path = cdl_path(rom_path);
+    match logger.save(&path, emulator.bus.rom.data()) {
+        Ok(_) => println!("Saved {}", path.display()),
+        Err(err) => println!("Error saving {}: {}", path.display(), err),
+    }
+}
@@ -33,11 +33,23 @@ impl eframe::App for SnesEmulatorApp {
             self.emulator.bus.ppu.registers.get_current_res(),
         );
         emu_ui::debug::build_all_debug_options(ctx, &mut self.state.debug_options, &mut self.state.emulation_state, &mut self.emulator);
-        if !self.state.emulation_state.is_paused {
-            if self.state.emulation_state.one_tick_per_frame {
-                self.emulator.tick();
+        let buttons = utils::joypad_input::get_joypad_buttons(ctx);
+        self.emulator.bus.joypad.ports[0].set_buttons(buttons);
+        let emulation_state = &mut self.state.emulation_state;
+        let pending_load = emulation_state.nwa_server.as_ref()
+            .and_then(|nwa_server| nwa_server.poll(&mut self.emulator, &mut emulation_state.is_paused));
+        if let Some(pending_load) = pending_load {
+            let result = emu_ui::menu::load_rom(&mut self.emulator, emulation_state, &pending_load.path);
+            pending_load.finish(result);
+        }
+        if !emulation_state.is_paused {
+            let stop_reason = if emulation_state.one_tick_per_frame {
+                emulation_state.debugger.tick(&mut self.emulator)
             } else {
-                self.emulator.loop_frame();
+                emulation_state.debugger.run_frame(&mut self.emulator)
+            };
+            if stop_reason.is_some() {
+                emulation_state.is_paused = true;
             }
         }
         ctx.request_repaint();
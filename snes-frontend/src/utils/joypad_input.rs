@@ -0,0 +1,30 @@
+use eframe::egui::{self, Key};
+use snes_core::joypad::controller::{
+    BUTTON_A, BUTTON_B, BUTTON_X, BUTTON_Y,
+    BUTTON_L, BUTTON_R, BUTTON_START, BUTTON_SELECT,
+    BUTTON_UP, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT,
+};
+
+const KEY_BINDINGS: [(Key, u16); 12] = [
+    (Key::ArrowUp, BUTTON_UP),
+    (Key::ArrowDown, BUTTON_DOWN),
+    (Key::ArrowLeft, BUTTON_LEFT),
+    (Key::ArrowRight, BUTTON_RIGHT),
+    (Key::X, BUTTON_A),
+    (Key::Z, BUTTON_B),
+    (Key::S, BUTTON_X),
+    (Key::A, BUTTON_Y),
+    (Key::Q, BUTTON_L),
+    (Key::W, BUTTON_R),
+    (Key::Enter, BUTTON_START),
+    (Key::Backspace, BUTTON_SELECT),
+];
+
+pub fn get_joypad_buttons(ctx: &egui::Context) -> u16 {
+    ctx.input(|input| {
+        KEY_BINDINGS
+            .iter()
+            .filter(|(key, _)| input.key_down(*key))
+            .fold(0, |buttons, (_, button)| buttons | button)
+    })
+}
@@ -1 +1,2 @@
-pub mod frame_limiter;
\ No newline at end of file
+pub mod frame_limiter;
+pub mod joypad_input;
\ No newline at end of file
@@ -0,0 +1,259 @@
+use std::path::{Path, PathBuf};
+
+use snes_core::emulator::Emulator;
+
+use crate::image;
+use crate::input::InputScript;
+
+pub const DEFAULT_FRAMES: u64 = 60;
+/// Extensions of the ROMs picked up when scanning a directory
+pub const ROM_EXTENSIONS: [&str; 2] = ["sfc", "smc"];
+
+/// Loads `rom_path` and runs it for `frames` frames, feeding controller 1 from `input`
+pub fn run_rom(rom_path: &str, frames: u64, input: &InputScript) -> std::io::Result<Emulator> {
+    let mut emulator = Emulator::new();
+    emulator.bus.rom.load(rom_path)?;
+    emulator.hard_reset();
+    for frame in 0..frames {
+        emulator.bus.joypad.ports[0].set_buttons(input.buttons_at(frame));
+        emulator.loop_frame();
+    }
+    Ok(emulator)
+}
+
+/// Golden files are either a PPM dump of the framebuffer, or its hash in hex
+pub enum Golden {
+    Hash(u64),
+    Image(Vec<u8>),
+}
+
+fn is_ppm(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("ppm"))
+}
+
+/// `None` when there is no golden file yet
+pub fn load_golden(path: &Path) -> Result<Option<Golden>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    if is_ppm(path) {
+        return image::from_ppm(&data).map(|pixels| Some(Golden::Image(pixels)));
+    }
+    let text = String::from_utf8_lossy(&data);
+    let hex = text.trim().trim_start_matches("0x");
+    u64::from_str_radix(hex, 16)
+        .map(|hash| Some(Golden::Hash(hash)))
+        .map_err(|_| format!("Invalid hash in {}", path.display()))
+}
+
+pub fn save_golden(path: &Path, framebuffer: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if is_ppm(path) {
+        image::write_ppm(path, framebuffer)
+    } else {
+        std::fs::write(path, format!("{:016x}\n", image::hash(framebuffer)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    /// No golden file to compare with
+    Missing,
+    /// The golden file was (re)written
+    Updated,
+    Error(String),
+}
+
+impl Outcome {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Outcome::Fail(_) | Outcome::Error(_))
+    }
+}
+
+pub struct RegressionCase {
+    pub name: String,
+    pub rom: PathBuf,
+    pub frames: u64,
+    pub input: Option<PathBuf>,
+    pub golden: PathBuf,
+    /// Where to also save the last frame as a PPM
+    pub dump: Option<PathBuf>,
+}
+
+pub struct CaseResult {
+    pub name: String,
+    pub hash: Option<u64>,
+    pub outcome: Outcome,
+}
+
+impl RegressionCase {
+    /// Runs the ROM and compares its last frame with the golden file,
+    /// or replaces the golden file when `update` is set
+    pub fn run(&self, update: bool) -> CaseResult {
+        let mut result = CaseResult {name: self.name.clone(), hash: None, outcome: Outcome::Pass};
+        let input = match &self.input {
+            Some(path) => match InputScript::load(&path.to_string_lossy()) {
+                Ok(input) => input,
+                Err(err) => {
+                    result.outcome = Outcome::Error(format!("Could not load {}: {}", path.display(), err));
+                    return result;
+                },
+            },
+            None => InputScript::new(),
+        };
+        let emulator = match run_rom(&self.rom.to_string_lossy(), self.frames, &input) {
+            Ok(emulator) => emulator,
+            Err(err) => {
+                result.outcome = Outcome::Error(format!("Could not load {}: {}", self.rom.display(), err));
+                return result;
+            },
+        };
+        let framebuffer = emulator.bus.ppu.framebuffer();
+        let hash = image::hash(framebuffer);
+        result.hash = Some(hash);
+        if let Some(dump) = &self.dump {
+            if let Err(err) = image::write_ppm(dump, framebuffer) {
+                result.outcome = Outcome::Error(format!("Could not write {}: {}", dump.display(), err));
+                return result;
+            }
+        }
+        result.outcome = if update {
+            match save_golden(&self.golden, framebuffer) {
+                Ok(_) => Outcome::Updated,
+                Err(err) => Outcome::Error(format!("Could not write {}: {}", self.golden.display(), err)),
+            }
+        } else {
+            match load_golden(&self.golden) {
+                Ok(None) => Outcome::Missing,
+                Ok(Some(Golden::Hash(expected))) if expected == hash => Outcome::Pass,
+                Ok(Some(Golden::Hash(expected))) => Outcome::Fail(format!("expected hash {:016x}", expected)),
+                Ok(Some(Golden::Image(expected))) => match image::count_different_pixels(&expected, framebuffer) {
+                    0 => Outcome::Pass,
+                    count => Outcome::Fail(format!("{} pixels differ", count)),
+                },
+                Err(err) => Outcome::Error(err),
+            }
+        };
+        result
+    }
+}
+
+fn find_roms(directory: &Path, roms: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        } else if path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ROM_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            roms.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// One case per ROM under `rom_directory`, with golden hashes at the same relative
+/// path under `golden_directory`. A `.input` script next to a ROM is used if present.
+pub fn discover(rom_directory: &Path, golden_directory: &Path, frames: u64) -> std::io::Result<Vec<RegressionCase>> {
+    let mut roms = vec![];
+    find_roms(rom_directory, &mut roms)?;
+    roms.sort();
+    Ok(roms.into_iter().map(|rom| {
+        let relative = rom.strip_prefix(rom_directory).unwrap_or(&rom).to_path_buf();
+        let input = rom.with_extension("input");
+        RegressionCase {
+            name: relative.to_string_lossy().replace('\\', "/"),
+            golden: golden_directory.join(&relative).with_extension("hash"),
+            input: if input.exists() { Some(input) } else { None },
+            dump: None,
+            frames,
+            rom,
+        }
+    }).collect())
+}
+
+pub fn print_table(results: &[CaseResult]) {
+    let width = results.iter().map(|r| r.name.len()).max().unwrap_or(0).max(4);
+    println!("{:<width$}  {:<16}  Result", "ROM", "Hash", width = width);
+    for result in results {
+        let hash = result.hash.map(|h| format!("{:016x}", h)).unwrap_or_default();
+        let outcome = match &result.outcome {
+            Outcome::Pass => String::from("PASS"),
+            Outcome::Fail(reason) => format!("FAIL ({})", reason),
+            Outcome::Missing => String::from("NO GOLDEN"),
+            Outcome::Updated => String::from("UPDATED"),
+            Outcome::Error(err) => format!("ERROR ({})", err),
+        };
+        println!("{:<width$}  {:<16}  {}", result.name, hash, outcome, width = width);
+    }
+    let count = |f: fn(&Outcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    println!(
+        "{} passed, {} failed, {} without golden",
+        count(|o| *o == Outcome::Pass),
+        count(Outcome::is_failure),
+        count(|o| *o == Outcome::Missing),
+    );
+}
+
+
+#[cfg(test)]
+mod headless_harness_tests {
+    use super::*;
+
+    /// LoROM that sets the backdrop color and loops forever
+    fn write_test_rom(directory: &Path, color: u8) -> PathBuf {
+        let program: &[u8] = &[
+            0x18,               // CLC
+            0xFB,               // XCE
+            0xA9, 0x00,         // LDA #$00
+            0x8D, 0x21, 0x21,   // STA CGADD
+            0xA9, color,        // LDA #color
+            0x8D, 0x22, 0x21,   // STA CGDATA
+            0x9C, 0x22, 0x21,   // STZ CGDATA
+            0xA9, 0x0F,         // LDA #$0F
+            0x8D, 0x00, 0x21,   // STA INIDISP
+            0x80, 0xFE,         // BRA *
+        ];
+        let mut rom = vec![0; 0x8000];
+        rom[..program.len()].copy_from_slice(program);
+        rom[0x7FFD] = 0x80;
+        std::fs::create_dir_all(directory).unwrap();
+        let path = directory.join(format!("color{:02x}.sfc", color));
+        std::fs::write(&path, &rom).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_golden_round_trip() {
+        let root = std::env::temp_dir().join(format!("snes-headless-test-{}", std::process::id()));
+        let roms = root.join("roms");
+        let goldens = root.join("golden");
+        write_test_rom(&roms.join("ppu"), 0x1F);
+
+        let cases = discover(&roms, &goldens, 2).unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].name, "ppu/color1f.sfc");
+        assert_eq!(cases[0].run(false).outcome, Outcome::Missing);
+        assert_eq!(cases[0].run(true).outcome, Outcome::Updated);
+        let result = cases[0].run(false);
+        assert_eq!(result.outcome, Outcome::Pass);
+
+        // Same ROM name, different picture
+        write_test_rom(&roms.join("ppu"), 0x00);
+        std::fs::rename(roms.join("ppu/color00.sfc"), roms.join("ppu/color1f.sfc")).unwrap();
+        let result = cases[0].run(false);
+        assert!(matches!(result.outcome, Outcome::Fail(_)));
+
+        // PPM goldens report how many pixels changed
+        let ppm_case = RegressionCase {golden: root.join("golden.ppm"), ..discover(&roms, &goldens, 2).unwrap().remove(0)};
+        assert_eq!(ppm_case.run(true).outcome, Outcome::Updated);
+        assert_eq!(ppm_case.run(false).outcome, Outcome::Pass);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
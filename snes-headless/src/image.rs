@@ -0,0 +1,68 @@
+//! Framebuffer hashing and PPM dumps. The framebuffer is RGBA, PPM files store RGB.
+use std::path::Path;
+
+use snes_core::ppu::registers::{MAX_TV_HEIGHT, MAX_TV_WIDTH};
+
+/// 64-bit FNV-1a
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF29CE484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001B3))
+}
+
+pub fn to_ppm(framebuffer: &[u8]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", MAX_TV_WIDTH, MAX_TV_HEIGHT).into_bytes();
+    for pixel in framebuffer.chunks_exact(4) {
+        ppm.extend(&pixel[..3]);
+    }
+    ppm
+}
+
+/// Reads back an RGBA framebuffer written by `to_ppm`
+pub fn from_ppm(data: &[u8]) -> Result<Vec<u8>, String> {
+    let header = format!("P6\n{} {}\n255\n", MAX_TV_WIDTH, MAX_TV_HEIGHT);
+    let pixels = data.strip_prefix(header.as_bytes())
+        .ok_or(format!("Expected a {}x{} binary PPM", MAX_TV_WIDTH, MAX_TV_HEIGHT))?;
+    if pixels.len() != MAX_TV_WIDTH * MAX_TV_HEIGHT * 3 {
+        return Err(String::from("Truncated PPM"));
+    }
+    Ok(pixels.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF]).collect())
+}
+
+pub fn write_ppm(filename: &Path, framebuffer: &[u8]) -> std::io::Result<()> {
+    std::fs::write(filename, to_ppm(framebuffer))
+}
+
+/// Number of pixels that differ between two RGBA framebuffers
+pub fn count_different_pixels(a: &[u8], b: &[u8]) -> usize {
+    a.chunks_exact(4).zip(b.chunks_exact(4))
+        .filter(|(a, b)| a[..3] != b[..3])
+        .count()
+}
+
+
+#[cfg(test)]
+mod headless_image_tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(b""), 0xCBF29CE484222325);
+        assert_eq!(hash(b"a"), 0xAF63DC4C8601EC8C);
+    }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let mut framebuffer = vec![0xFF; MAX_TV_WIDTH * MAX_TV_HEIGHT * 4];
+        framebuffer[0] = 0x12;
+        framebuffer[4 * 100 + 2] = 0x34;
+        let ppm = to_ppm(&framebuffer);
+        assert!(ppm.starts_with(b"P6\n512 448\n255\n"));
+        let read = from_ppm(&ppm).unwrap();
+        assert_eq!(read, framebuffer);
+
+        let mut other = read.clone();
+        other[1] = 0x00;
+        other[3] = 0x00;
+        assert_eq!(count_different_pixels(&read, &other), 1);
+        assert!(from_ppm(&ppm[..100]).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Scripted controller input, one change per line:
+//!
+//! ```text
+//! # frame  buttons (controller 1)
+//! 60       START
+//! 62       none
+//! 120      A+RIGHT
+//! ```
+//!
+//! Buttons are held from the given frame until the next line.
+use snes_core::joypad::controller::{
+    BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_L, BUTTON_LEFT, BUTTON_R, BUTTON_RIGHT,
+    BUTTON_SELECT, BUTTON_START, BUTTON_UP, BUTTON_X, BUTTON_Y,
+};
+
+fn parse_button(name: &str) -> Result<u16, String> {
+    match name.to_uppercase().as_str() {
+        "A" => Ok(BUTTON_A),
+        "B" => Ok(BUTTON_B),
+        "X" => Ok(BUTTON_X),
+        "Y" => Ok(BUTTON_Y),
+        "L" => Ok(BUTTON_L),
+        "R" => Ok(BUTTON_R),
+        "START" => Ok(BUTTON_START),
+        "SELECT" => Ok(BUTTON_SELECT),
+        "UP" => Ok(BUTTON_UP),
+        "DOWN" => Ok(BUTTON_DOWN),
+        "LEFT" => Ok(BUTTON_LEFT),
+        "RIGHT" => Ok(BUTTON_RIGHT),
+        "NONE" => Ok(0),
+        _ => Err(format!("Unknown button '{}'", name)),
+    }
+}
+
+pub struct InputScript {
+    /// (frame, buttons), sorted by frame
+    changes: Vec<(u64, u16)>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self {
+            changes: vec![],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut changes = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue
+            }
+            let error = |err: String| format!("Line {}: {}", number + 1, err);
+            let (frame, buttons) = line.split_once(char::is_whitespace)
+                .ok_or_else(|| error(String::from("Expected a frame and buttons")))?;
+            let frame = frame.parse::<u64>().map_err(|_| error(format!("Invalid frame '{}'", frame)))?;
+            let buttons = buttons.split('+')
+                .map(|button| parse_button(button.trim()))
+                .collect::<Result<Vec<u16>, String>>()
+                .map_err(error)?
+                .into_iter()
+                .fold(0, |all, button| all | button);
+            changes.push((frame, buttons));
+        }
+        changes.sort_by_key(|(frame, _)| *frame);
+        Ok(Self {changes})
+    }
+
+    pub fn load(filename: &str) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(filename)?;
+        Self::parse(&text).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Buttons held during `frame`
+    pub fn buttons_at(&self, frame: u64) -> u16 {
+        self.changes.iter()
+            .take_while(|(start, _)| *start <= frame)
+            .last()
+            .map_or(0, |(_, buttons)| *buttons)
+    }
+}
+
+impl Default for InputScript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod headless_input_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = InputScript::parse("# title screen\n60 START\n62 none\n120 a+Right # walk\n").unwrap();
+        assert_eq!(script.buttons_at(0), 0);
+        assert_eq!(script.buttons_at(60), BUTTON_START);
+        assert_eq!(script.buttons_at(61), BUTTON_START);
+        assert_eq!(script.buttons_at(62), 0);
+        assert_eq!(script.buttons_at(500), BUTTON_A | BUTTON_RIGHT);
+        assert!(InputScript::parse("10 JUMP\n").is_err());
+        assert!(InputScript::parse("soon A\n").is_err());
+    }
+}
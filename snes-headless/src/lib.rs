@@ -0,0 +1,8 @@
+//! Runs ROMs without a window and checks what they rendered against golden files,
+//! for catching PPU/CPU/DMA regressions with test ROM suites.
+pub mod input;
+pub mod image;
+pub mod harness;
+
+pub use harness::{run_rom, RegressionCase, CaseResult, Outcome};
+pub use input::InputScript;
@@ -0,0 +1,86 @@
+// Headless regression runner.
+//
+// Usage: snes-headless <rom file or directory> [options]
+//   --frames <n>          frames to run before capturing, 60 by default
+//   --input <file>        scripted controller input (single ROM only, see `input.rs`)
+//   --golden <path>       golden file (single ROM) or directory (default: ./golden)
+//   --update              write the golden files instead of comparing
+//   --dump <file.ppm>     also save the last frame (single ROM only)
+use std::path::{Path, PathBuf};
+
+use snes_headless::harness::{self, RegressionCase, DEFAULT_FRAMES};
+
+struct Arguments {
+    path: String,
+    frames: u64,
+    input: Option<PathBuf>,
+    golden: Option<PathBuf>,
+    update: bool,
+    dump: Option<PathBuf>,
+}
+
+fn parse_arguments(args: &[String]) -> Result<Arguments, String> {
+    let mut arguments = Arguments {
+        path: String::new(),
+        frames: DEFAULT_FRAMES,
+        input: None,
+        golden: None,
+        update: false,
+        dump: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--frames" => arguments.frames = value(arg)?.parse().map_err(|_| String::from("Invalid frame count"))?,
+            "--input" => arguments.input = Some(PathBuf::from(value(arg)?)),
+            "--golden" => arguments.golden = Some(PathBuf::from(value(arg)?)),
+            "--dump" => arguments.dump = Some(PathBuf::from(value(arg)?)),
+            "--update" => arguments.update = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => arguments.path = arg.clone(),
+        }
+    }
+    if arguments.path.is_empty() {
+        return Err(String::from("A ROM file or directory must be provided"));
+    }
+    Ok(arguments)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arguments = match parse_arguments(&args) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        },
+    };
+
+    let path = Path::new(&arguments.path);
+    let cases = if path.is_dir() {
+        let golden_directory = arguments.golden.clone().unwrap_or_else(|| PathBuf::from("golden"));
+        match harness::discover(path, &golden_directory, arguments.frames) {
+            Ok(cases) => cases,
+            Err(err) => {
+                eprintln!("Could not read {}: {}", arguments.path, err);
+                std::process::exit(1);
+            },
+        }
+    } else {
+        vec![RegressionCase {
+            name: arguments.path.clone(),
+            rom: path.to_path_buf(),
+            frames: arguments.frames,
+            input: arguments.input.clone(),
+            golden: arguments.golden.clone().unwrap_or_else(|| path.with_extension("hash")),
+            dump: arguments.dump.clone(),
+        }]
+    };
+
+    let results: Vec<_> = cases.iter().map(|case| case.run(arguments.update)).collect();
+    harness::print_table(&results);
+    if results.iter().any(|r| r.outcome.is_failure()) {
+        std::process::exit(1);
+    }
+}